@@ -0,0 +1,244 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::Parser;
+
+use rfuse_core::{block_cache::BlockCacheOptions, sys_fs::CacheOptions};
+
+use crate::logging::LogLevel;
+
+#[derive(Debug, Parser)]
+#[command(
+    author,
+    name = "rfusec",
+    about = "rfuse: A fuse client.",
+    after_help = "For help with a specific command, see: `rfusec help <command>`."
+)]
+#[command(version)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+    #[clap(flatten)]
+    pub log_level_args: LogLevelArgs,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    Mount(MountCommand),
+}
+
+#[derive(Parser, Debug)]
+#[command(version, author, about, long_about = None, about = "Mount a folder served by rfuses.")]
+pub struct MountCommand {
+    #[clap(help = "Server address, e.g. 127.0.0.1:7878")]
+    pub addr: String,
+
+    #[clap(help = "Mount point address")]
+    pub mount: PathBuf,
+
+    #[clap(short, long, help = "Read only")]
+    pub read_only: bool,
+
+    #[clap(default_value = "rfusec", help = "Set the name of the source in mtab.")]
+    pub fs_name: String,
+
+    #[clap(long, help = "Shared secret sent to the server in the handshake")]
+    pub token: Option<String>,
+
+    #[clap(long, help = "Disable extended attributes")]
+    pub no_xattr: bool,
+
+    #[clap(
+        long,
+        default_value_t = 30,
+        value_name = "SECONDS",
+        help = "Timeout for connecting and for each request to the server, 0 waits forever"
+    )]
+    pub timeout: u64,
+
+    #[clap(
+        long,
+        default_value_t = 4,
+        value_name = "N",
+        help = "Worker threads for file reads and writes, 0 disables the pool"
+    )]
+    pub threads: usize,
+
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+
+    #[clap(flatten)]
+    pub block_cache: BlockCacheArgs,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CacheArgs {
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may cache file attributes"
+    )]
+    pub attr_ttl: Duration,
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may cache directory entries"
+    )]
+    pub entry_ttl: Duration,
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may remember that a name does not exist"
+    )]
+    pub negative_ttl: Duration,
+    #[arg(
+        long,
+        help_heading = "Kernel cache",
+        help = "Read and write through the kernel page cache"
+    )]
+    pub no_direct_io: bool,
+    #[arg(
+        long,
+        requires = "no_direct_io",
+        help_heading = "Kernel cache",
+        help = "Keep cached file pages when a file is opened again"
+    )]
+    pub keep_cache: bool,
+    #[arg(
+        long,
+        help_heading = "Kernel cache",
+        help = "Buffer writes in the kernel and flush them in batches"
+    )]
+    pub writeback_cache: bool,
+}
+
+// TTL 以秒为单位, 可以是小数
+fn parse_ttl(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("invalid TTL `{}`, expected seconds >= 0", s)),
+    }
+}
+
+impl From<&CacheArgs> for CacheOptions {
+    fn from(args: &CacheArgs) -> Self {
+        Self {
+            attr_ttl: args.attr_ttl,
+            entry_ttl: args.entry_ttl,
+            negative_ttl: args.negative_ttl,
+            direct_io: !args.no_direct_io,
+            keep_cache: args.keep_cache,
+            writeback_cache: args.writeback_cache,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BlockCacheArgs {
+    #[arg(
+        long,
+        default_value_t = 64,
+        value_name = "MIB",
+        help_heading = "Block cache",
+        help = "Memory for cached file blocks, 0 disables the block cache"
+    )]
+    pub cache_memory: u64,
+    #[arg(
+        long,
+        default_value_t = 128,
+        value_name = "KIB",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help_heading = "Block cache",
+        help = "Size of each cached block"
+    )]
+    pub block_size: u64,
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_name = "BLOCKS",
+        help_heading = "Block cache",
+        help = "Blocks to read ahead when a file is read sequentially"
+    )]
+    pub read_ahead: u64,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help_heading = "Block cache",
+        help = "Keep blocks evicted from memory in this directory"
+    )]
+    pub cache_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        value_name = "MIB",
+        requires = "cache_dir",
+        help_heading = "Block cache",
+        help = "Disk space for cached file blocks"
+    )]
+    pub cache_disk: u64,
+}
+
+impl From<&BlockCacheArgs> for BlockCacheOptions {
+    fn from(args: &BlockCacheArgs) -> Self {
+        Self {
+            block_size: args.block_size * 1024,
+            memory_budget: args.cache_memory * 1024 * 1024,
+            disk_dir: args.cache_dir.clone(),
+            disk_budget: args.cache_disk * 1024 * 1024,
+            read_ahead: args.read_ahead,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct LogLevelArgs {
+    /// Enable verbose logging.
+    #[arg(
+        short,
+        long,
+        global = true,
+        group = "verbosity",
+        help_heading = "Log levels"
+    )]
+    pub verbose: bool,
+    /// Print diagnostics, but nothing else.
+    #[arg(
+        short,
+        long,
+        global = true,
+        group = "verbosity",
+        help_heading = "Log levels"
+    )]
+    pub quiet: bool,
+    /// Disable all logging (but still exit with status code "1" upon detecting diagnostics).
+    #[arg(
+        short,
+        long,
+        global = true,
+        group = "verbosity",
+        help_heading = "Log levels"
+    )]
+    pub silent: bool,
+}
+
+impl From<&LogLevelArgs> for LogLevel {
+    fn from(args: &LogLevelArgs) -> Self {
+        if args.silent {
+            Self::Silent
+        } else if args.quiet {
+            Self::Quiet
+        } else if args.verbose {
+            Self::Verbose
+        } else {
+            Self::Default
+        }
+    }
+}
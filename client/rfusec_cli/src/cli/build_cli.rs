@@ -0,0 +1,7 @@
+use clap::Parser;
+
+use super::args::Args;
+
+pub fn build_cli() -> Args {
+    Args::parse()
+}
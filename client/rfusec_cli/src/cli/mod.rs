@@ -0,0 +1,4 @@
+pub(crate) mod args;
+
+mod build_cli;
+pub use build_cli::*;
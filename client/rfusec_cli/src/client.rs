@@ -0,0 +1,166 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    time::Duration,
+};
+
+use log::{debug, error, info, warn};
+use rfuse_protocol::{
+    codec::{read_frame, write_frame, ProtocolError},
+    message::{Request, Response},
+    PROTOCOL_VERSION,
+};
+
+/// 默认的连接和读写超时
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 与 rfuses 服务端的连接, 请求是按顺序一发一收的
+///
+/// 连接出错之后会丢弃这条连接, 下一个请求重新连接并握手
+pub struct RemoteClient {
+    addrs: Vec<SocketAddr>,
+    token: String,
+    timeout: Option<Duration>,
+    inner: Mutex<ClientInner>,
+}
+
+struct ClientInner {
+    // 上一个请求出错之后为 None
+    stream: Option<TcpStream>,
+    next_id: u64,
+}
+
+impl RemoteClient {
+    /// 连接服务端并完成握手, 服务端没有配置 token 时 token 可以为空
+    pub fn connect<A: ToSocketAddrs>(addr: A, token: &str) -> Result<Self, ProtocolError> {
+        Self::connect_timeout(addr, token, Some(DEFAULT_TIMEOUT))
+    }
+
+    /// 同 [`RemoteClient::connect`], timeout 为 None 时一直等待服务端的响应
+    pub fn connect_timeout<A: ToSocketAddrs>(
+        addr: A,
+        token: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Self, ProtocolError> {
+        let client = RemoteClient {
+            addrs: addr.to_socket_addrs()?.collect(),
+            token: token.to_string(),
+            timeout,
+            inner: Mutex::new(ClientInner {
+                stream: None,
+                next_id: 1,
+            }),
+        };
+        let stream = client.open(1)?;
+        let mut inner = client.inner.lock().unwrap();
+        inner.stream = Some(stream);
+        inner.next_id = 2;
+        drop(inner);
+        Ok(client)
+    }
+
+    // 建立一条新的连接并握手, 握手使用请求 id
+    fn open(&self, id: u64) -> Result<TcpStream, ProtocolError> {
+        let mut stream = self.dial()?;
+        let request = Request::Handshake {
+            version: PROTOCOL_VERSION,
+            token: self.token.clone(),
+        };
+        match exchange(&mut stream, id, &request)? {
+            Response::Handshake { version } if version == PROTOCOL_VERSION => {
+                debug!(
+                    "[RemoteClient][open] handshake success, version: {}",
+                    version
+                );
+                Ok(stream)
+            }
+            Response::Handshake { version } => {
+                error!(
+                    "[RemoteClient][open] server protocol version {} does not match {}",
+                    version, PROTOCOL_VERSION
+                );
+                Err(ProtocolError::VersionMismatch(version))
+            }
+            // 协议版本或 token 不一致, 服务端会关闭连接
+            Response::Error(e) => {
+                error!("[RemoteClient][open] handshake rejected: {}", e);
+                Err(ProtocolError::HandshakeRejected)
+            }
+            r => {
+                error!("[RemoteClient][open] unexpected response: {:?}", r);
+                Err(ProtocolError::UnexpectedResponse)
+            }
+        }
+    }
+
+    // 依次尝试解析出来的地址
+    fn dial(&self) -> Result<TcpStream, ProtocolError> {
+        let mut last = io::Error::new(io::ErrorKind::InvalidInput, "no address to connect");
+        for addr in &self.addrs {
+            let stream = match self.timeout {
+                Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match stream {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_read_timeout(self.timeout)?;
+                    stream.set_write_timeout(self.timeout)?;
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    debug!("[RemoteClient][dial] connect to {} failed: {}", addr, e);
+                    last = e;
+                }
+            }
+        }
+        Err(last.into())
+    }
+
+    /// 发送一个请求并等待对应的响应
+    ///
+    /// 读写出错或者响应对不上请求时, 这条连接上可能还留着没读完的数据,
+    /// 所以直接丢弃, 下一个请求重新连接. 出错的请求不会重发, 由调用者决定是否重试
+    pub fn call(&self, request: Request) -> Result<Response, ProtocolError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut stream = match inner.stream.take() {
+            Some(stream) => stream,
+            None => {
+                let id = inner.next_id;
+                inner.next_id += 1;
+                let stream = self.open(id)?;
+                info!("[RemoteClient][call] reconnected to server");
+                stream
+            }
+        };
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        let response = exchange(&mut stream, id, &request);
+        match response {
+            Ok(_) => inner.stream = Some(stream),
+            Err(ref e) => warn!("[RemoteClient][call] drop the connection: {}", e),
+        }
+        response
+    }
+}
+
+// 在连接上发送一帧并读取对应的响应
+fn exchange(stream: &mut TcpStream, id: u64, request: &Request) -> Result<Response, ProtocolError> {
+    write_frame(stream, &request.encode(id))?;
+    let payload = match read_frame(stream)? {
+        Some(p) => p,
+        // 服务端关闭了连接
+        None => return Err(ProtocolError::UnexpectedEof),
+    };
+    let (response_id, response) = Response::decode(&payload)?;
+    if response_id != id {
+        error!(
+            "[RemoteClient][call] response id {} does not match request id {}",
+            response_id, id
+        );
+        return Err(ProtocolError::UnexpectedResponse);
+    }
+    Ok(response)
+}
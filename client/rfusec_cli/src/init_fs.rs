@@ -0,0 +1,62 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use fuser::FUSE_ROOT_ID;
+use log::{error, info};
+use rfuse_core::{
+    inode::{root_node, Inode},
+    remote_fs::{InitFsFuncType, RemoteFileInitializeError, RemoteFileManager},
+};
+use rfuse_protocol::message::{RemoteAttr, Request, Response};
+
+use crate::client::RemoteClient;
+
+fn get_attr(client: &RemoteClient, path: &Path) -> Result<RemoteAttr, RemoteFileInitializeError> {
+    match client.call(Request::GetAttr {
+        path: path.to_path_buf(),
+    }) {
+        Ok(Response::Attr(attr)) => Ok(attr),
+        Ok(r) => {
+            error!(
+                "[remote_init_fs][get_attr] {} unexpected response: {:?}",
+                path.display(),
+                r
+            );
+            Err(RemoteFileInitializeError::Error)
+        }
+        Err(e) => {
+            error!(
+                "[remote_init_fs][get_attr] {} failed: {}",
+                path.display(),
+                e
+            );
+            Err(RemoteFileInitializeError::Error)
+        }
+    }
+}
+
+// 远程挂载的初始化函数, 只插入根节点, 文件夹的内容在第一次访问时由 RFuseFS 通过 RemoteFS 读取
+pub fn remote_init_fs(client: Arc<RemoteClient>) -> Box<InitFsFuncType> {
+    Box::new(
+        move |_file_manager: &mut RemoteFileManager,
+              inodes: &mut HashMap<u64, Inode>,
+              _source_dir: PathBuf| {
+            let root_attr = get_attr(&client, Path::new("/"))?;
+            let mut root = root_node(
+                "",
+                "/".to_string(),
+                root_attr.permissions,
+                root_attr.uid,
+                root_attr.gid,
+            );
+            root.loaded = false;
+            inodes.insert(FUSE_ROOT_ID, root);
+
+            info!("File system init success.");
+            Ok(())
+        },
+    )
+}
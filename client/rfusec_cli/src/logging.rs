@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use colored::Colorize;
+use directories::ProjectDirs;
+use fern;
+use log::Level;
+
+pub fn init_log(level: &LogLevel) {
+    let mut logger = fern::Dispatch::new()
+        .format(|out, message, record| match record.level() {
+            Level::Error => {
+                out.finish(format_args!(
+                    "[{}:{}][{}] {}",
+                    record.file().unwrap_or("unknown"),
+                    record.line().unwrap_or(0),
+                    "ERROR".red(),
+                    message
+                ));
+            }
+            Level::Warn => {
+                out.finish(format_args!(
+                    "[{}:{}][{}] {}",
+                    record.file().unwrap_or("unknown"),
+                    record.line().unwrap_or(0),
+                    "WARNING".yellow(),
+                    message
+                ));
+            }
+            Level::Info | Level::Debug | Level::Trace => {
+                out.finish(format_args!(
+                    "{}[{}][{}:{}][{}] {}",
+                    chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                    record.target(),
+                    record.file().unwrap_or("unknown"),
+                    record.line().unwrap_or(0),
+                    match record.level() {
+                        Level::Info => "INFO".green(),
+                        Level::Debug => "DEBUG".blue(),
+                        Level::Trace => "Trace".purple(),
+                        Level::Error => "Error".red(),
+                        Level::Warn => "Warn".yellow(),
+                    },
+                    message
+                ));
+            }
+        })
+        .level(level.level_filter())
+        .level_for("fuser", log::LevelFilter::Warn);
+
+    // debug 模式下输出到 stderr
+    if cfg!(debug_assertions) {
+        logger = logger.chain(std::io::stdout());
+    } else {
+        // 查看文件夹是否存在
+        let base_path = ProjectDirs::from("", "", "rfuse").unwrap();
+
+        let log_dir_path = {
+            if let Some(runtime_dir) = base_path.runtime_dir() {
+                let mut log_path = runtime_dir.to_owned();
+                log_path.push("logs/");
+                log_path
+            } else {
+                PathBuf::from("./logs/")
+            }
+        };
+
+        // 创建文件夹
+        if !log_dir_path.is_dir() {
+            std::fs::create_dir_all(&log_dir_path).unwrap();
+        }
+        let log_dir = fern::DateBased::new(log_dir_path, "%Y-%m-%d-rfusec.log");
+        logger = logger.chain(log_dir);
+    }
+
+    logger.apply().unwrap();
+}
+
+#[derive(Debug, Default, PartialOrd, Ord, PartialEq, Eq, Copy, Clone)]
+pub enum LogLevel {
+    /// No output ([`log::LevelFilter::Off`]).
+    Silent,
+    /// Only show lint violations, with no decorative output
+    /// ([`log::LevelFilter::Off`]).
+    Quiet,
+    /// All user-facing output ([`log::LevelFilter::Info`]).
+    #[default]
+    Default,
+    /// All user-facing output ([`log::LevelFilter::Debug`]).
+    Verbose,
+}
+
+impl LogLevel {
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    const fn level_filter(&self) -> log::LevelFilter {
+        match self {
+            LogLevel::Default => log::LevelFilter::Info,
+            LogLevel::Verbose => log::LevelFilter::Debug,
+            LogLevel::Quiet => log::LevelFilter::Off,
+            LogLevel::Silent => log::LevelFilter::Off,
+        }
+    }
+}
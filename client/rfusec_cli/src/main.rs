@@ -1,3 +1,19 @@
-fn main() {
-    println!("Hello, world!");
+use log::error;
+use rfusec_cli::cli::build_cli;
+use rfusec_cli::run::run;
+use rfusec_cli::ExitStatus;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // 解析命令行参数
+    let cli = build_cli();
+
+    match run(cli).await {
+        Ok(exit_code) => exit_code.into(),
+        Err(e) => {
+            error!("[main] run error: {:?}", e);
+            ExitStatus::Error.into()
+        }
+    }
 }
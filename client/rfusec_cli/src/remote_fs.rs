@@ -0,0 +1,390 @@
+use std::{
+    ffi::OsString,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use log::{debug, error};
+use nix::libc;
+use rfuse_core::{
+    inode::{Inode, InodeAttributes},
+    tmp_file::{StatFs, TmpFile, TmpFileError, TmpFileTrait},
+};
+use rfuse_protocol::{
+    codec::MAX_DATA_SIZE,
+    message::{RemoteAttr, Request, Response},
+};
+
+use crate::client::RemoteClient;
+
+/// 通过网络访问 rfuses 服务端上的文件, 挂载时 source_dir 为空, 所以 `tf.path` 就是服务端的相对路径
+pub struct RemoteFS(pub Arc<RemoteClient>);
+
+fn remote_path(tf: &TmpFile) -> PathBuf {
+    tf.full_path()
+}
+
+impl RemoteFS {
+    // 发送请求, 网络错误和服务端返回的错误都会转换成 TmpFileError
+    fn request(&self, request: Request, err: TmpFileError) -> Result<Response, TmpFileError> {
+        match self.0.call(request) {
+            Ok(Response::Error(e)) => Err(e),
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!("[RemoteFS][request] {} failed: {}", err, e);
+                Err(err)
+            }
+        }
+    }
+
+    fn request_ok(&self, request: Request, err: TmpFileError) -> Result<(), TmpFileError> {
+        match self.request(request, err)? {
+            Response::Ok => Ok(()),
+            r => {
+                error!("[RemoteFS][request_ok] unexpected response: {:?}", r);
+                Err(err)
+            }
+        }
+    }
+
+    fn request_inode(
+        &self,
+        tf: &TmpFile,
+        request: Request,
+        err: TmpFileError,
+    ) -> Result<Inode, TmpFileError> {
+        match self.request(request, err)? {
+            // parent_ino 由 RFuseFS 负责维护
+            Response::Attr(attr) => Ok(attr.to_inode(
+                0,
+                tf.file_name.clone(),
+                tf.path.clone().into_os_string(),
+            )),
+            r => {
+                error!("[RemoteFS][request_inode] unexpected response: {:?}", r);
+                Err(err)
+            }
+        }
+    }
+
+    fn request_data(&self, request: Request, err: TmpFileError) -> Result<Vec<u8>, TmpFileError> {
+        match self.request(request, err)? {
+            Response::Data(data) => Ok(data),
+            r => {
+                error!("[RemoteFS][request_data] unexpected response: {:?}", r);
+                Err(err)
+            }
+        }
+    }
+
+    // 分段读取整个文件, 每段都不超过服务端单次返回的上限
+    fn read_chunked(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let path = remote_path(tf);
+        let size = match self.request(
+            Request::GetAttr { path: path.clone() },
+            TmpFileError::ReadError,
+        )? {
+            Response::Attr(attr) => attr.size,
+            r => {
+                error!("[RemoteFS][read_chunked] unexpected response: {:?}", r);
+                return Err(TmpFileError::ReadError);
+            }
+        };
+        let mut data = Vec::new();
+        while (data.len() as u64) < size {
+            let offset = data.len() as u64;
+            let chunk = self.request_data(
+                Request::Read {
+                    path: path.clone(),
+                    offset,
+                    size: (size - offset).min(MAX_DATA_SIZE as u64) as u32,
+                },
+                TmpFileError::ReadError,
+            )?;
+            if chunk.is_empty() {
+                break;
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+impl TmpFileTrait for RemoteFS {
+    fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::Write {
+                path: remote_path(tf),
+                offset,
+                data: data.to_vec(),
+                write_time: *write_time,
+            },
+            TmpFileError::WriteError,
+        )
+    }
+
+    fn get_attr(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.request_inode(
+            tf,
+            Request::GetAttr {
+                path: remote_path(tf),
+            },
+            TmpFileError::ReadError,
+        )
+    }
+
+    fn read_dir(&self, tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        let dir_path = remote_path(tf);
+        match self.request(
+            Request::ReadDir {
+                path: dir_path.clone(),
+            },
+            TmpFileError::ReadError,
+        )? {
+            Response::Entries(entries) => {
+                let mut path = dir_path.into_os_string();
+                if !path.as_bytes().ends_with(b"/") {
+                    path.push("/");
+                }
+                Ok(entries
+                    .into_iter()
+                    .map(|entry| entry.attr.to_inode(0, entry.name, path.clone()))
+                    .collect())
+            }
+            r => {
+                error!("[RemoteFS][read_dir] unexpected response: {:?}", r);
+                Err(TmpFileError::ReadError)
+            }
+        }
+    }
+
+    fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        match self.request_data(
+            Request::ReadAll {
+                path: remote_path(tf),
+            },
+            TmpFileError::ReadError,
+        ) {
+            // 文件超过单帧的大小时服务端返回 EFBIG
+            Err(TmpFileError::Errno(libc::EFBIG)) => {
+                debug!(
+                    "[RemoteFS][read_all] {} is too large, read in chunks",
+                    remote_path(tf).display()
+                );
+                self.read_chunked(tf)
+            }
+            r => r,
+        }
+    }
+
+    fn read_exact(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        let data = self.request_data(
+            Request::Read {
+                path: remote_path(tf),
+                offset,
+                size: buf.len() as u32,
+            },
+            TmpFileError::ReadError,
+        )?;
+        if data.len() != buf.len() {
+            error!(
+                "[RemoteFS][read_exact] expect {} bytes, got {}",
+                buf.len(),
+                data.len()
+            );
+            return Err(TmpFileError::ReadError);
+        }
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn set_attr(&self, tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::SetAttr {
+                path: remote_path(tf),
+                attr: RemoteAttr::from_attributes(0, attr),
+            },
+            TmpFileError::SetAttrError,
+        )
+    }
+
+    fn rename(
+        &self,
+        tf: &TmpFile,
+        new_path: &Path,
+        rename_time: &SystemTime,
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::Rename {
+                path: remote_path(tf),
+                new_path: new_path.to_path_buf(),
+                rename_time: *rename_time,
+                flags,
+            },
+            TmpFileError::RenameError,
+        )
+    }
+
+    fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_inode(
+            tf,
+            Request::Create {
+                path: remote_path(tf),
+                mode,
+            },
+            TmpFileError::CreateError,
+        )
+    }
+
+    fn remove_file(&self, tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::Unlink {
+                path: remote_path(tf),
+                rm_file_time: *rm_file_time,
+            },
+            TmpFileError::RemoveError,
+        )
+    }
+
+    fn make_dir(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_inode(
+            tf,
+            Request::MakeDir {
+                path: remote_path(tf),
+                mode,
+            },
+            TmpFileError::MakeDirError,
+        )
+    }
+
+    fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_inode(
+            tf,
+            Request::Symlink {
+                path: remote_path(tf),
+                target: target.to_path_buf(),
+            },
+            TmpFileError::CreateError,
+        )
+    }
+
+    fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        let data = self.request_data(
+            Request::ReadLink {
+                path: remote_path(tf),
+            },
+            TmpFileError::ReadError,
+        )?;
+        Ok(PathBuf::from(OsString::from_vec(data)))
+    }
+
+    fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::Link {
+                path: remote_path(tf),
+                new_path: new_path.to_path_buf(),
+            },
+            TmpFileError::CreateError,
+        )
+    }
+
+    fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.request_data(
+            Request::GetXattr {
+                path: remote_path(tf),
+                name: name.to_string(),
+            },
+            TmpFileError::XattrError,
+        )
+    }
+
+    fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::SetXattr {
+                path: remote_path(tf),
+                name: name.to_string(),
+                value: value.to_vec(),
+                flags,
+            },
+            TmpFileError::XattrError,
+        )
+    }
+
+    fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.request_data(
+            Request::ListXattr {
+                path: remote_path(tf),
+            },
+            TmpFileError::XattrError,
+        )
+    }
+
+    fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::RemoveXattr {
+                path: remote_path(tf),
+                name: name.to_string(),
+            },
+            TmpFileError::XattrError,
+        )
+    }
+
+    fn statfs(&self, tf: &TmpFile) -> Result<StatFs, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        match self.request(
+            Request::StatFs {
+                path: remote_path(tf),
+            },
+            TmpFileError::ReadError,
+        )? {
+            Response::StatFs(stat) => Ok(stat),
+            r => {
+                error!("[RemoteFS][statfs] unexpected response: {:?}", r);
+                Err(TmpFileError::ReadError)
+            }
+        }
+    }
+
+    fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.request_ok(
+            Request::RemoveDir {
+                path: remote_path(tf),
+                rm_dir_time: *rm_dir_time,
+            },
+            TmpFileError::RemoveDirError,
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{
+    cli::args::{Args, Command, MountCommand},
+    client::RemoteClient,
+    init_fs::remote_init_fs,
+    logging::{init_log, LogLevel},
+    remote_fs::RemoteFS,
+    ExitStatus,
+};
+use anyhow::Result;
+use fuser::MountOption;
+use log::{debug, error, info};
+use nix::unistd::geteuid;
+use rfuse_core::{
+    block_cache::{BlockCache, BlockCacheOptions},
+    sys_fs::{CacheOptions, RFuseFS},
+    tmp_file::TmpFileTrait,
+};
+use tokio::{runtime::Handle, signal};
+
+pub async fn run(
+    Args {
+        command,
+        log_level_args,
+    }: Args,
+) -> Result<ExitStatus> {
+    // 日志初始化
+    let log_level = LogLevel::from(&log_level_args);
+    init_log(&log_level);
+
+    match command {
+        Command::Mount(mount_command) => run_mount(mount_command).await,
+    }
+}
+
+async fn run_mount(
+    MountCommand {
+        addr,
+        mount,
+        read_only,
+        fs_name,
+        token,
+        no_xattr,
+        timeout,
+        threads,
+        cache,
+        block_cache,
+    }: MountCommand,
+) -> Result<ExitStatus> {
+    // 挂载选项
+    let mut options = vec![MountOption::FSName(fs_name.clone())];
+
+    if geteuid().is_root() {
+        options.push(MountOption::AllowOther); // 这样可以让其他用户访问
+    }
+
+    if read_only {
+        options.push(MountOption::RO); // 这样是只读
+    } else {
+        options.push(MountOption::RW); // 这样才是读写
+    }
+
+    let timeout = match timeout {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let client = match RemoteClient::connect_timeout(&addr, token.as_deref().unwrap_or(""), timeout)
+    {
+        Ok(c) => Arc::new(c),
+        Err(e) => {
+            error!("[mount] connect to {} failed: {}", addr, e);
+            return Ok(ExitStatus::Failure);
+        }
+    };
+    info!("[mount] connected to {}", addr);
+
+    // 每次读取都是一次网络往返, 所以在 RemoteFS 之上缓存文件块
+    let mut counters = None;
+    let backend: Box<dyn TmpFileTrait> = match block_cache.cache_memory {
+        0 => Box::new(RemoteFS(client.clone())),
+        _ => {
+            let cache = BlockCache::new(
+                Box::new(RemoteFS(client.clone())),
+                BlockCacheOptions::from(&block_cache),
+            );
+            counters = Some(cache.counters());
+            Box::new(cache)
+        }
+    };
+
+    // 远程文件的路径都是相对于服务端共享目录的, 所以 source_dir 为空
+    let mut rfs = RFuseFS::new(
+        fs_name,
+        CacheOptions::from(&cache),
+        !no_xattr,
+        PathBuf::new(),
+        remote_init_fs(client),
+        backend,
+    );
+    rfs.spawn_workers(threads);
+    // 网络请求在 tokio 的阻塞线程池中等待, 不占用 fuser 的请求线程
+    rfs.adapt_sync_backend(Handle::current());
+    let guard = fuser::spawn_mount2(rfs, mount.display().to_string(), &options)?;
+
+    match signal::ctrl_c().await {
+        Ok(_) => debug!("ctrl+c received"),
+        Err(e) => error!("ctrl+c error: {:?}", e),
+    };
+    info!("[mount] unmounting {}", mount.display());
+    guard.join();
+    if let Some(counters) = counters {
+        info!("[mount] block cache {}", counters.snapshot());
+    }
+
+    Ok(ExitStatus::Success)
+}
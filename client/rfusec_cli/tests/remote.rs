@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    net::TcpListener as StdTcpListener,
+    os::unix::{ffi::OsStrExt, fs::FileExt},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use fuser::FUSE_ROOT_ID;
+use rfuse_core::{
+    inode::{Inode, InodeAttributes, InodeKind},
+    remote_fs::RemoteFileManager,
+    sys_fs::{CacheOptions, Caller, RFuseFS},
+};
+use rfuse_protocol::codec::{ProtocolError, MAX_DATA_SIZE};
+use rfusec_cli::{client::RemoteClient, init_fs::remote_init_fs, remote_fs::RemoteFS};
+use rfuses_device_local::serve::serve;
+use tokio::{net::TcpListener, runtime::Runtime};
+
+// 在当前进程中启动服务端, 返回监听的地址
+async fn spawn_server(origin: PathBuf, token: Option<&str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(serve(listener, origin, token.map(String::from)));
+    addr
+}
+
+// 在单独的运行时中启动服务端, 关闭运行时就会断开所有连接, 用来模拟服务端重启
+fn start_server(origin: PathBuf, addr: &str) -> Runtime {
+    let rt = Runtime::new().unwrap();
+    let listener = rt.block_on(TcpListener::bind(addr)).unwrap();
+    rt.spawn(serve(listener, origin, None));
+    rt
+}
+
+// 和 RFuseFS 一样, 在第一次访问文件夹时通过 RemoteFS 读取子文件
+fn load_dir(manager: &mut RemoteFileManager, inodes: &mut HashMap<u64, Inode>, dir: u64) {
+    let inode = &inodes[&dir];
+    if inode.loaded {
+        return;
+    }
+    let children = manager
+        .read_dir(inode.attr.name.clone(), PathBuf::from(&inode.attr.path))
+        .unwrap();
+    for mut child in children {
+        child.parent_ino = dir;
+        manager.add_file(
+            child.ino,
+            child.attr.name.clone(),
+            PathBuf::from(&child.attr.path),
+        );
+        let parent = inodes.get_mut(&dir).unwrap();
+        parent
+            .entries
+            .insert_child(child.attr.name.clone(), child.ino);
+        inodes.insert(child.ino, child);
+    }
+    inodes.get_mut(&dir).unwrap().loaded = true;
+}
+
+fn child(
+    manager: &mut RemoteFileManager,
+    inodes: &mut HashMap<u64, Inode>,
+    parent: u64,
+    name: impl AsRef<OsStr>,
+) -> Inode {
+    load_dir(manager, inodes, parent);
+    let name = name.as_ref();
+    let ino = inodes[&parent]
+        .entries
+        .lookup(name)
+        .unwrap_or_else(|| panic!("{:?} not found", name));
+    inodes[&ino].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remote_init_fs() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("test_remote.txt"), "Hello, World!").unwrap();
+    fs::create_dir_all(origin.path().join("test_remote_dir/sub")).unwrap();
+    fs::write(
+        origin.path().join("test_remote_dir/sub/nested.txt"),
+        "rfuse",
+    )
+    .unwrap();
+
+    let addr = spawn_server(origin.path().to_owned(), None).await;
+    tokio::task::spawn_blocking(move || {
+        let client = Arc::new(RemoteClient::connect(&addr, "").unwrap());
+        let mut manager = RemoteFileManager::new(
+            remote_init_fs(client.clone()),
+            Box::new(RemoteFS(client.clone())),
+        );
+        let mut inodes = HashMap::new();
+        assert!(manager.initialize_fs(&mut inodes, PathBuf::new()).is_ok());
+
+        // 挂载时只读取根节点, 文件夹的内容在第一次访问时读取
+        assert_eq!(inodes.len(), 1);
+        assert!(!inodes[&FUSE_ROOT_ID].loaded);
+        load_dir(&mut manager, &mut inodes, FUSE_ROOT_ID);
+
+        // 根节点下有一个文件和一个文件夹
+        assert_eq!(inodes[&FUSE_ROOT_ID].entries.len(), 2);
+        let file = child(&mut manager, &mut inodes, FUSE_ROOT_ID, "test_remote.txt");
+        assert_eq!(file.attr.kind, InodeKind::File);
+        assert_eq!(file.attr.path, "/");
+        assert_eq!(file.attr.size, 13);
+        assert_eq!(manager.read_all(file.ino), b"Hello, World!");
+
+        // 多层文件夹的父节点和路径
+        let dir = child(&mut manager, &mut inodes, FUSE_ROOT_ID, "test_remote_dir");
+        assert_eq!(dir.attr.kind, InodeKind::Directory);
+        assert!(!dir.loaded);
+        let sub = child(&mut manager, &mut inodes, dir.ino, "sub");
+        assert_eq!(sub.parent_ino, dir.ino);
+        assert_eq!(sub.attr.path, "/test_remote_dir/");
+        let nested = child(&mut manager, &mut inodes, sub.ino, "nested.txt");
+        assert_eq!(nested.parent_ino, sub.ino);
+        assert_eq!(nested.attr.path, "/test_remote_dir/sub/");
+        let mut buf = [0u8; 3];
+        manager.read(nested.ino, &mut buf, 1).unwrap();
+        assert_eq!(&buf, b"fus");
+
+        // 容量信息来自服务端共享目录所在的文件系统
+        let stat = manager.statfs("/".into()).unwrap();
+        assert!(stat.blocks > 0);
+        assert!(stat.namelen > 0);
+
+        // RFuseFS 在查找时逐层读取服务端的文件夹
+        let mut rfs = RFuseFS::new(
+            "rfusec".to_string(),
+            CacheOptions::default(),
+            true,
+            PathBuf::new(),
+            remote_init_fs(client.clone()),
+            Box::new(RemoteFS(client)),
+        );
+        rfs.re_init_fs();
+        let caller = Caller { uid: 0, gid: 0 };
+        let dir = rfs
+            .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("test_remote_dir"))
+            .unwrap();
+        let sub = rfs
+            .lookup_entry(caller, dir.ino, OsStr::new("sub"))
+            .unwrap();
+        assert!(!rfs.get_inode(sub.ino).unwrap().loaded);
+        let nested = rfs
+            .lookup_entry(caller, sub.ino, OsStr::new("nested.txt"))
+            .unwrap();
+        assert_eq!(nested.size, 5);
+        assert_eq!(
+            rfs.get_inode(nested.ino).unwrap().attr.path,
+            "/test_remote_dir/sub/"
+        );
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remote_write() {
+    let origin = tempfile::tempdir().unwrap();
+    let origin_path = origin.path().to_owned();
+
+    let addr = spawn_server(origin.path().to_owned(), None).await;
+    tokio::task::spawn_blocking(move || {
+        let client = Arc::new(RemoteClient::connect(&addr, "").unwrap());
+        let mut manager =
+            RemoteFileManager::new(remote_init_fs(client.clone()), Box::new(RemoteFS(client)));
+        let now = SystemTime::now();
+
+        // 创建文件夹和文件
+        let dir_attr = InodeAttributes::new("test_remote_dir", InodeKind::Directory, "/");
+        let dir = manager.mk_dir(&dir_attr, "/".into()).unwrap();
+        assert!(origin_path.join("test_remote_dir").is_dir());
+
+        let file_attr =
+            InodeAttributes::new("test_remote.txt", InodeKind::File, "/test_remote_dir/");
+        let file = manager
+            .new_file(dir.ino, file_attr, "/test_remote_dir/".into())
+            .unwrap();
+        assert_eq!(file.attr.kind, InodeKind::File);
+
+        // 写入文件
+        manager
+            .write_file(file.ino, b"Hello, World!", &now, 0)
+            .unwrap();
+        let test_file_origin = origin_path.join("test_remote_dir/test_remote.txt");
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"Hello, World!");
+
+        // 重命名文件
+        manager
+            .rename(
+                file.ino,
+                "test_remote_rename.txt".into(),
+                "/".into(),
+                &now,
+                0,
+            )
+            .unwrap();
+        assert!(!test_file_origin.exists());
+        assert!(origin_path.join("test_remote_rename.txt").exists());
+
+        // 删除文件和文件夹
+        manager.remove_file(file.ino, &now).unwrap();
+        manager.remove_dir(dir.ino, &now).unwrap();
+        assert!(!origin_path.join("test_remote_rename.txt").exists());
+        assert!(!origin_path.join("test_remote_dir").exists());
+    })
+    .await
+    .unwrap();
+}
+
+// 服务端的文件名不是 UTF-8 时, 名字按原始字节传输
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remote_non_utf8_name() {
+    let origin = tempfile::tempdir().unwrap();
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs::write(origin.path().join(name), "Hello, World!").unwrap();
+
+    let addr = spawn_server(origin.path().to_owned(), None).await;
+    tokio::task::spawn_blocking(move || {
+        let client = Arc::new(RemoteClient::connect(&addr, "").unwrap());
+        let mut manager =
+            RemoteFileManager::new(remote_init_fs(client.clone()), Box::new(RemoteFS(client)));
+        let mut inodes = HashMap::new();
+        assert!(manager.initialize_fs(&mut inodes, PathBuf::new()).is_ok());
+
+        let file = child(&mut manager, &mut inodes, FUSE_ROOT_ID, name);
+        assert_eq!(manager.read_all(file.ino), b"Hello, World!");
+
+        // 改成另一个非 UTF-8 的名字
+        let new_name = OsStr::from_bytes(b"\xff\xfe.txt");
+        manager
+            .rename(file.ino, new_name.into(), "/".into(), &SystemTime::now(), 0)
+            .unwrap();
+        assert!(!origin.path().join(name).exists());
+        assert_eq!(
+            fs::read(origin.path().join(new_name)).unwrap(),
+            b"Hello, World!"
+        );
+    })
+    .await
+    .unwrap();
+}
+
+// 服务端配置了 token 时, token 不一致的客户端不能连接
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remote_token() {
+    let origin = tempfile::tempdir().unwrap();
+
+    let addr = spawn_server(origin.path().to_owned(), Some("rfuse")).await;
+    tokio::task::spawn_blocking(move || {
+        assert!(matches!(
+            RemoteClient::connect(&addr, ""),
+            Err(ProtocolError::HandshakeRejected)
+        ));
+        assert!(matches!(
+            RemoteClient::connect(&addr, "other"),
+            Err(ProtocolError::HandshakeRejected)
+        ));
+        assert!(RemoteClient::connect(&addr, "rfuse").is_ok());
+    })
+    .await
+    .unwrap();
+}
+
+// 放不进一帧的文件分段读取
+#[tokio::test(flavor = "multi_thread")]
+async fn test_remote_read_all_large() {
+    let origin = tempfile::tempdir().unwrap();
+    let size = MAX_DATA_SIZE as u64 + 5;
+    let file = fs::File::create(origin.path().join("test_remote_big.bin")).unwrap();
+    file.set_len(size).unwrap();
+    file.write_all_at(b"rfuse", size - 5).unwrap();
+
+    let addr = spawn_server(origin.path().to_owned(), None).await;
+    tokio::task::spawn_blocking(move || {
+        let client = Arc::new(RemoteClient::connect(&addr, "").unwrap());
+        let mut manager =
+            RemoteFileManager::new(remote_init_fs(client.clone()), Box::new(RemoteFS(client)));
+        let mut inodes = HashMap::new();
+        assert!(manager.initialize_fs(&mut inodes, PathBuf::new()).is_ok());
+
+        let file = child(
+            &mut manager,
+            &mut inodes,
+            FUSE_ROOT_ID,
+            "test_remote_big.bin",
+        );
+        let data = manager.read_all(file.ino);
+        assert_eq!(data.len() as u64, size);
+        assert_eq!(&data[data.len() - 5..], b"rfuse");
+    })
+    .await
+    .unwrap();
+}
+
+// 服务端重启之后, 客户端在下一个请求时重新连接并握手
+#[test]
+fn test_remote_reconnect() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("test_remote.txt"), "Hello, World!").unwrap();
+
+    let addr = StdTcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let server = start_server(origin.path().to_owned(), &addr);
+    let client = Arc::new(RemoteClient::connect(&addr, "").unwrap());
+    let mut manager =
+        RemoteFileManager::new(remote_init_fs(client.clone()), Box::new(RemoteFS(client)));
+    let mut inodes = HashMap::new();
+    assert!(manager.initialize_fs(&mut inodes, PathBuf::new()).is_ok());
+    let file = child(&mut manager, &mut inodes, FUSE_ROOT_ID, "test_remote.txt").ino;
+    let mut buf = [0u8; 5];
+    manager.read(file, &mut buf, 0).unwrap();
+    assert_eq!(&buf, b"Hello");
+
+    // 服务端停止之后请求失败, 而不是一直等待
+    server.shutdown_timeout(Duration::from_secs(5));
+    assert!(manager.read(file, &mut buf, 7).is_err());
+    assert!(manager.read(file, &mut buf, 7).is_err());
+
+    // 服务端重新启动之后, 已经挂载的文件可以继续访问
+    let _server = start_server(origin.path().to_owned(), &addr);
+    manager.read(file, &mut buf, 7).unwrap();
+    assert_eq!(&buf, b"World");
+    manager
+        .write_file(file, b"rfuse", &SystemTime::now(), 0)
+        .unwrap();
+    assert_eq!(
+        fs::read(origin.path().join("test_remote.txt")).unwrap(),
+        b"rfuse, World!"
+    );
+}
+
+// 服务端不响应时, 请求在超时之后返回错误
+#[test]
+fn test_remote_timeout() {
+    let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let started = std::time::Instant::now();
+    assert!(matches!(
+        RemoteClient::connect_timeout(addr, "", Some(Duration::from_millis(200))),
+        Err(ProtocolError::Io(_))
+    ));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
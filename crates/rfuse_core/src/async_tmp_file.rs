@@ -0,0 +1,158 @@
+use std::{future::Future, path::PathBuf, pin::Pin, sync::Arc, time::SystemTime};
+
+use log::error;
+
+use crate::tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait};
+
+/// 后端返回的 future, 在 tokio 的任务中等待
+pub type BackendFuture<T> = Pin<Box<dyn Future<Output = Result<T, TmpFileError>> + Send + 'static>>;
+
+/// 异步的后端接口, 等待结果期间不会占用 fuser 的请求线程
+///
+/// 只包括不修改 inode 表的操作: read, write, readlink, statfs, getxattr 和 listxattr.
+/// lookup, getattr, setattr, readdir, 创建, 删除, 重命名和 setxattr/removexattr
+/// 需要在回复之前读取或者更新 inode 表, 仍然在请求线程中同步调用 TmpFileTrait
+pub trait AsyncTmpFileTrait: Send + Sync {
+    // 从 offset 开始读取 size 个字节, file_size 是 inode 表中的文件大小, 读到文件末尾时返回的数据更短
+    fn read(
+        &self,
+        tf: Arc<TmpFile>,
+        offset: u64,
+        size: usize,
+        file_size: u64,
+    ) -> BackendFuture<Vec<u8>>;
+
+    // 写入文件, 不会截断文件
+    fn write(
+        &self,
+        tf: Arc<TmpFile>,
+        data: Vec<u8>,
+        write_time: SystemTime,
+        offset: u64,
+    ) -> BackendFuture<()>;
+
+    // 读取软链接指向的地址
+    fn read_link(&self, tf: Arc<TmpFile>) -> BackendFuture<PathBuf>;
+
+    // 获取文件所在文件系统的容量信息
+    fn statfs(&self, tf: Arc<TmpFile>) -> BackendFuture<StatFs>;
+
+    // 读取扩展属性的值
+    fn get_xattr(&self, tf: Arc<TmpFile>, name: String) -> BackendFuture<Vec<u8>>;
+
+    // 列出扩展属性名, 每个名字以 `\0` 结尾
+    fn list_xattr(&self, tf: Arc<TmpFile>) -> BackendFuture<Vec<u8>>;
+
+    // 通过 open 时返回的句柄读取, 需要读满, 默认在 tokio 的阻塞线程池中调用同步的句柄
+    fn read_handle(
+        &self,
+        handle: Arc<dyn FileHandle>,
+        offset: u64,
+        size: usize,
+    ) -> BackendFuture<Vec<u8>> {
+        blocking(TmpFileError::ReadError, move || {
+            let mut buf = vec![0; size];
+            handle.read_exact(&mut buf, offset).map(|_| buf)
+        })
+    }
+
+    // 通过 open 时返回的句柄写入, 不会截断文件
+    fn write_handle(
+        &self,
+        handle: Arc<dyn FileHandle>,
+        data: Vec<u8>,
+        write_time: SystemTime,
+        offset: u64,
+    ) -> BackendFuture<()> {
+        blocking(TmpFileError::WriteError, move || {
+            handle.write(&data, &write_time, offset)
+        })
+    }
+}
+
+// 在 future 第一次被 poll 时才提交到阻塞线程池, 所以可以在 runtime 之外创建 future
+fn blocking<T, F>(err: TmpFileError, f: F) -> BackendFuture<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, TmpFileError> + Send + 'static,
+{
+    Box::pin(async move {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(result) => result,
+            Err(e) => {
+                error!("[AsyncTmpFileTrait][blocking] {} failed: {}", err, e);
+                Err(err)
+            }
+        }
+    })
+}
+
+/// 把同步的 TmpFileTrait 包装成 AsyncTmpFileTrait, 例如 LocalFS
+///
+/// 每次调用都放到 tokio 的阻塞线程池中执行
+pub struct SyncAdapter(pub Arc<dyn TmpFileTrait>);
+
+impl SyncAdapter {
+    fn blocking<T, F>(&self, err: TmpFileError, f: F) -> BackendFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn TmpFileTrait) -> Result<T, TmpFileError> + Send + 'static,
+    {
+        let backend = self.0.clone();
+        blocking(err, move || f(backend.as_ref()))
+    }
+}
+
+impl AsyncTmpFileTrait for SyncAdapter {
+    fn read(
+        &self,
+        tf: Arc<TmpFile>,
+        offset: u64,
+        size: usize,
+        file_size: u64,
+    ) -> BackendFuture<Vec<u8>> {
+        self.blocking(TmpFileError::ReadError, move |backend| {
+            let mut buf = vec![0; size];
+            backend
+                .read_sized(&tf, &mut buf, offset, file_size)
+                .map(|len| {
+                    buf.truncate(len);
+                    buf
+                })
+        })
+    }
+
+    fn write(
+        &self,
+        tf: Arc<TmpFile>,
+        data: Vec<u8>,
+        write_time: SystemTime,
+        offset: u64,
+    ) -> BackendFuture<()> {
+        self.blocking(TmpFileError::WriteError, move |backend| {
+            backend.write(&tf, &data, &write_time, offset)
+        })
+    }
+
+    fn read_link(&self, tf: Arc<TmpFile>) -> BackendFuture<PathBuf> {
+        self.blocking(TmpFileError::ReadError, move |backend| {
+            backend.read_link(&tf)
+        })
+    }
+
+    fn statfs(&self, tf: Arc<TmpFile>) -> BackendFuture<StatFs> {
+        self.blocking(TmpFileError::ReadError, move |backend| backend.statfs(&tf))
+    }
+
+    fn get_xattr(&self, tf: Arc<TmpFile>, name: String) -> BackendFuture<Vec<u8>> {
+        self.blocking(TmpFileError::XattrError, move |backend| {
+            backend.get_xattr(&tf, &name)
+        })
+    }
+
+    fn list_xattr(&self, tf: Arc<TmpFile>) -> BackendFuture<Vec<u8>> {
+        self.blocking(TmpFileError::XattrError, move |backend| {
+            backend.list_xattr(&tf)
+        })
+    }
+}
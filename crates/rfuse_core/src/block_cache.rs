@@ -0,0 +1,566 @@
+use core::fmt;
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    inode::{Inode, InodeAttributes},
+    tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait},
+};
+
+/// 块缓存的配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCacheOptions {
+    pub block_size: u64,           // 缓存和读取后端的最小单位
+    pub memory_budget: u64,        // 内存中最多缓存的字节数
+    pub disk_dir: Option<PathBuf>, // 从内存中淘汰的块写到这个文件夹, 为 None 时直接丢弃
+    pub disk_budget: u64,          // 磁盘上最多缓存的字节数
+    pub read_ahead: u64,           // 顺序读取时额外读取的块数, 为 0 时不预读
+}
+
+impl Default for BlockCacheOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 128 * 1024,
+            memory_budget: 64 * 1024 * 1024,
+            disk_dir: None,
+            disk_budget: 0,
+            read_ahead: 4,
+        }
+    }
+}
+
+/// 块缓存的统计数据
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    pub hits: u64,          // 在缓存中找到的块
+    pub misses: u64,        // 需要从后端读取的块
+    pub read_ahead: u64,    // 预读的块
+    pub evictions: u64,     // 从内存中淘汰的块
+    pub backend_reads: u64, // 对后端的 read_exact 调用次数
+}
+
+impl fmt::Display for BlockCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hits: {}, misses: {}, read_ahead: {}, evictions: {}, backend_reads: {}",
+            self.hits, self.misses, self.read_ahead, self.evictions, self.backend_reads
+        )
+    }
+}
+
+/// 块缓存的计数器, 可以在缓存交给 RFuseFS 之后继续读取
+#[derive(Debug, Default)]
+pub struct BlockCacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    read_ahead: AtomicU64,
+    evictions: AtomicU64,
+    backend_reads: AtomicU64,
+}
+
+impl BlockCacheCounters {
+    pub fn snapshot(&self) -> BlockCacheStats {
+        BlockCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            read_ahead: self.read_ahead.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            backend_reads: self.backend_reads.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+// (文件的 ino, 块的序号), 硬链接的多个名字共用同一个文件的块
+type BlockKey = (u64, u64);
+
+// 按最近使用时间淘汰的块, 同一个文件的块在 BTreeMap 中相邻, 方便整个文件失效
+struct Lru<V> {
+    entries: BTreeMap<BlockKey, (V, u64, u64)>, // 块 -> (内容, 最近使用时间, 大小)
+    order: BTreeMap<u64, BlockKey>,             // 最近使用时间 -> 块
+    next_tick: u64,
+    used: u64,
+    budget: u64,
+}
+
+impl<V> Lru<V> {
+    fn new(budget: u64) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            order: BTreeMap::new(),
+            next_tick: 0,
+            used: 0,
+            budget,
+        }
+    }
+
+    fn contains(&self, key: &BlockKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    // 读取并更新最近使用时间
+    fn get(&mut self, key: &BlockKey) -> Option<&V> {
+        let tick = self.tick();
+        let (_, last, _) = self.entries.get_mut(key)?;
+        self.order.remove(last);
+        *last = tick;
+        self.order.insert(tick, *key);
+        self.entries.get(key).map(|(value, _, _)| value)
+    }
+
+    // 插入块, 返回被替换的旧内容和因为超出预算被淘汰的块, 比整个预算还大的块直接返回
+    fn insert(&mut self, key: BlockKey, value: V, len: u64) -> Vec<(BlockKey, V)> {
+        let mut evicted = Vec::new();
+        if let Some(old) = self.remove(&key) {
+            evicted.push((key, old));
+        }
+        if len > self.budget {
+            evicted.push((key, value));
+            return evicted;
+        }
+        while self.used + len > self.budget {
+            let oldest = match self.order.keys().next() {
+                Some(tick) => self.order[tick],
+                None => break,
+            };
+            if let Some(old) = self.remove(&oldest) {
+                evicted.push((oldest, old));
+            }
+        }
+        let tick = self.tick();
+        self.order.insert(tick, key);
+        self.entries.insert(key, (value, tick, len));
+        self.used += len;
+        evicted
+    }
+
+    fn remove(&mut self, key: &BlockKey) -> Option<V> {
+        let (value, tick, len) = self.entries.remove(key)?;
+        self.order.remove(&tick);
+        self.used -= len;
+        Some(value)
+    }
+
+    // 删除一个文件的所有块
+    fn remove_file(&mut self, ino: u64) -> Vec<V> {
+        let keys: Vec<BlockKey> = self
+            .entries
+            .range((ino, 0)..=(ino, u64::MAX))
+            .map(|(key, _)| *key)
+            .collect();
+        keys.iter().filter_map(|key| self.remove(key)).collect()
+    }
+}
+
+// 每个文件的读取状态, 文件失效时删除
+struct FileState {
+    last_end: Option<u64>, // 上一次读取的结束位置, 用于判断是否是顺序读取
+    generation: u64,       // 创建时分配, 不会重复, 用于判断读取后端期间文件是否失效
+}
+
+struct CacheState {
+    memory: Lru<Vec<u8>>,
+    disk: Option<(PathBuf, Lru<PathBuf>)>, // 磁盘缓存的文件夹和其中的块文件
+    files: HashMap<u64, FileState>,
+    next_disk_id: u64,
+    next_generation: u64,
+}
+
+impl CacheState {
+    fn file(&mut self, ino: u64) -> &mut FileState {
+        let next_generation = &mut self.next_generation;
+        self.files.entry(ino).or_insert_with(|| {
+            *next_generation += 1;
+            FileState {
+                last_end: None,
+                generation: *next_generation,
+            }
+        })
+    }
+
+    // 读取后端期间文件没有失效
+    fn is_current(&self, ino: u64, generation: u64) -> bool {
+        self.files
+            .get(&ino)
+            .is_some_and(|file| file.generation == generation)
+    }
+}
+
+/// 在 RemoteFileManager 和后端之间缓存文件内容的块, 减少网络后端的往返次数
+///
+/// 块按 tmp_file_map 中的 ino 缓存, 文件大小使用 inode 表中的大小, 不需要向后端查询;
+/// 顺序读取时一次从后端读取多个块, 文件在 open, 写入, 修改属性, 重命名, 删除和源文件改动时失效
+pub struct BlockCache {
+    backend: Box<dyn TmpFileTrait>,
+    options: BlockCacheOptions,
+    state: Mutex<CacheState>,
+    counters: Arc<BlockCacheCounters>,
+}
+
+impl BlockCache {
+    pub fn new(backend: Box<dyn TmpFileTrait>, options: BlockCacheOptions) -> Self {
+        let disk = match &options.disk_dir {
+            Some(dir) if options.disk_budget > 0 => match fs::create_dir_all(dir) {
+                Ok(_) => Some((dir.clone(), Lru::new(options.disk_budget))),
+                Err(e) => {
+                    warn!(
+                        "[BlockCache][new] disable disk cache {}: {}",
+                        dir.display(),
+                        e
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        Self {
+            backend,
+            state: Mutex::new(CacheState {
+                memory: Lru::new(options.memory_budget),
+                disk,
+                files: HashMap::new(),
+                next_disk_id: 0,
+                next_generation: 0,
+            }),
+            options,
+            counters: Arc::new(BlockCacheCounters::default()),
+        }
+    }
+
+    pub fn counters(&self) -> Arc<BlockCacheCounters> {
+        self.counters.clone()
+    }
+
+    pub fn stats(&self) -> BlockCacheStats {
+        self.counters.snapshot()
+    }
+
+    // 依次在内存和磁盘中查找块, 磁盘中找到的块移回内存
+    fn get_block(&self, state: &mut CacheState, key: &BlockKey) -> Option<Vec<u8>> {
+        if let Some(data) = state.memory.get(key) {
+            return Some(data.clone());
+        }
+        let file = state.disk.as_mut()?.1.remove(key)?;
+        let data = fs::read(&file);
+        let _ = fs::remove_file(&file);
+        match data {
+            Ok(data) => {
+                self.put_block(state, *key, data.clone());
+                Some(data)
+            }
+            Err(e) => {
+                debug!("[BlockCache][get_block] read {}: {}", file.display(), e);
+                None
+            }
+        }
+    }
+
+    fn contains_block(state: &CacheState, key: &BlockKey) -> bool {
+        state.memory.contains(key)
+            || state
+                .disk
+                .as_ref()
+                .is_some_and(|(_, disk)| disk.contains(key))
+    }
+
+    // 放入内存, 被淘汰的块写到磁盘缓存中
+    fn put_block(&self, state: &mut CacheState, key: BlockKey, data: Vec<u8>) {
+        // 磁盘中的旧内容已经过期
+        if let Some((_, disk)) = state.disk.as_mut() {
+            if let Some(file) = disk.remove(&key) {
+                let _ = fs::remove_file(file);
+            }
+        }
+        let len = data.len() as u64;
+        let inserted = key;
+        for (key, data) in state.memory.insert(key, data, len) {
+            // 被替换的旧内容直接丢弃
+            if key == inserted && state.memory.contains(&key) {
+                continue;
+            }
+            BlockCacheCounters::add(&self.counters.evictions, 1);
+            let id = state.next_disk_id;
+            let (dir, disk) = match state.disk.as_mut() {
+                Some(disk) => disk,
+                None => continue,
+            };
+            let file = dir.join(format!("block-{}", id));
+            if let Err(e) = fs::write(&file, &data) {
+                debug!("[BlockCache][put_block] write {}: {}", file.display(), e);
+                continue;
+            }
+            state.next_disk_id += 1;
+            for (_, old) in disk.insert(key, file, data.len() as u64) {
+                let _ = fs::remove_file(old);
+            }
+        }
+    }
+
+    // 文件内容可能已经改变, 删除文件的所有块和读取状态,
+    // 正在读取后端的请求看到读取状态变化之后不会再缓存读到的内容
+    fn invalidate(&self, ino: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.memory.remove_file(ino);
+        if let Some((_, disk)) = state.disk.as_mut() {
+            for file in disk.remove_file(ino) {
+                let _ = fs::remove_file(file);
+            }
+        }
+        state.files.remove(&ino);
+    }
+
+    fn read_backend(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        BlockCacheCounters::add(&self.counters.backend_reads, 1);
+        self.backend.read_exact(tf, buf, offset)
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            if let Some((_, disk)) = state.disk.as_mut() {
+                for (file, _, _) in disk.entries.values() {
+                    let _ = fs::remove_file(file);
+                }
+            }
+        }
+    }
+}
+
+impl TmpFileTrait for BlockCache {
+    fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        let result = self.backend.write(tf, data, write_time, offset);
+        self.invalidate(tf.ino);
+        result
+    }
+
+    // 每次 open 都重新读取, 保证能看到其他客户端关闭文件前的写入
+    fn open(&self, tf: &TmpFile, flags: i32) -> Result<Option<Box<dyn FileHandle>>, TmpFileError> {
+        self.invalidate(tf.ino);
+        self.backend.open(tf, flags)
+    }
+
+    fn get_attr(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+        self.backend.get_attr(tf)
+    }
+
+    fn read_dir(&self, tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+        self.backend.read_dir(tf)
+    }
+
+    fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        self.backend.read_all(tf)
+    }
+
+    // 不知道文件大小时不经过缓存
+    fn read_exact(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        self.read_backend(tf, buf, offset)
+    }
+
+    fn read_sized(
+        &self,
+        tf: &TmpFile,
+        buf: &mut [u8],
+        offset: u64,
+        size: u64,
+    ) -> Result<usize, TmpFileError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ino = tf.ino;
+        let block_size = self.options.block_size;
+        let end = offset + buf.len() as u64;
+        // 不在 tmp_file_map 中的文件或者读到文件之外时交给后端处理
+        if ino == 0 || end > size {
+            BlockCacheCounters::add(&self.counters.backend_reads, 1);
+            return self.backend.read_sized(tf, buf, offset, size);
+        }
+        let first = offset / block_size;
+        let last = (end - 1) / block_size;
+        let last_block = (size - 1) / block_size;
+        // 按当前的文件大小, 块应该有的长度
+        let block_len = |index: u64| min(block_size, size - index * block_size);
+
+        let (mut blocks, ahead, generation) = {
+            let mut state = self.state.lock().unwrap();
+            // 文件在缓存之后变长或者变短时, 长度不对的块重新读取
+            let blocks: Vec<Option<Vec<u8>>> = (first..=last)
+                .map(|index| {
+                    self.get_block(&mut state, &(ino, index))
+                        .filter(|data| data.len() as u64 == block_len(index))
+                })
+                .collect();
+            let file = state.file(ino);
+            let sequential = file.last_end == Some(offset);
+            file.last_end = Some(end);
+            let generation = file.generation;
+            // 上次预读的块用完之后再继续预读
+            let ahead = if sequential
+                && last < last_block
+                && !Self::contains_block(&state, &(ino, last + 1))
+            {
+                min(self.options.read_ahead, last_block - last)
+            } else {
+                0
+            };
+            (blocks, ahead, generation)
+        };
+
+        let missing: Vec<u64> = (first..=last)
+            .filter(|index| blocks[(index - first) as usize].is_none())
+            .collect();
+        BlockCacheCounters::add(
+            &self.counters.hits,
+            blocks.len() as u64 - missing.len() as u64,
+        );
+        BlockCacheCounters::add(&self.counters.misses, missing.len() as u64);
+
+        // 缺少的块和预读的块合并成一次后端读取
+        if !missing.is_empty() || ahead > 0 {
+            let fetch_first = missing.first().copied().unwrap_or(last + 1);
+            let fetch_last = max(missing.last().copied().unwrap_or(last), last + ahead);
+            let start = fetch_first * block_size;
+            let stop = min((fetch_last + 1) * block_size, size);
+            let mut data = vec![0; (stop - start) as usize];
+            self.read_backend(tf, &mut data, start)?;
+            BlockCacheCounters::add(&self.counters.read_ahead, ahead);
+
+            let mut state = self.state.lock().unwrap();
+            // 读取期间文件被写入或者失效, 读到的内容只用于这次读取
+            let current = state.is_current(ino, generation);
+            for (chunk, index) in data.chunks(block_size as usize).zip(fetch_first..) {
+                if (first..=last).contains(&index) {
+                    blocks[(index - first) as usize] = Some(chunk.to_vec());
+                }
+                if current {
+                    self.put_block(&mut state, (ino, index), chunk.to_vec());
+                }
+            }
+        }
+
+        for (block, index) in blocks.iter().zip(first..) {
+            let block_start = index * block_size;
+            let from = max(offset, block_start);
+            let to = min(end, block_start + block_size);
+            match block {
+                Some(data) if block_start + data.len() as u64 >= to => {
+                    buf[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                        &data[(from - block_start) as usize..(to - block_start) as usize],
+                    );
+                }
+                // 缓存中的块不完整, 直接从后端读取
+                _ => {
+                    self.invalidate(ino);
+                    return self.read_backend(tf, buf, offset).map(|_| buf.len());
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn set_attr(&self, tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
+        let result = self.backend.set_attr(tf, attr);
+        self.invalidate(tf.ino);
+        result
+    }
+
+    fn rename(
+        &self,
+        tf: &TmpFile,
+        new_path: &Path,
+        rename_time: &SystemTime,
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
+        // 被覆盖的文件使用自己的 ino, 重新打开时失效
+        let result = self.backend.rename(tf, new_path, rename_time, flags);
+        self.invalidate(tf.ino);
+        result
+    }
+
+    fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        self.backend.create_file(tf, mode)
+    }
+
+    fn remove_file(&self, tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
+        let result = self.backend.remove_file(tf, rm_file_time);
+        self.invalidate(tf.ino);
+        result
+    }
+
+    fn make_dir(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        self.backend.make_dir(tf, mode)
+    }
+
+    fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        self.backend.symlink(tf, target)
+    }
+
+    fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        self.backend.read_link(tf)
+    }
+
+    fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        self.backend.link(tf, new_path)
+    }
+
+    fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        self.backend.get_xattr(tf, name)
+    }
+
+    fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        self.backend.set_xattr(tf, name, value, flags)
+    }
+
+    fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        self.backend.list_xattr(tf)
+    }
+
+    fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        self.backend.remove_xattr(tf, name)
+    }
+
+    fn statfs(&self, tf: &TmpFile) -> Result<StatFs, TmpFileError> {
+        self.backend.statfs(tf)
+    }
+
+    fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
+        self.backend.remove_dir(tf, rm_dir_time)
+    }
+
+    fn invalidate_cache(&self, tf: &TmpFile) {
+        self.invalidate(tf.ino);
+        self.backend.invalidate_cache(tf);
+    }
+}
@@ -2,11 +2,21 @@ pub const BLOCK_SIZE: u32 = 4096;
 pub const DEFAULT_HARD_LINKS: u32 = 1;
 pub const RDEV: u32 = 0;
 pub const FLAGS: u32 = 0;
-pub const DEFAULT_PERMISSIONS: u16 = 600;
+pub const DEFAULT_PERMISSIONS: u16 = 0o600;
 pub const MAX_NAME_LENGTH: u32 = 255;
 pub const FMODE_EXEC: i32 = 0x20;
 
+// rename 的标志, 取值与 Linux 的 RENAME_NOREPLACE 和 RENAME_EXCHANGE 一致
+pub const RFUSE_RENAME_NOREPLACE: u32 = 1;
+pub const RFUSE_RENAME_EXCHANGE: u32 = 2;
+
 #[cfg(target_os = "linux")]
 pub const RFUSE_S_ISVTX: u16 = libc::S_ISVTX as u16;
 #[cfg(target_os = "macos")]
 pub const RFUSE_S_ISVTX: u16 = libc::S_ISVTX;
+
+// 扩展属性不存在时的错误码
+#[cfg(target_os = "linux")]
+pub const RFUSE_ENOATTR: i32 = libc::ENODATA;
+#[cfg(target_os = "macos")]
+pub const RFUSE_ENOATTR: i32 = libc::ENOATTR;
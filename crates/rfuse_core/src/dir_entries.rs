@@ -0,0 +1,151 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+};
+
+/// readdir 中 `.` 和 `..` 占用偏移 1 和 2, 目录项从 3 开始
+pub const FIRST_ENTRY_OFFSET: u64 = 3;
+
+/// 文件夹中的一个目录项, primary 为 false 时是指向其他 inode 的硬链接
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: OsString,
+    pub ino: u64,
+    pub primary: bool,
+}
+
+/// 文件夹的目录项索引
+///
+/// 按名字查找和按 ino 删除都是 O(1), 每个目录项在插入时分配一个递增的偏移, 之后不再改变,
+/// 所以 readdir 过程中有其他目录项被插入或删除时, 已经返回的偏移仍然有效
+#[derive(Clone, Debug)]
+pub struct DirEntries {
+    next_offset: u64,
+    entries: BTreeMap<u64, DirEntry>, // 偏移 -> 目录项, 按插入顺序排列
+    names: HashMap<OsString, u64>,    // 名字 -> 偏移
+    primaries: HashMap<u64, u64>,     // 主名字的 ino -> 偏移
+}
+
+impl Default for DirEntries {
+    fn default() -> Self {
+        Self {
+            next_offset: FIRST_ENTRY_OFFSET,
+            entries: BTreeMap::new(),
+            names: HashMap::new(),
+            primaries: HashMap::new(),
+        }
+    }
+}
+
+impl DirEntries {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &OsStr) -> Option<&DirEntry> {
+        self.names
+            .get(name)
+            .and_then(|offset| self.entries.get(offset))
+    }
+
+    /// 按名字查找 ino, 包括硬链接
+    pub fn lookup(&self, name: &OsStr) -> Option<u64> {
+        self.get(name).map(|entry| entry.ino)
+    }
+
+    /// ino 以主名字的身份在这个文件夹中
+    pub fn contains_child(&self, ino: u64) -> bool {
+        self.primaries.contains_key(&ino)
+    }
+
+    /// 插入 inode 的主名字, 名字已经存在或者 ino 已经有主名字时返回 false
+    pub fn insert_child(&mut self, name: impl Into<OsString>, ino: u64) -> bool {
+        if self.primaries.contains_key(&ino) {
+            return false;
+        }
+        self.insert(name.into(), ino, true)
+    }
+
+    /// 插入硬链接, 名字已经存在时返回 false
+    pub fn insert_link(&mut self, name: impl Into<OsString>, ino: u64) -> bool {
+        self.insert(name.into(), ino, false)
+    }
+
+    fn insert(&mut self, name: OsString, ino: u64, primary: bool) -> bool {
+        if self.names.contains_key(&name) {
+            return false;
+        }
+        let offset = self.next_offset;
+        self.next_offset += 1;
+        self.names.insert(name.clone(), offset);
+        if primary {
+            self.primaries.insert(ino, offset);
+        }
+        self.entries.insert(offset, DirEntry { name, ino, primary });
+        true
+    }
+
+    /// 按 ino 删除主名字
+    pub fn remove_child(&mut self, ino: u64) -> Option<DirEntry> {
+        let offset = self.primaries.remove(&ino)?;
+        let entry = self.entries.remove(&offset)?;
+        self.names.remove(&entry.name);
+        Some(entry)
+    }
+
+    /// 按名字删除目录项, 可以是主名字也可以是硬链接
+    pub fn remove(&mut self, name: &OsStr) -> Option<DirEntry> {
+        let offset = self.names.remove(name)?;
+        let entry = self.entries.remove(&offset)?;
+        if entry.primary {
+            self.primaries.remove(&entry.ino);
+        }
+        Some(entry)
+    }
+
+    /// 把硬链接变成主名字, 偏移保持不变
+    pub fn promote(&mut self, name: &OsStr) -> bool {
+        let offset = match self.names.get(name) {
+            Some(offset) => *offset,
+            None => return false,
+        };
+        let entry = self.entries.get_mut(&offset).unwrap();
+        if entry.primary || self.primaries.contains_key(&entry.ino) {
+            return false;
+        }
+        entry.primary = true;
+        self.primaries.insert(entry.ino, offset);
+        true
+    }
+
+    /// 所有主名字的 ino
+    pub fn children(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries
+            .values()
+            .filter(|entry| entry.primary)
+            .map(|entry| entry.ino)
+    }
+
+    /// 所有硬链接 (名字, ino)
+    pub fn links(&self) -> impl Iterator<Item = (&OsStr, u64)> + '_ {
+        self.entries
+            .values()
+            .filter(|entry| !entry.primary)
+            .map(|entry| (entry.name.as_os_str(), entry.ino))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DirEntry> + '_ {
+        self.entries.values()
+    }
+
+    /// 偏移大于 offset 的目录项, 返回 (偏移, 目录项), 用于 readdir 从上次的位置继续读取
+    pub fn iter_from(&self, offset: u64) -> impl Iterator<Item = (u64, &DirEntry)> + '_ {
+        self.entries
+            .range(offset.saturating_add(1)..)
+            .map(|(offset, entry)| (*offset, entry))
+    }
+}
@@ -1,7 +1,8 @@
 use fuser::{FileAttr, FUSE_ROOT_ID};
 
-use crate::common::*;
+use crate::{common::*, dir_entries::DirEntries};
 use std::{
+    ffi::OsString,
     fmt::Display,
     sync::atomic::{AtomicU64, Ordering},
     time::SystemTime,
@@ -19,13 +20,19 @@ fn alloc_ino() -> u64 {
 pub enum InodeKind {
     File,
     Directory,
+    Symlink,
 }
 
 #[derive(Clone, Debug)]
 pub struct Inode {
     pub ino: u64,
     pub parent_ino: u64,
-    pub children_ino: Vec<u64>,
+    // 文件夹中的目录项, 包括指向其他 inode 的硬链接, 每个 inode 只在一个文件夹中有主名字
+    pub entries: DirEntries,
+    // 文件的名字数量, 文件夹的链接数由 RFuseFS 根据子文件夹计算
+    pub nlink: u32,
+    // 文件夹的子文件是否已经从后端读取, 没有读取的文件夹在第一次访问时读取
+    pub loaded: bool,
     pub attr: InodeAttributes,
 }
 
@@ -33,9 +40,11 @@ pub struct Inode {
 pub struct InodeAttributes {
     pub id: String,
     pub size: u64,
-    pub name: String,
+    // 名字和路径可以包含任意字节, 不要求是 UTF-8
+    pub name: OsString,
     pub kind: InodeKind,
-    pub path: String,
+    // 相对于挂载根目录的文件夹路径, 以 `/` 结尾
+    pub path: OsString,
     pub atime: SystemTime,
     pub mtime: SystemTime,
     pub ctime: SystemTime,
@@ -49,26 +58,13 @@ impl Inode {
         Self {
             ino: alloc_ino(),
             parent_ino,
-            children_ino: Vec::new(),
+            entries: DirEntries::default(),
+            nlink: DEFAULT_HARD_LINKS,
+            loaded: true,
             attr,
         }
     }
 
-    pub fn insert_child(&mut self, child: u64) {
-        self.children_ino.push(child);
-    }
-
-    pub fn remove_child(&mut self, child: u64) {
-        let mut index = 0;
-        for ele in self.children_ino.iter() {
-            if *ele == child {
-                break;
-            }
-            index += 1;
-        }
-        self.children_ino.remove(index);
-    }
-
     pub fn file_attr(&self) -> FileAttr {
         let attrs = &self.attr;
         FileAttr {
@@ -81,7 +77,8 @@ impl Inode {
             crtime: attrs.ctime,
             kind: attrs.kind.into(),
             perm: attrs.permissions,
-            nlink: DEFAULT_HARD_LINKS,
+            // 没有子文件夹的文件夹链接数为 2 (自身和 `.`)
+            nlink: if self.is_dir() { 2 } else { self.nlink },
             uid: attrs.uid,
             gid: attrs.gid,
             rdev: RDEV,
@@ -100,14 +97,14 @@ impl Inode {
 }
 
 impl InodeAttributes {
-    pub fn new(name: String, kind: InodeKind, path: String) -> Self {
+    pub fn new(name: impl Into<OsString>, kind: InodeKind, path: impl Into<OsString>) -> Self {
         let now = SystemTime::now();
         Self {
             id: alloc_ino().to_string(),
             size: BLOCK_SIZE as u64,
-            name,
+            name: name.into(),
             kind,
-            path,
+            path: path.into(),
             atime: now,
             mtime: now,
             ctime: now,
@@ -123,6 +120,7 @@ impl Display for InodeKind {
         match self {
             InodeKind::Directory => write!(f, "file"),
             InodeKind::File => write!(f, "directory"),
+            InodeKind::Symlink => write!(f, "symlink"),
         }
     }
 }
@@ -132,6 +130,7 @@ impl From<InodeKind> for FileType {
         match value {
             InodeKind::Directory => FileType::Directory,
             InodeKind::File => FileType::RegularFile,
+            InodeKind::Symlink => FileType::Symlink,
         }
     }
 }
@@ -140,8 +139,8 @@ pub fn root_node(fs_name: &str, mountpoint: String, permissions: u16, uid: u32,
     let attr = InodeAttributes {
         id: fs_name.to_string(),
         size: BLOCK_SIZE as u64,
-        name: fs_name.to_string(),
-        path: mountpoint,
+        name: OsString::from(fs_name),
+        path: OsString::from(mountpoint),
         kind: InodeKind::Directory,
         atime: SystemTime::now(),
         mtime: SystemTime::now(),
@@ -153,7 +152,9 @@ pub fn root_node(fs_name: &str, mountpoint: String, permissions: u16, uid: u32,
     Inode {
         ino: FUSE_ROOT_ID,
         parent_ino: FUSE_ROOT_ID,
-        children_ino: Vec::new(),
+        entries: DirEntries::default(),
+        nlink: DEFAULT_HARD_LINKS,
+        loaded: true,
         attr,
     }
 }
@@ -1,6 +1,12 @@
+pub mod async_tmp_file;
+pub mod block_cache;
 pub mod common;
+pub mod dir_entries;
 pub mod inode;
+pub mod notify;
 pub mod remote_fs;
+pub mod shared_fs;
 pub mod sys_fs;
 pub mod tmp_file;
 pub mod utils;
+pub mod worker;
@@ -0,0 +1,62 @@
+use std::{ffi::OsString, sync::mpsc::Receiver};
+
+use fuser::Notifier;
+use log::{debug, info};
+
+/// 源目录发生的改动, 由监听线程发送给 RFuseFS
+///
+/// 路径都是相对于源目录的路径, 以 `/` 开头, 例如 `/dir/file`, 可以包含非 UTF-8 的字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginChange {
+    // 新建了文件或者文件夹
+    Create(OsString),
+    // 文件的内容或者属性发生了变化
+    Modify(OsString),
+    // 文件或者文件夹被删除
+    Remove(OsString),
+    // 源目录内部的改名
+    Rename { from: OsString, to: OsString },
+    // 无法确定改动的范围 (例如事件队列溢出), 重新加载整个 inode 表
+    Reload,
+}
+
+/// 需要推送给内核的缓存失效
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+    // 目录项失效, 用于新增或者改名的文件
+    Entry {
+        parent: u64,
+        name: OsString,
+    },
+    // 文件属性和数据失效
+    Inode {
+        ino: u64,
+    },
+    // 目录项被删除
+    Delete {
+        parent: u64,
+        child: u64,
+        name: OsString,
+    },
+}
+
+// 不能在 RFuseFS 的回调中直接调用 Notifier, 内核可能持有目录锁等待回调返回, 所以放到单独的线程中推送
+pub fn notify_kernel(notifier: Notifier, invalidations: Receiver<Invalidation>) {
+    info!("[notify_kernel] Invalidation thread started");
+    for invalidation in invalidations {
+        let result = match &invalidation {
+            Invalidation::Entry { parent, name } => notifier.inval_entry(*parent, name),
+            Invalidation::Inode { ino } => notifier.inval_inode(*ino, 0, 0),
+            Invalidation::Delete {
+                parent,
+                child,
+                name,
+            } => notifier.delete(*parent, *child, name),
+        };
+        // 内核中没有对应的缓存时会返回 ENOENT, 可以忽略
+        if let Err(e) = result {
+            debug!("[notify_kernel] {:?} failed: {}", invalidation, e);
+        }
+    }
+    info!("[notify_kernel] Invalidation thread stopped");
+}
@@ -1,17 +1,25 @@
 use core::fmt;
-use std::{collections::HashMap, sync::RwLock, time::SystemTime};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 
+use fuser::FUSE_ROOT_ID;
 use log::{debug, error};
 
 use crate::{
     inode::{Inode, InodeAttributes},
-    tmp_file::{TmpFile, TmpFileTrait},
+    tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait},
+    worker::FileIo,
 };
 
 pub type InitFsFuncType = dyn Fn(
         &mut RemoteFileManager,
         &mut HashMap<u64, Inode>,
-        String,
+        PathBuf,
     ) -> Result<(), RemoteFileInitializeError>
     + 'static
     + Send;
@@ -31,9 +39,9 @@ impl fmt::Display for RemoteFileInitializeError {
 }
 
 pub struct RemoteFileManager {
-    pub tmp_file_map: HashMap<u64, TmpFile>,
+    pub tmp_file_map: HashMap<u64, Arc<TmpFile>>, // 工作线程读写时持有同一个 TmpFile
     pub init_fs: Box<InitFsFuncType>,
-    pub tmp_file_trait: Box<dyn TmpFileTrait>,
+    pub tmp_file_trait: Arc<dyn TmpFileTrait>,
 }
 
 impl RemoteFileManager {
@@ -41,7 +49,7 @@ impl RemoteFileManager {
         RemoteFileManager {
             tmp_file_map: HashMap::new(),
             init_fs,
-            tmp_file_trait,
+            tmp_file_trait: Arc::from(tmp_file_trait),
         }
     }
 
@@ -49,35 +57,103 @@ impl RemoteFileManager {
         self.tmp_file_map.contains_key(&ino)
     }
 
-    pub fn add_file(&mut self, ino: u64, file_name: String, path: String) {
-        self.tmp_file_map.insert(
-            ino,
-            TmpFile {
-                file_name,
-                path,
-                lock: RwLock::new(()),
-            },
-        );
+    pub fn add_file(&mut self, ino: u64, file_name: OsString, path: PathBuf) {
+        // 已经存在的文件换成新的路径, 沿用原来的读写锁
+        let tf = match self.tmp_file_map.get(&ino) {
+            Some(tf) => tf.renamed(file_name, path),
+            None => TmpFile::new(file_name, path).with_ino(ino),
+        };
+        self.tmp_file_map.insert(ino, Arc::new(tf));
+    }
+
+    /// 重新初始化之后仍然存在的文件沿用 old_files 中的读写锁
+    pub fn keep_locks(&mut self, old_files: &HashMap<u64, Arc<TmpFile>>) {
+        for (ino, tf) in self.tmp_file_map.iter_mut() {
+            if let Some(old) = old_files.get(ino) {
+                *tf = Arc::new(old.renamed(tf.file_name.clone(), tf.path.clone()));
+            }
+        }
+    }
+
+    pub fn tmp_file(&self, ino: u64) -> Option<Arc<TmpFile>> {
+        self.tmp_file_map.get(&ino).cloned()
+    }
+
+    // 按路径读写文件的方式, 可以交给工作线程执行
+    pub fn file_io(&self, ino: u64) -> Result<FileIo, TmpFileError> {
+        match self.tmp_file_map.get(&ino) {
+            Some(tf) => Ok(FileIo::Path(self.tmp_file_trait.clone(), tf.clone())),
+            None => {
+                error!("[RemoteFileManager][file_io]file not found, ino: {}", ino);
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    // 源文件已经在外部被删除, 只从缓存中移除
+    pub fn forget_file(&mut self, ino: u64) {
+        self.tmp_file_map.remove(&ino);
+    }
+
+    // 按名字和路径读取源文件的属性, 不要求文件已经在缓存中, path 为所在目录的完整路径
+    pub fn get_attr(&self, name: OsString, path: PathBuf) -> Result<Inode, TmpFileError> {
+        let tf = TmpFile::new(name, path);
+        match self.tmp_file_trait.get_attr(&tf) {
+            Ok(inode) => Ok(inode),
+            Err(e) => {
+                debug!("[RemoteFileManager][get_attr] failed: {}", e);
+                Err(e)
+            }
+        }
     }
 
-    pub fn read(&self, ino: u64, buf: &mut [u8], offset: u64) -> Result<(), &str> {
+    // 读取源目录下的所有文件, 参数同 get_attr
+    pub fn read_dir(&self, name: OsString, path: PathBuf) -> Result<Vec<Inode>, TmpFileError> {
+        let tf = TmpFile::new(name, path);
+        match self.tmp_file_trait.read_dir(&tf) {
+            Ok(children) => Ok(children),
+            Err(e) => {
+                error!("[RemoteFileManager][read_dir] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn read(&self, ino: u64, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
         let inode = match self.tmp_file_map.get(&ino) {
             Some(i) => i,
             None => {
                 error!("[RemoteFileManager][read]file not found, ino: {}", ino);
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self.tmp_file_trait.read_exact(inode, buf, offset) {
             Ok(_) => {}
             Err(e) => {
                 error!("[RemoteFileManager][read] failed: {}", e);
-                return Err("read file failed");
+                return Err(e);
             }
         };
         Ok(())
     }
 
+    pub fn open(&self, ino: u64, flags: i32) -> Result<Option<Box<dyn FileHandle>>, TmpFileError> {
+        let inode = match self.tmp_file_map.get(&ino) {
+            Some(i) => i,
+            None => {
+                error!("[RemoteFileManager][open]file not found, ino: {}", ino);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        match self.tmp_file_trait.open(inode, flags) {
+            Ok(handle) => Ok(handle),
+            Err(e) => {
+                error!("[RemoteFileManager][open] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
     pub fn read_all(&self, ino: u64) -> Vec<u8> {
         let inode = match self.tmp_file_map.get(&ino) {
             Some(i) => i,
@@ -101,7 +177,7 @@ impl RemoteFileManager {
         data: &[u8],
         write_time: &SystemTime,
         offset: u64,
-    ) -> Result<(), &str> {
+    ) -> Result<(), TmpFileError> {
         let tmp = match self.tmp_file_map.get(&ino) {
             Some(t) => t,
             None => {
@@ -109,14 +185,14 @@ impl RemoteFileManager {
                     "[RemoteFileManager][write_file]file not found, ino: {}",
                     ino
                 );
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self.tmp_file_trait.write(tmp, data, write_time, offset) {
             Ok(_) => {}
             Err(e) => {
                 error!("[RemoteFileManager][write_file] failed: {}", e);
-                return Err("write file failed");
+                return Err(e);
             }
         };
         Ok(())
@@ -126,39 +202,42 @@ impl RemoteFileManager {
         &mut self,
         ino: u64,
         attr: InodeAttributes,
-        path: String,
-    ) -> Result<Inode, &str> {
-        debug!("[RemoteFileManager][new_file] ino: {}, path: {}", ino, path);
-        let inode = TmpFile {
-            file_name: attr.name.clone(),
-            path: path.clone(),
-            lock: RwLock::new(()),
-        };
+        path: PathBuf,
+    ) -> Result<Inode, TmpFileError> {
+        debug!(
+            "[RemoteFileManager][new_file] ino: {}, path: {:?}",
+            ino, path
+        );
+        let inode = TmpFile::new(attr.name.clone(), path);
 
-        let meta = match self.tmp_file_trait.create_file(&inode) {
+        let meta = match self
+            .tmp_file_trait
+            .create_file(&inode, attr.permissions.into())
+        {
             Ok(m) => m,
             Err(e) => {
                 error!("[RemoteFileManager][new_file]create file failed: {}", e);
-                return Err("create file failed");
+                return Err(e);
             }
         };
-        self.tmp_file_map.insert(meta.ino(), inode);
+        self.tmp_file_map
+            .insert(meta.ino(), Arc::new(inode.with_ino(meta.ino())));
         Ok(meta)
     }
 
-    pub fn set_attr(&mut self, ino: u64, attr: &InodeAttributes) -> Result<(), &str> {
+    pub fn set_attr(&mut self, ino: u64, attr: &InodeAttributes) -> Result<(), TmpFileError> {
         let inode = match self.tmp_file_map.get_mut(&ino) {
             Some(t) => t,
             None => {
                 error!("[RemoteFileManager][set_attr]file not found, ino: {}", ino);
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self.tmp_file_trait.set_attr(inode, attr) {
             Ok(_) => Ok(()),
             Err(e) => {
                 error!("[RemoteFileManager][set_attr] failed: {}", e);
-                Err("set attr failed")
+                Err(e)
             }
         }
     }
@@ -166,33 +245,34 @@ impl RemoteFileManager {
     pub fn rename(
         &mut self,
         ino: u64,
-        new_name: String,
-        new_path: String,
+        new_name: OsString,
+        new_path: PathBuf,
         rename_time: &SystemTime,
-    ) -> Result<(), &str> {
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
         let inode = match self.tmp_file_map.get_mut(&ino) {
             Some(t) => t,
             None => {
                 error!("[RemoteFileManager][rename]file not found, ino: {}", ino);
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self
             .tmp_file_trait
-            .rename(inode, new_path.clone() + new_name.as_str(), rename_time)
+            .rename(inode, &new_path.join(&new_name), rename_time, flags)
         {
             Ok(_) => {}
             Err(e) => {
                 error!("[RemoteFileManager][rename] failed: {}", e);
-                return Err("rename failed");
+                return Err(e);
             }
         };
-        inode.file_name = new_name;
-        inode.path = new_path;
+        // 正在进行的读写仍然持有旧的 TmpFile, 这里换成新的路径, 读写锁不变
+        *inode = Arc::new(inode.renamed(new_name, new_path));
         Ok(())
     }
 
-    pub fn remove_file(&mut self, ino: u64, rm_file_time: &SystemTime) -> Result<(), &str> {
+    pub fn remove_file(&mut self, ino: u64, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
         let inode = match self.tmp_file_map.get(&ino) {
             Some(t) => t,
             None => {
@@ -200,26 +280,22 @@ impl RemoteFileManager {
                     "[RemoteFileManager][remove_file]file not found, ino: {}, rm_file_time: {:?}",
                     ino, rm_file_time
                 );
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self.tmp_file_trait.remove_file(inode, rm_file_time) {
             Ok(_) => {}
             Err(e) => {
                 error!("[RemoteFileManager][remove_file] failed: {}", e);
-                return Err("remove file failed");
+                return Err(e);
             }
         };
         self.tmp_file_map.remove(&ino);
         Ok(())
     }
 
-    pub fn mk_dir(&mut self, attr: &InodeAttributes, path: String) -> Result<Inode, &str> {
-        let inode = TmpFile {
-            file_name: attr.name.clone(),
-            path,
-            lock: RwLock::new(()),
-        };
+    pub fn mk_dir(&mut self, attr: &InodeAttributes, path: PathBuf) -> Result<Inode, TmpFileError> {
+        let inode = TmpFile::new(attr.name.clone(), path);
         let dir = match self
             .tmp_file_trait
             .make_dir(&inode, attr.permissions.into())
@@ -227,14 +303,182 @@ impl RemoteFileManager {
             Ok(d) => d,
             Err(e) => {
                 error!("[RemoteFileManager][mk_dir] failed: {}", e);
-                return Err("make dir failed");
+                return Err(e);
             }
         };
-        self.tmp_file_map.insert(dir.ino(), inode);
+        self.tmp_file_map
+            .insert(dir.ino(), Arc::new(inode.with_ino(dir.ino())));
         Ok(dir)
     }
 
-    pub fn remove_dir(&mut self, ino: u64, rm_dir_time: &SystemTime) -> Result<(), &str> {
+    pub fn symlink(
+        &mut self,
+        attr: &InodeAttributes,
+        path: PathBuf,
+        target: &Path,
+    ) -> Result<Inode, TmpFileError> {
+        let inode = TmpFile::new(attr.name.clone(), path);
+        let link = match self.tmp_file_trait.symlink(&inode, target) {
+            Ok(l) => l,
+            Err(e) => {
+                error!("[RemoteFileManager][symlink] failed: {}", e);
+                return Err(e);
+            }
+        };
+        self.tmp_file_map
+            .insert(link.ino(), Arc::new(inode.with_ino(link.ino())));
+        Ok(link)
+    }
+
+    pub fn read_link(&self, ino: u64) -> Result<PathBuf, TmpFileError> {
+        let inode = match self.tmp_file_map.get(&ino) {
+            Some(i) => i,
+            None => {
+                error!("[RemoteFileManager][read_link]file not found, ino: {}", ino);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        match self.tmp_file_trait.read_link(inode) {
+            Ok(target) => Ok(target),
+            Err(e) => {
+                error!("[RemoteFileManager][read_link] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn link(
+        &self,
+        ino: u64,
+        new_name: OsString,
+        new_path: PathBuf,
+    ) -> Result<(), TmpFileError> {
+        let inode = match self.tmp_file_map.get(&ino) {
+            Some(i) => i,
+            None => {
+                error!("[RemoteFileManager][link]file not found, ino: {}", ino);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        match self.tmp_file_trait.link(inode, &new_path.join(&new_name)) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("[RemoteFileManager][link] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    // 硬链接不在 tmp_file_map 中, 这里根据名字和路径直接操作
+    pub fn remove_link(
+        &self,
+        name: OsString,
+        path: PathBuf,
+        rm_file_time: &SystemTime,
+    ) -> Result<(), TmpFileError> {
+        let link = TmpFile::new(name, path);
+        match self.tmp_file_trait.remove_file(&link, rm_file_time) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("[RemoteFileManager][remove_link] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn rename_link(
+        &self,
+        name: OsString,
+        path: PathBuf,
+        new_path: PathBuf,
+        rename_time: &SystemTime,
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
+        let link = TmpFile::new(name, path);
+        match self
+            .tmp_file_trait
+            .rename(&link, &new_path, rename_time, flags)
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("[RemoteFileManager][rename_link] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    // 源文件在挂载点之外被修改, 让后端丢弃缓存的内容
+    pub fn invalidate_cache(&self, ino: u64) {
+        if let Some(tf) = self.tmp_file_map.get(&ino) {
+            self.tmp_file_trait.invalidate_cache(tf);
+        }
+    }
+
+    pub fn get_xattr(&self, ino: u64, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        match self.tmp_file_map.get(&ino) {
+            Some(inode) => self.tmp_file_trait.get_xattr(inode, name),
+            None => {
+                debug!("[RemoteFileManager][get_xattr]file not found, ino: {}", ino);
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn set_xattr(
+        &self,
+        ino: u64,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        match self.tmp_file_map.get(&ino) {
+            Some(inode) => self.tmp_file_trait.set_xattr(inode, name, value, flags),
+            None => {
+                debug!("[RemoteFileManager][set_xattr]file not found, ino: {}", ino);
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn list_xattr(&self, ino: u64) -> Result<Vec<u8>, TmpFileError> {
+        match self.tmp_file_map.get(&ino) {
+            Some(inode) => self.tmp_file_trait.list_xattr(inode),
+            None => {
+                debug!(
+                    "[RemoteFileManager][list_xattr]file not found, ino: {}",
+                    ino
+                );
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn remove_xattr(&self, ino: u64, name: &str) -> Result<(), TmpFileError> {
+        match self.tmp_file_map.get(&ino) {
+            Some(inode) => self.tmp_file_trait.remove_xattr(inode, name),
+            None => {
+                debug!(
+                    "[RemoteFileManager][remove_xattr]file not found, ino: {}",
+                    ino
+                );
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    // 查询 path 所在文件系统的容量信息, path 为目录的完整路径
+    pub fn statfs(&self, path: PathBuf) -> Result<StatFs, TmpFileError> {
+        let tf = TmpFile::new(OsString::new(), path);
+        match self.tmp_file_trait.statfs(&tf) {
+            Ok(stat) => Ok(stat),
+            Err(e) => {
+                error!("[RemoteFileManager][statfs] failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn remove_dir(&mut self, ino: u64, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
         let inode = match self.tmp_file_map.get(&ino) {
             Some(t) => t,
             None => {
@@ -242,14 +486,14 @@ impl RemoteFileManager {
                     "[RemoteFileManager][remove_dir]file not found, ino: {}, rm_dir_time: {:?}",
                     ino, rm_dir_time
                 );
-                return Err("file not found");
+                return Err(TmpFileError::Errno(libc::ENOENT));
             }
         };
         match self.tmp_file_trait.remove_dir(inode, rm_dir_time) {
             Ok(_) => {}
             Err(e) => {
                 error!("[RemoteFileManager][remove_dir] failed: {}", e);
-                return Err("remove dir failed");
+                return Err(e);
             }
         };
         self.tmp_file_map.remove(&ino);
@@ -259,9 +503,17 @@ impl RemoteFileManager {
     pub fn initialize_fs(
         &mut self,
         inodes: &mut HashMap<u64, Inode>,
-        source_dir: String,
+        source_dir: PathBuf,
     ) -> Result<(), RemoteFileInitializeError> {
+        // init_fs 需要 &mut self, 调用期间先取出来, 调用之后放回去, 之后还可以重新初始化
         let init_fs = std::mem::replace(&mut self.init_fs, Box::new(|_, _, _| Ok(())));
-        init_fs(self, inodes, source_dir)
+        let result = init_fs(self, inodes, source_dir.clone());
+        self.init_fs = init_fs;
+        result?;
+        // 根节点不在任何文件夹中, 初始化函数不会登记它, 根目录上的扩展属性等操作需要它
+        let mut root_dir = source_dir.into_os_string();
+        root_dir.push("/");
+        self.add_file(FUSE_ROOT_ID, OsString::new(), PathBuf::from(root_dir));
+        Ok(())
     }
 }
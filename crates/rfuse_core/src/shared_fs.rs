@@ -0,0 +1,298 @@
+use std::{
+    ffi::OsStr,
+    path::Path,
+    sync::{mpsc::Receiver, Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use fuser::{
+    Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEntry, ReplyOpen,
+    ReplyStatfs, ReplyXattr, Request, TimeOrNow,
+};
+use log::info;
+
+use crate::{notify::OriginChange, sys_fs::RFuseFS};
+
+/// 在 fuser 的请求线程和应用源目录改动的线程之间共享的 RFuseFS
+///
+/// fuser 在一个线程中按顺序分发请求, 这里的锁只会和源目录改动的线程竞争
+#[derive(Clone)]
+pub struct SharedFS(Arc<Mutex<RFuseFS>>);
+
+impl SharedFS {
+    pub fn new(rfs: RFuseFS) -> Self {
+        Self(Arc::new(Mutex::new(rfs)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, RFuseFS> {
+        self.0.lock().unwrap()
+    }
+}
+
+// 源目录的改动不等下一次请求, 收到之后马上更新 inode 表, 缓存失效交给 notify_kernel 的线程推送
+pub fn consume_origin_changes(fs: SharedFS, origin_changes: Receiver<OriginChange>) {
+    info!("[consume_origin_changes] Origin change thread started");
+    while let Ok(change) = origin_changes.recv() {
+        // 一次处理已经到达的所有改动, 其中有重新加载时只需要重新加载一次
+        let mut changes = vec![change];
+        changes.extend(origin_changes.try_iter());
+        fs.lock().apply_origin_changes(changes);
+    }
+    info!("[consume_origin_changes] Origin change thread stopped");
+}
+
+impl Filesystem for SharedFS {
+    fn init(&mut self, req: &Request, config: &mut fuser::KernelConfig) -> Result<(), libc::c_int> {
+        self.lock().init(req, config)
+    }
+
+    fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        self.lock().lookup(req, parent, name, reply)
+    }
+
+    fn getattr(&mut self, req: &Request, ino: u64, fh: Option<u64>, reply: ReplyAttr) {
+        self.lock().getattr(req, ino, fh, reply)
+    }
+
+    fn setattr(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        crtime: Option<SystemTime>,
+        chgtime: Option<SystemTime>,
+        bkuptime: Option<SystemTime>,
+        flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        self.lock().setattr(
+            req, ino, mode, uid, gid, size, atime, mtime, ctime, fh, crtime, chgtime, bkuptime,
+            flags, reply,
+        )
+    }
+
+    fn readlink(&mut self, req: &Request<'_>, ino: u64, reply: ReplyData) {
+        self.lock().readlink(req, ino, reply)
+    }
+
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.lock().open(req, ino, flags, reply)
+    }
+
+    fn read(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        flags: i32,
+        lock: Option<u64>,
+        reply: ReplyData,
+    ) {
+        self.lock()
+            .read(req, ino, fh, offset, size, flags, lock, reply)
+    }
+
+    fn readdir(&mut self, req: &Request, ino: u64, fh: u64, offset: i64, reply: ReplyDirectory) {
+        self.lock().readdir(req, ino, fh, offset, reply)
+    }
+
+    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        self.lock().rmdir(req, parent, name, reply)
+    }
+
+    fn mkdir(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        reply: ReplyEntry,
+    ) {
+        self.lock().mkdir(req, parent, name, mode, umask, reply)
+    }
+
+    fn symlink(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        self.lock().symlink(req, parent, link_name, target, reply)
+    }
+
+    fn link(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        newparent: u64,
+        newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        self.lock().link(req, ino, newparent, newname, reply)
+    }
+
+    fn rename(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock()
+            .rename(req, parent, name, newparent, newname, flags, reply)
+    }
+
+    fn write(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        write_flags: u32,
+        flags: i32,
+        lock_owner: Option<u64>,
+        reply: fuser::ReplyWrite,
+    ) {
+        self.lock().write(
+            req,
+            ino,
+            fh,
+            offset,
+            data,
+            write_flags,
+            flags,
+            lock_owner,
+            reply,
+        )
+    }
+
+    fn create(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        self.lock()
+            .create(req, parent, name, mode, umask, flags, reply)
+    }
+
+    fn flush(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock().flush(req, ino, fh, lock_owner, reply)
+    }
+
+    fn release(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        flags: i32,
+        lock_owner: Option<u64>,
+        flush: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock()
+            .release(req, ino, fh, flags, lock_owner, flush, reply)
+    }
+
+    fn fsync(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock().fsync(req, ino, fh, datasync, reply)
+    }
+
+    fn opendir(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.lock().opendir(req, ino, flags, reply)
+    }
+
+    fn releasedir(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        flags: i32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock().releasedir(req, ino, fh, flags, reply)
+    }
+
+    fn statfs(&mut self, req: &Request<'_>, ino: u64, reply: ReplyStatfs) {
+        self.lock().statfs(req, ino, reply)
+    }
+
+    fn setxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        position: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        self.lock()
+            .setxattr(req, ino, name, value, flags, position, reply)
+    }
+
+    fn getxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        self.lock().getxattr(req, ino, name, size, reply)
+    }
+
+    fn listxattr(&mut self, req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        self.lock().listxattr(req, ino, size, reply)
+    }
+
+    fn removexattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        self.lock().removexattr(req, ino, name, reply)
+    }
+
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
+        self.lock().access(req, ino, mask, reply)
+    }
+
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        self.lock().unlink(req, parent, name, reply)
+    }
+
+    fn destroy(&mut self) {
+        self.lock().destroy()
+    }
+}
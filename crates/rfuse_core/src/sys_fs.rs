@@ -1,65 +1,527 @@
 use std::{
     cmp::min,
-    collections::HashMap,
-    ffi::OsStr,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
     time::{Duration, SystemTime},
 };
 
 use fuser::{
-    consts::FOPEN_DIRECT_IO, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData,
-    ReplyDirectory, ReplyEntry, ReplyOpen, Request, TimeOrNow,
+    consts::{FOPEN_DIRECT_IO, FOPEN_KEEP_CACHE, FUSE_WRITEBACK_CACHE},
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, ReplyStatfs, ReplyXattr, Request, TimeOrNow, FUSE_ROOT_ID,
 };
 use libc::ENOENT;
-use log::{debug, info};
+use log::{debug, error, info};
+use tokio::runtime::Handle;
 
 use crate::{
-    common::{FMODE_EXEC, MAX_NAME_LENGTH, RFUSE_S_ISVTX},
+    async_tmp_file::{AsyncTmpFileTrait, SyncAdapter},
+    common::{
+        BLOCK_SIZE, FMODE_EXEC, MAX_NAME_LENGTH, RFUSE_ENOATTR, RFUSE_RENAME_EXCHANGE,
+        RFUSE_RENAME_NOREPLACE, RFUSE_S_ISVTX,
+    },
+    dir_entries::{DirEntries, FIRST_ENTRY_OFFSET},
     inode::{Inode, InodeAttributes, InodeKind},
+    notify::{Invalidation, OriginChange},
     remote_fs::{InitFsFuncType, RemoteFileManager},
-    tmp_file::TmpFileTrait,
+    tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait},
     utils::check_access,
+    worker::{FileIo, ReadTask, WorkerPool, WriteDone, WriteTask},
 };
 
 pub enum RFuseFSOP {
-    ReInItFs,
     Exit,
     Nothing,
 }
 
+/// 发起请求的用户, 对应 fuser 中 Request 的 uid 和 gid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Caller {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl From<&Request<'_>> for Caller {
+    fn from(req: &Request<'_>) -> Self {
+        Self {
+            uid: req.uid(),
+            gid: req.gid(),
+        }
+    }
+}
+
+/// setattr 中需要修改的属性, 为 None 的保持不变
+#[derive(Debug, Default)]
+pub struct SetAttr {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub size: Option<u64>,
+    pub atime: Option<TimeOrNow>,
+    pub mtime: Option<TimeOrNow>,
+}
+
+/// 内核缓存相关的选项, 默认不缓存任何内容并且绕过页缓存
+///
+/// fuser 的目录项回复中属性和目录项共用一个 TTL, 所以 lookup, mkdir 等返回的属性使用 `entry_ttl`,
+/// `attr_ttl` 只用于 getattr 和 setattr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheOptions {
+    pub attr_ttl: Duration,     // 文件属性的缓存时间
+    pub entry_ttl: Duration,    // 目录项的缓存时间
+    pub negative_ttl: Duration, // 不存在的名字的缓存时间, 为 0 时直接返回 ENOENT
+    pub direct_io: bool,        // 读写绕过内核的页缓存
+    pub keep_cache: bool,       // 打开文件时保留之前的页缓存, 只在不使用 direct_io 时生效
+    pub writeback_cache: bool,  // 写入先留在内核的页缓存中, 由内核合并后写回
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            attr_ttl: Duration::ZERO,
+            entry_ttl: Duration::ZERO,
+            negative_ttl: Duration::ZERO,
+            direct_io: true,
+            keep_cache: false,
+            writeback_cache: false,
+        }
+    }
+}
+
+impl CacheOptions {
+    /// open 和 create 回复中的 open_flags
+    pub fn open_flags(&self) -> u32 {
+        if self.direct_io {
+            FOPEN_DIRECT_IO
+        } else if self.keep_cache {
+            FOPEN_KEEP_CACHE
+        } else {
+            0
+        }
+    }
+}
+
+// 不存在的名字对应的目录项
+fn negative_entry() -> FileAttr {
+    FileAttr {
+        ino: 0,
+        size: 0,
+        blocks: 0,
+        atime: SystemTime::UNIX_EPOCH,
+        mtime: SystemTime::UNIX_EPOCH,
+        ctime: SystemTime::UNIX_EPOCH,
+        crtime: SystemTime::UNIX_EPOCH,
+        kind: FileType::RegularFile,
+        perm: 0,
+        nlink: 0,
+        uid: 0,
+        gid: 0,
+        rdev: 0,
+        blksize: 0,
+        flags: 0,
+    }
+}
+
+// 文件夹中子文件的 path
+fn children_path(dir: &Inode) -> OsString {
+    let mut path = dir.attr.path.clone();
+    if !dir.attr.name.is_empty() {
+        path.push(&dir.attr.name);
+        path.push("/");
+    }
+    path
+}
+
+// inode 表中所有的目录项 (父目录, 名字, ino), 包括硬链接
+fn dir_entries(inodes: &HashMap<u64, Inode>) -> HashSet<(u64, OsString, u64)> {
+    let mut entries = HashSet::new();
+    for dir in inodes.values() {
+        for entry in dir.entries.iter() {
+            entries.insert((dir.ino, entry.name.clone(), entry.ino));
+        }
+    }
+    entries
+}
+
+// 将源目录中的相对路径拆分为 (所在文件夹, 名字), 文件夹以 `/` 结尾, 根目录的名字为空
+fn split_origin_path(path: &OsStr) -> (&OsStr, &OsStr) {
+    let mut path = path.as_bytes();
+    while let Some(rest) = path.strip_suffix(b"/") {
+        path = rest;
+    }
+    match path.iter().rposition(|b| *b == b'/') {
+        Some(index) => {
+            let (dir, name) = path.split_at(index + 1);
+            (OsStr::from_bytes(dir), OsStr::from_bytes(name))
+        }
+        None => (OsStr::new("/"), OsStr::from_bytes(path)),
+    }
+}
+
+// 挂载目录中的文件夹路径在源目录中对应的路径, path 以 `/` 开头, 所以直接拼接而不是 join
+fn origin_dir(source_dir: &Path, path: &OsStr) -> PathBuf {
+    let mut dir = source_dir.as_os_str().to_os_string();
+    dir.push(path);
+    PathBuf::from(dir)
+}
+
+// 回复扩展属性的值或者名字列表, size 为 0 时只返回长度
+fn reply_xattr(reply: ReplyXattr, size: u32, data: Result<Vec<u8>, libc::c_int>) {
+    match data {
+        Ok(data) => {
+            if size == 0 {
+                reply.size(data.len() as u32);
+            } else if data.len() <= size as usize {
+                reply.data(&data);
+            } else {
+                reply.error(libc::ERANGE);
+            }
+        }
+        Err(errno) => reply.error(errno),
+    }
+}
+
+// 补全后端返回的容量信息并回复, 文件名长度受 RFuseFS 自身的限制
+fn reply_statfs(reply: ReplyStatfs, stat: StatFs) {
+    let namelen = if stat.namelen == 0 {
+        MAX_NAME_LENGTH
+    } else {
+        min(stat.namelen, MAX_NAME_LENGTH)
+    };
+    let bsize = if stat.bsize == 0 {
+        BLOCK_SIZE
+    } else {
+        stat.bsize
+    };
+    let frsize = if stat.frsize == 0 { bsize } else { stat.frsize };
+    reply.statfs(
+        stat.blocks,
+        stat.bfree,
+        stat.bavail,
+        stat.files,
+        stat.ffree,
+        bsize,
+        namelen,
+        frsize,
+    );
+}
+
+// 异步后端和等待它的 tokio runtime
+struct AsyncBackend {
+    backend: Arc<dyn AsyncTmpFileTrait>,
+    runtime: Handle,
+}
+
+// 一次 open 对应的文件句柄, handle 为 None 时按路径读写
+struct OpenFile {
+    ino: u64,
+    flags: i32,                          // open 时传入的标志, 用于处理 O_APPEND
+    handle: Option<Arc<dyn FileHandle>>, // 工作线程读写时持有同一个句柄
+}
+
 pub struct RFuseFS {
     fs_name: String, // 后期可能会有多个目录的需求，这个先保留
-    source_dir: String,
+    source_dir: PathBuf,
     inodes: HashMap<u64, Inode>,
-    direct_io: bool,
+    cache: CacheOptions,
+    xattr: bool, // 是否启用扩展属性
     remote_file_manager: RemoteFileManager,
+    handles: HashMap<u64, OpenFile>, // fh -> 打开的文件
+    unlinked: HashSet<u64>,          // 已经删除但仍然打开的文件
+    next_fh: u64,
+    invalidations: Option<Sender<Invalidation>>, // 推送给内核的缓存失效
+    workers: Option<WorkerPool>,                 // 为 None 时在请求线程中直接读写
+    async_backend: Option<AsyncBackend>,         // 为 None 时所有后端调用都是同步的
+    write_done: Sender<WriteDone>,               // 工作线程中完成的写入
+    completed_writes: Receiver<WriteDone>,
+    append_ends: HashMap<u64, u64>, // ino -> 还没有完成的追加写的结束位置
+    size_gen: u64,                  // 每次截断文件时增加, 写入任务记录创建时的值
+    truncated: HashMap<u64, u64>,   // ino -> 最近一次截断时的 size_gen
+    writes_in_flight: usize,        // 已经创建但还没有完成的写入
 }
 
 impl RFuseFS {
     pub fn new(
         fs_name: String,
-        direct_io: bool,
-        source_dir: String,
+        cache: CacheOptions,
+        xattr: bool,
+        source_dir: PathBuf,
         init_fs_func: Box<InitFsFuncType>,
         tmp_file_trait: Box<dyn TmpFileTrait + 'static + Send>,
     ) -> Self {
+        let (write_done, completed_writes) = mpsc::channel();
         Self {
             fs_name,
             source_dir,
             inodes: HashMap::new(),
-            direct_io,
+            cache,
+            xattr,
             remote_file_manager: RemoteFileManager::new(init_fs_func, tmp_file_trait),
+            handles: HashMap::new(),
+            unlinked: HashSet::new(),
+            next_fh: 1,
+            invalidations: None,
+            workers: None,
+            async_backend: None,
+            write_done,
+            completed_writes,
+            append_ends: HashMap::new(),
+            size_gen: 0,
+            truncated: HashMap::new(),
+            writes_in_flight: 0,
+        }
+    }
+
+    /// 启动 threads 个工作线程处理 read 和 write, 不同文件的读写可以并行进行
+    ///
+    /// threads 为 0 时所有请求都在 fuser 的请求线程中依次处理
+    pub fn spawn_workers(&mut self, threads: usize) {
+        self.workers = match threads {
+            0 => None,
+            threads => Some(WorkerPool::new(threads)),
+        };
+    }
+
+    /// 读写 (包括通过后端句柄的读写), readlink 和 statfs 交给异步后端, 在 runtime 的任务中等待结果后回复
+    pub fn set_async_backend(&mut self, backend: Arc<dyn AsyncTmpFileTrait>, runtime: Handle) {
+        self.async_backend = Some(AsyncBackend { backend, runtime });
+    }
+
+    /// 使用 SyncAdapter 包装当前的同步后端, 阻塞的调用在 runtime 的阻塞线程池中执行
+    pub fn adapt_sync_backend(&mut self, runtime: Handle) {
+        let backend = SyncAdapter(self.remote_file_manager.tmp_file_trait.clone());
+        self.set_async_backend(Arc::new(backend), runtime);
+    }
+
+    pub fn lookup_name(&self, parent: u64, name: &OsStr) -> Option<u64> {
+        self.inodes.get(&parent)?.entries.lookup(name)
+    }
+
+    /// 文件夹的链接数为 2 + 子文件夹数量
+    pub fn file_attr(&self, inode: &Inode) -> FileAttr {
+        let mut attr = inode.file_attr();
+        if inode.is_dir() {
+            attr.nlink += inode
+                .entries
+                .children()
+                .filter(|ino| self.get_inode(*ino).is_some_and(|child| child.is_dir()))
+                .count() as u32;
+        }
+        attr
+    }
+
+    /// 检查 inode 表是否是一棵一致的树, 返回发现的第一个问题
+    ///
+    /// 每个 inode 只在一个文件夹中有主名字, 主名字与 inode 的名字一致, parent_ino 和路径与所在文件夹一致,
+    /// 硬链接指向存在的文件, 并且文件的链接数等于名字的数量
+    pub fn verify_tree(&self) -> Result<(), String> {
+        let root = match self.get_inode(FUSE_ROOT_ID) {
+            Some(root) if root.is_dir() => root,
+            _ => return Err("root is missing or not a directory".to_string()),
+        };
+        if root.parent_ino != FUSE_ROOT_ID || !root.attr.name.is_empty() {
+            return Err("root must have an empty name and itself as parent".to_string());
+        }
+
+        // 从根目录出发, 每个 inode 只能被访问一次
+        let mut visited = HashSet::from([FUSE_ROOT_ID]);
+        let mut names: HashMap<u64, u32> = HashMap::new();
+        let mut dirs = vec![FUSE_ROOT_ID];
+        while let Some(dir_ino) = dirs.pop() {
+            let dir = self.get_inode(dir_ino).unwrap();
+            if !dir.loaded && !dir.entries.is_empty() {
+                return Err(format!("unloaded directory {} has entries", dir_ino));
+            }
+            let path = children_path(dir);
+            for entry in dir.entries.iter() {
+                let ino = &entry.ino;
+                if !entry.primary {
+                    match self.get_inode(*ino) {
+                        Some(target) if !target.is_dir() => {}
+                        _ => {
+                            return Err(format!(
+                                "hard link {:?} in {} is broken",
+                                entry.name, dir_ino
+                            ))
+                        }
+                    }
+                    *names.entry(*ino).or_default() += 1;
+                    continue;
+                }
+                let child = match self.get_inode(*ino) {
+                    Some(child) => child,
+                    None => return Err(format!("child {} of {} is missing", ino, dir_ino)),
+                };
+                if !visited.insert(*ino) {
+                    return Err(format!("inode {} is listed more than once", ino));
+                }
+                if child.parent_ino != dir_ino {
+                    return Err(format!(
+                        "inode {} has parent {}, but is listed in {}",
+                        ino, child.parent_ino, dir_ino
+                    ));
+                }
+                if child.attr.path != path {
+                    return Err(format!(
+                        "inode {} has path {:?}, expected {:?}",
+                        ino, child.attr.path, path
+                    ));
+                }
+                if child.attr.name != entry.name {
+                    return Err(format!(
+                        "inode {} is named {:?}, but listed as {:?} in {}",
+                        ino, child.attr.name, entry.name, dir_ino
+                    ));
+                }
+                match self.remote_file_manager.tmp_file_map.get(ino) {
+                    Some(tf)
+                        if tf.file_name == child.attr.name
+                            && tf.path == origin_dir(&self.source_dir, &path) => {}
+                    _ => return Err(format!("inode {} does not match its origin file", ino)),
+                }
+                *names.entry(*ino).or_default() += 1;
+                if child.is_dir() {
+                    dirs.push(*ino);
+                }
+            }
+        }
+
+        // 已经删除但仍然打开的文件不在任何文件夹中
+        if visited.len() + self.unlinked.len() != self.inodes.len() {
+            return Err(format!(
+                "{} inodes are not reachable from the root",
+                self.inodes.len() - visited.len() - self.unlinked.len()
+            ));
+        }
+        for (ino, count) in names {
+            let inode = self.get_inode(ino).unwrap();
+            if !inode.is_dir() && inode.nlink != count {
+                return Err(format!(
+                    "inode {} has nlink {}, but {} names",
+                    ino, inode.nlink, count
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // 主名字被删除后, 将一个硬链接提升为 inode 的主名字
+    fn promote_hard_link(&mut self, ino: u64) {
+        let found = self.inodes.values().find_map(|dir| {
+            dir.entries
+                .links()
+                .find(|(_, link_ino)| *link_ino == ino)
+                .map(|(name, _)| (dir.ino, name.to_os_string()))
+        });
+        let (dir_ino, name) = match found {
+            Some(f) => f,
+            None => {
+                self.drop_inode(ino);
+                return;
+            }
+        };
+
+        let dir = self.inodes.get_mut(&dir_ino).unwrap();
+        dir.entries.promote(&name);
+        let path = children_path(dir);
+        self.remote_file_manager
+            .add_file(ino, name.clone(), origin_dir(&self.source_dir, &path));
+
+        let inode = self.inodes.get_mut(&ino).unwrap();
+        inode.nlink -= 1;
+        inode.parent_ino = dir_ino;
+        inode.attr.name = name;
+        inode.attr.path = path;
+    }
+
+    // 文件的最后一个名字已经删除, 仍然打开时保留 inode, 直到最后一次 release
+    fn drop_inode(&mut self, ino: u64) {
+        if self.is_open(ino) {
+            if let Some(inode) = self.inodes.get_mut(&ino) {
+                inode.nlink = 0;
+            }
+            self.unlinked.insert(ino);
+        } else {
+            self.inodes.remove(&ino);
         }
     }
 
-    pub fn lookup_name(&self, parent: u64, name: &str) -> Option<u64> {
-        let parent_inode = self.inodes.get(&parent).unwrap();
-        for ino in parent_inode.children_ino.iter() {
-            let inode = self.inodes.get(ino).unwrap();
-            if inode.attr.name.eq(name) {
-                return Some(*ino);
+    fn is_open(&self, ino: u64) -> bool {
+        self.handles.values().any(|open_file| open_file.ino == ino)
+    }
+
+    // 分配新的文件句柄
+    fn insert_handle(&mut self, ino: u64, flags: i32, handle: Option<Box<dyn FileHandle>>) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        let handle = handle.map(Arc::from);
+        self.handles.insert(fh, OpenFile { ino, flags, handle });
+        fh
+    }
+
+    // 打开文件并分配句柄, 写打开时处理 O_TRUNC
+    fn open_file(&mut self, ino: u64, flags: i32) -> Result<u64, libc::c_int> {
+        // 使用 writeback cache 时内核自己计算追加写的偏移, 并且可能读取只写打开的文件来补齐页
+        let flags = if self.cache.writeback_cache {
+            match flags & libc::O_ACCMODE {
+                libc::O_WRONLY => (flags & !libc::O_ACCMODE & !libc::O_APPEND) | libc::O_RDWR,
+                _ => flags & !libc::O_APPEND,
+            }
+        } else {
+            flags
+        };
+        if flags & libc::O_TRUNC != 0 && flags & libc::O_ACCMODE != libc::O_RDONLY {
+            if !self.inodes.contains_key(&ino) {
+                return Err(ENOENT);
+            }
+            self.mark_truncated(ino);
+            let inode = self.inodes.get_mut(&ino).unwrap();
+            if inode.attr.size != 0 {
+                let now = SystemTime::now();
+                inode.attr.size = 0;
+                inode.attr.mtime = now;
+                inode.attr.ctime = now;
+                let attr = inode.attr.clone();
+                if let Err(e) = self.remote_file_manager.set_attr(ino, &attr) {
+                    debug!("[RFuseFS][open_file] -> Truncate the file. {}", e);
+                    return Err(e.errno());
+                }
+            }
+        }
+
+        let handle = match self.remote_file_manager.open(ino, flags) {
+            Ok(handle) => handle,
+            Err(e) => {
+                debug!("[RFuseFS][open_file] -> Open a file. {}", e);
+                return Err(e.errno());
             }
+        };
+        Ok(self.insert_handle(ino, flags, handle))
+    }
+
+    // 获取 fh 对应的后端句柄, fh 必须属于 ino
+    fn file_handle(&self, ino: u64, fh: u64) -> Option<&Arc<dyn FileHandle>> {
+        match self.handles.get(&fh) {
+            Some(open_file) if open_file.ino == ino => open_file.handle.as_ref(),
+            _ => None,
+        }
+    }
+
+    // 读写 fh 的方式, 没有后端句柄时按路径读写
+    fn file_io(&self, ino: u64, fh: u64) -> Result<FileIo, libc::c_int> {
+        match self.file_handle(ino, fh) {
+            Some(handle) => Ok(FileIo::Handle(handle.clone())),
+            None => self.remote_file_manager.file_io(ino).map_err(|e| {
+                debug!("[RFuseFS][file_io] -> Open a file. {}", e);
+                e.errno()
+            }),
         }
-        None
     }
 
     /// 清空inode, 重建
@@ -75,176 +537,1775 @@ impl RFuseFS {
         self.inodes.insert(inode.ino, inode.clone());
     }
 
+    /// 应用源目录的改动时, 需要推送给内核的缓存失效发送到 invalidations
+    ///
+    /// 源目录的改动由 [`crate::shared_fs::consume_origin_changes`] 在单独的线程中应用
+    pub fn watch_origin(&mut self, invalidations: Sender<Invalidation>) {
+        self.invalidations = Some(invalidations);
+    }
+
+    fn invalidate(&self, invalidation: Invalidation) {
+        if let Some(invalidations) = &self.invalidations {
+            if let Err(e) = invalidations.send(invalidation) {
+                debug!("[RFuseFS][invalidate] send failed: {}", e);
+            }
+        }
+    }
+
+    /// 应用一批源目录的改动
+    pub fn apply_origin_changes(&mut self, changes: Vec<OriginChange>) {
+        self.apply_completed_writes();
+        // 重新加载时会读取源目录的最新状态, 其他改动不需要再处理
+        if changes.contains(&OriginChange::Reload) {
+            self.re_init_fs();
+            return;
+        }
+        for change in changes {
+            self.apply_origin_change(change);
+        }
+    }
+
+    // 把工作线程中完成的写入更新到 inode 表, 在读取文件大小之前调用
+    fn apply_completed_writes(&mut self) {
+        for done in self.completed_writes.try_iter() {
+            self.writes_in_flight = self.writes_in_flight.saturating_sub(1);
+            // 追加写失败时之后的追加写重新从文件末尾开始, 不留下空洞
+            if !done.ok {
+                self.append_ends.remove(&done.ino);
+                continue;
+            }
+            // 写入任务创建之后文件被截断过, 不能用截断之前的结束位置把文件大小改回去
+            let stale = self
+                .truncated
+                .get(&done.ino)
+                .is_some_and(|gen| done.size_gen < *gen);
+            // 写入期间 inode 可能已经被源目录的改动删除
+            if let Some(inode) = self.inodes.get_mut(&done.ino) {
+                inode.attr.mtime = done.write_time;
+                inode.attr.ctime = done.write_time;
+                if !stale && done.end > inode.attr.size {
+                    inode.attr.size = done.end;
+                }
+            }
+        }
+        // 截断之前创建的写入都已经完成
+        if self.writes_in_flight == 0 {
+            self.truncated.clear();
+        }
+        // 预留的位置都已经写入之后不再需要记录
+        let inodes = &self.inodes;
+        self.append_ends
+            .retain(|ino, end| inodes.get(ino).is_some_and(|inode| inode.attr.size < *end));
+    }
+
+    /// 重新读取源目录, 并将新旧 inode 表的差异推送给内核, 不需要重新挂载
     pub fn re_init_fs(&mut self) {
-        self.clean_inode();
+        let old_inodes = std::mem::take(&mut self.inodes);
+        let old_files = std::mem::take(&mut self.remote_file_manager.tmp_file_map);
         match self
             .remote_file_manager
             .initialize_fs(&mut self.inodes, self.source_dir.clone())
         {
             Ok(_) => {}
             Err(e) => {
-                debug!("[RFuseFS][init] -> Initialize filesystem. {}", e);
+                error!("[RFuseFS][re_init_fs] -> Initialize filesystem. {}", e);
+                self.inodes = old_inodes;
+                self.remote_file_manager.tmp_file_map = old_files;
+                return;
             }
         };
+        // 重新读取之前已经读取过的文件夹
+        let mut dirs = vec![FUSE_ROOT_ID];
+        while let Some(dir) = dirs.pop() {
+            // 读取失败的文件夹保持未读取, 下次访问时重试
+            if self.ensure_loaded(dir).is_err() {
+                continue;
+            }
+            if let Some(inode) = self.get_inode(dir) {
+                dirs.extend(inode.entries.children().filter(|ino| {
+                    old_inodes
+                        .get(ino)
+                        .is_some_and(|old| old.is_dir() && old.loaded)
+                }));
+            }
+        }
+        self.remote_file_manager.keep_locks(&old_files);
+        // 已经删除但仍然打开的文件不在源目录中, 沿用旧的 inode
+        for ino in std::mem::take(&mut self.unlinked) {
+            if self.inodes.contains_key(&ino) || !self.is_open(ino) {
+                continue;
+            }
+            if let Some(inode) = old_inodes.get(&ino) {
+                self.inodes.insert(ino, inode.clone());
+                self.unlinked.insert(ino);
+            }
+        }
+
+        let old_entries = dir_entries(&old_inodes);
+        let new_entries = dir_entries(&self.inodes);
+        for (parent, name, ino) in old_entries.difference(&new_entries) {
+            self.invalidate(Invalidation::Delete {
+                parent: *parent,
+                child: *ino,
+                name: name.clone(),
+            });
+        }
+        for (parent, name, _) in new_entries.difference(&old_entries) {
+            self.invalidate(Invalidation::Entry {
+                parent: *parent,
+                name: name.clone(),
+            });
+        }
+        // 属性发生变化的文件, 内核中的属性和数据缓存都需要失效
+        for (ino, inode) in self.inodes.iter() {
+            if let Some(old) = old_inodes.get(ino) {
+                if old.attr.size != inode.attr.size
+                    || old.attr.mtime != inode.attr.mtime
+                    || old.attr.ctime != inode.attr.ctime
+                {
+                    self.invalidate(Invalidation::Inode { ino: *ino });
+                }
+            }
+        }
     }
-}
 
-impl Filesystem for RFuseFS {
-    fn init(
-        &mut self,
-        _req: &Request,
-        _config: &mut fuser::KernelConfig,
-    ) -> Result<(), libc::c_int> {
-        info!("[RFuseFS][init] -> Initialize filesystem.");
+    /// 按源目录中的相对路径查找 inode, 例如 `/dir/file`, 不会读取还没有读取过的文件夹
+    pub fn resolve_path(&self, path: impl AsRef<OsStr>) -> Option<u64> {
+        let mut ino = FUSE_ROOT_ID;
+        for name in path.as_ref().as_bytes().split(|b| *b == b'/') {
+            if name.is_empty() {
+                continue;
+            }
+            if !self.get_inode(ino)?.is_dir() {
+                return None;
+            }
+            ino = self.lookup_name(ino, OsStr::from_bytes(name))?;
+        }
+        Some(ino)
+    }
 
-        match self
+    /// 将源目录中单个路径的改动应用到 inode 表, 只读取改动涉及的文件, 不需要重新遍历源目录
+    pub fn apply_origin_change(&mut self, change: OriginChange) {
+        debug!("[RFuseFS][apply_origin_change] {:?}", change);
+        match change {
+            OriginChange::Create(path) => self.origin_create(&path),
+            OriginChange::Modify(path) => self.origin_modify(&path),
+            OriginChange::Remove(path) => self.origin_remove(&path),
+            OriginChange::Rename { from, to } => self.origin_rename(&from, &to),
+            OriginChange::Reload => self.re_init_fs(),
+        }
+    }
+
+    // 源目录中新建了文件, 父文件夹还没有读取时忽略, 读取父文件夹时会一起读到
+    fn origin_create(&mut self, path: &OsStr) {
+        let (dir, name) = split_origin_path(path);
+        if name.is_empty() {
+            return;
+        }
+        let parent = match self.resolve_path(dir) {
+            Some(ino) if self.get_inode(ino).is_some_and(|p| p.is_dir() && p.loaded) => ino,
+            _ => {
+                debug!("[RFuseFS][origin_create] parent not found: {:?}", path);
+                return;
+            }
+        };
+        // 已经存在的名字 (例如挂载点自己创建的文件), 只刷新属性
+        if self.lookup_name(parent, name).is_some() {
+            self.origin_modify(path);
+            return;
+        }
+
+        let meta = match self
             .remote_file_manager
-            .initialize_fs(&mut self.inodes, self.source_dir.clone())
+            .get_attr(name.to_os_string(), origin_dir(&self.source_dir, dir))
         {
-            Ok(_) => {}
+            Ok(meta) => meta,
             Err(e) => {
-                debug!("[RFuseFS][init] -> Initialize filesystem. {}", e);
-                return Err(libc::EIO);
+                debug!("[RFuseFS][origin_create] {:?} {}", path, e);
+                return;
             }
         };
-        // debug!("Inodes: {:?}", self.inodes);
-        Ok(())
+        self.attach_origin_inode(parent, meta);
+        self.invalidate(Invalidation::Entry {
+            parent,
+            name: OsString::from(name),
+        });
+        self.refresh_from_origin(parent);
     }
 
-    fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        // info!("[RFuseFS][lookup] -> Look up a directory entry by name.");
-        if name.len() > MAX_NAME_LENGTH as usize {
-            reply.error(libc::ENAMETOOLONG);
-            return;
+    // 源文件的内容或者属性发生变化
+    fn origin_modify(&mut self, path: &OsStr) {
+        let ino = match self.resolve_path(path) {
+            Some(ino) => ino,
+            None => {
+                self.origin_create(path);
+                return;
+            }
+        };
+        // 文件内容可能已经改变, 大小和修改时间不变时也不能使用缓存的块
+        self.remote_file_manager.invalidate_cache(ino);
+        let (dir, name) = split_origin_path(path);
+        match self
+            .remote_file_manager
+            .get_attr(name.to_os_string(), origin_dir(&self.source_dir, dir))
+        {
+            // 根目录在 inode 表中使用 FUSE_ROOT_ID, 不和源目录的 ino 比较
+            Ok(meta) if ino == FUSE_ROOT_ID || meta.ino == ino => self.refresh_attr(ino, &meta),
+            // 同名的文件被替换成了另一个文件
+            Ok(_) => {
+                self.detach_path(path);
+                self.origin_create(path);
+            }
+            Err(_) => self.detach_path(path),
         }
+    }
 
-        let parent_inode = self.get_inode(parent).unwrap();
-        if !check_access(
-            parent_inode.attr.uid,
-            parent_inode.attr.gid,
-            parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::X_OK,
-        ) {
-            reply.error(libc::EACCES);
+    // 源文件被删除
+    fn origin_remove(&mut self, path: &OsStr) {
+        let (dir, name) = split_origin_path(path);
+        // 删除后又重新创建的文件, 事件到达时源文件已经存在
+        if self
+            .remote_file_manager
+            .get_attr(name.to_os_string(), origin_dir(&self.source_dir, dir))
+            .is_ok()
+        {
+            self.origin_modify(path);
             return;
         }
+        self.detach_path(path);
+    }
 
-        let name = name.to_str().unwrap().to_owned();
-        // debug!(
-        //     "[RFuseFS][lookup] -> Look up a directory entry and get its attributes. {}",
-        //     name.clone()
-        // );
-        match self.lookup_name(parent, &name) {
-            Some(ino) => {
-                let inode = self.get_inode(ino).unwrap();
-                reply.entry(&Duration::new(0, 0), &inode.file_attr(), 0)
+    // 源目录内部的改名, 保留原来的 inode, 文件夹的子孙只更新路径
+    fn origin_rename(&mut self, from: &OsStr, to: &OsStr) {
+        let (from_dir, from_name) = split_origin_path(from);
+        let (to_dir, to_name) = split_origin_path(to);
+        let (old_parent, ino) = match self
+            .resolve_path(from_dir)
+            .and_then(|parent| Some((parent, self.lookup_name(parent, from_name)?)))
+        {
+            Some(found) => found,
+            // 旧名字不在 inode 表中 (例如挂载点自己的改名), 按新建处理
+            None => {
+                self.origin_create(to);
+                return;
             }
-            None => reply.error(ENOENT),
+        };
+        let new_parent = match self.resolve_path(to_dir) {
+            Some(parent)
+                if self
+                    .get_inode(parent)
+                    .is_some_and(|p| p.is_dir() && p.loaded) =>
+            {
+                parent
+            }
+            _ => {
+                self.origin_remove(from);
+                return;
+            }
+        };
+        let meta = match self
+            .remote_file_manager
+            .get_attr(to_name.to_os_string(), origin_dir(&self.source_dir, to_dir))
+        {
+            Ok(meta) if meta.ino == ino => meta,
+            // 新名字已经不存在或者不是同一个文件, 说明之后还有其他改动
+            _ => {
+                self.origin_remove(from);
+                self.origin_create(to);
+                return;
+            }
+        };
+
+        // 新名字上原有的文件被覆盖
+        match self.lookup_name(new_parent, to_name) {
+            Some(existing) if existing == ino => {
+                self.origin_remove(from);
+                return;
+            }
+            Some(existing) => self.detach_entry(new_parent, to_name, existing),
+            None => {}
+        }
+
+        let primary = self.take_entry(old_parent, from_name, ino);
+        self.put_entry(new_parent, to_name, ino, primary);
+        if primary && self.get_inode(ino).is_some_and(|inode| inode.is_dir()) {
+            self.update_descendant_paths(ino);
+        }
+        self.refresh_attr(ino, &meta);
+
+        self.invalidate(Invalidation::Entry {
+            parent: old_parent,
+            name: OsString::from(from_name),
+        });
+        self.invalidate(Invalidation::Entry {
+            parent: new_parent,
+            name: OsString::from(to_name),
+        });
+        self.refresh_from_origin(old_parent);
+        if new_parent != old_parent {
+            self.refresh_from_origin(new_parent);
         }
     }
 
-    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
-        // info!("[RFuseFS][getattr] -> Get attributes of a file.");
-        match self.get_inode(ino) {
-            Some(inode) => {
-                // debug!(
-                //     "[RFuseFS][getattr] -> Get file attributes. {}",
-                //     inode.attr.name.clone()
-                // );
-                reply.attr(&Duration::new(0, 0), &inode.file_attr())
+    // name 是 inode 的主名字, 而不是文件夹中的硬链接
+    fn is_primary_entry(&self, parent: u64, name: &OsStr, ino: u64) -> bool {
+        self.get_inode(parent)
+            .and_then(|dir| dir.entries.get(name))
+            .is_some_and(|entry| entry.primary && entry.ino == ino)
+    }
+
+    // 从 parent 中取下名为 name 的目录项, 返回它是否是主名字, inode 本身保留在表中
+    fn take_entry(&mut self, parent: u64, name: &OsStr, ino: u64) -> bool {
+        let primary = self.is_primary_entry(parent, name, ino);
+        self.inodes.get_mut(&parent).unwrap().entries.remove(name);
+        primary
+    }
+
+    // 把 take_entry 取下的目录项以 name 挂到 parent 下, 主名字会同时更新 inode 记录的路径
+    fn put_entry(&mut self, parent: u64, name: &OsStr, ino: u64, primary: bool) {
+        let dir = self.inodes.get_mut(&parent).unwrap();
+        if !primary {
+            dir.entries.insert_link(name, ino);
+            return;
+        }
+        dir.entries.insert_child(name, ino);
+        let path = children_path(dir);
+        let inode = self.inodes.get_mut(&ino).unwrap();
+        inode.parent_ino = parent;
+        inode.attr.name = name.to_os_string();
+        inode.attr.path = path.clone();
+        self.remote_file_manager.add_file(
+            ino,
+            name.to_os_string(),
+            origin_dir(&self.source_dir, &path),
+        );
+    }
+
+    // dir 是 ino 自己或者它的祖先
+    fn is_ancestor(&self, dir: u64, ino: u64) -> bool {
+        let mut current = ino;
+        loop {
+            if current == dir {
+                return true;
+            }
+            match self.get_inode(current) {
+                Some(inode) if current != FUSE_ROOT_ID && inode.parent_ino != current => {
+                    current = inode.parent_ino;
+                }
+                _ => return false,
             }
-            None => reply.error(ENOENT),
         }
     }
 
-    fn setattr(
-        &mut self,
-        req: &Request,
-        ino: u64,
-        mode: Option<u32>,
-        uid: Option<u32>,
-        gid: Option<u32>,
-        size: Option<u64>,
-        atime: Option<TimeOrNow>,
-        mtime: Option<TimeOrNow>,
-        _ctime: Option<SystemTime>,
-        _fh: Option<u64>,
-        _crtime: Option<SystemTime>,
-        _chgtime: Option<SystemTime>,
-        _bkuptime: Option<SystemTime>,
-        _flags: Option<u32>,
-        reply: ReplyAttr,
-    ) {
-        info!("[RFuseFS][setattr] -> Set attributes of a file.");
+    // 将源目录中读到的文件挂到 parent 下, 文件夹的子文件在第一次访问时读取
+    fn attach_origin_inode(&mut self, parent: u64, mut inode: Inode) {
+        let ino = inode.ino;
+        let name = inode.attr.name.clone();
+        if let Some(existing) = self.inodes.get_mut(&ino) {
+            // ino 已经存在, 说明是同一个文件的另一个名字.
+            // 后端不返回挂载在源目录中的其他文件系统, 所以 ino 相同就是同一个文件
+            if existing.is_dir() {
+                error!(
+                    "[RFuseFS][attach_origin_inode] directory {:?} already exists, ino: {}",
+                    name, ino
+                );
+                return;
+            }
+            existing.nlink += 1;
+            self.inodes
+                .get_mut(&parent)
+                .unwrap()
+                .entries
+                .insert_link(name, ino);
+            self.invalidate(Invalidation::Inode { ino });
+            return;
+        }
 
-        let inode = self.inodes.get_mut(&ino).unwrap();
+        let path = children_path(self.get_inode(parent).unwrap());
+        inode.parent_ino = parent;
+        inode.entries = DirEntries::default();
+        inode.loaded = !inode.is_dir();
+        inode.attr.path = path.clone();
+        self.remote_file_manager
+            .add_file(ino, name.clone(), origin_dir(&self.source_dir, &path));
+        self.inodes
+            .get_mut(&parent)
+            .unwrap()
+            .entries
+            .insert_child(name, ino);
+        self.inodes.insert(ino, inode);
+    }
+
+    /// 文件夹第一次被访问时从后端读取子文件, 已经读取过的文件夹不会重复读取
+    ///
+    /// 读取失败时文件夹保持未读取的状态, 返回后端的错误, 下次访问时重新读取
+    pub fn ensure_loaded(&mut self, dir: u64) -> Result<(), TmpFileError> {
+        if self
+            .get_inode(dir)
+            .is_some_and(|inode| inode.is_dir() && !inode.loaded)
+        {
+            self.load_origin_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    // 读取源目录中文件夹下的所有文件
+    fn load_origin_dir(&mut self, dir: u64) -> Result<(), TmpFileError> {
+        let (name, path) = match self.get_inode(dir) {
+            Some(inode) => (
+                inode.attr.name.clone(),
+                origin_dir(&self.source_dir, &inode.attr.path),
+            ),
+            None => return Ok(()),
+        };
+        let children = match self.remote_file_manager.read_dir(name, path) {
+            Ok(children) => children,
+            Err(e) => {
+                error!("[RFuseFS][load_origin_dir] -> Read directory. {}", e);
+                return Err(e);
+            }
+        };
+        debug!(
+            "[RFuseFS][load_origin_dir] -> Load {} entries, ino: {}",
+            children.len(),
+            dir
+        );
+        if let Some(inode) = self.inodes.get_mut(&dir) {
+            inode.loaded = true;
+        }
+        for child in children {
+            self.attach_origin_inode(dir, child);
+        }
+        Ok(())
+    }
+
+    // 将路径对应的目录项从 inode 表中摘除
+    fn detach_path(&mut self, path: &OsStr) {
+        let (dir, name) = split_origin_path(path);
+        if name.is_empty() {
+            return;
+        }
+        let parent = match self.resolve_path(dir) {
+            Some(parent) => parent,
+            None => return,
+        };
+        if let Some(ino) = self.lookup_name(parent, name) {
+            self.detach_entry(parent, name, ino);
+            self.refresh_from_origin(parent);
+        }
+    }
+
+    // 摘除 parent 下名为 name 的目录项, 文件夹会连同所有子孙一起摘除
+    fn detach_entry(&mut self, parent: u64, name: &OsStr, ino: u64) {
+        if self.take_entry(parent, name, ino) {
+            if self.get_inode(ino).is_some_and(|inode| inode.is_dir()) {
+                self.remove_subtree(ino);
+            } else {
+                self.remote_file_manager.forget_file(ino);
+                self.promote_hard_link(ino);
+            }
+        } else if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.nlink = inode.nlink.saturating_sub(1);
+        }
+        self.invalidate(Invalidation::Delete {
+            parent,
+            child: ino,
+            name: OsString::from(name),
+        });
+    }
+
+    // 删除文件夹和它的所有子孙, 子孙中的文件在文件夹外还有硬链接时保留
+    fn remove_subtree(&mut self, dir: u64) {
+        let mut dirs = vec![dir];
+        let mut files = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            self.remote_file_manager.forget_file(dir);
+            let inode = match self.inodes.remove(&dir) {
+                Some(inode) => inode,
+                None => continue,
+            };
+            // 文件夹中的硬链接随文件夹一起消失
+            for (_, link_ino) in inode.entries.links() {
+                if let Some(target) = self.inodes.get_mut(&link_ino) {
+                    target.nlink = target.nlink.saturating_sub(1);
+                }
+            }
+            for child in inode.entries.children() {
+                if self.get_inode(child).is_some_and(|child| child.is_dir()) {
+                    dirs.push(child);
+                } else {
+                    files.push(child);
+                }
+            }
+        }
+        for file in files {
+            self.remote_file_manager.forget_file(file);
+            self.promote_hard_link(file);
+        }
+    }
+
+    // 文件夹改名或者移动后, 更新所有子孙的路径
+    fn update_descendant_paths(&mut self, dir: u64) {
+        let mut dirs = vec![dir];
+        while let Some(dir) = dirs.pop() {
+            let (path, children) = match self.get_inode(dir) {
+                Some(inode) => (
+                    children_path(inode),
+                    inode.entries.children().collect::<Vec<_>>(),
+                ),
+                None => continue,
+            };
+            for child in children {
+                if let Some(inode) = self.inodes.get_mut(&child) {
+                    inode.parent_ino = dir;
+                    inode.attr.path = path.clone();
+                    self.remote_file_manager.add_file(
+                        child,
+                        inode.attr.name.clone(),
+                        origin_dir(&self.source_dir, &path),
+                    );
+                    if inode.is_dir() {
+                        dirs.push(child);
+                    }
+                }
+            }
+        }
+    }
+
+    // 使用源文件的属性更新 inode, 名字和路径保持不变
+    fn refresh_attr(&mut self, ino: u64, meta: &Inode) {
+        let inode = match self.inodes.get_mut(&ino) {
+            Some(inode) => inode,
+            None => return,
+        };
+        let attr = &mut inode.attr;
+        let changed = attr.size != meta.attr.size
+            || attr.mtime != meta.attr.mtime
+            || attr.ctime != meta.attr.ctime
+            || attr.permissions != meta.attr.permissions
+            || attr.uid != meta.attr.uid
+            || attr.gid != meta.attr.gid;
+        attr.size = meta.attr.size;
+        attr.atime = meta.attr.atime;
+        attr.mtime = meta.attr.mtime;
+        attr.ctime = meta.attr.ctime;
+        attr.permissions = meta.attr.permissions;
+        attr.uid = meta.attr.uid;
+        attr.gid = meta.attr.gid;
+        if changed {
+            self.remote_file_manager.invalidate_cache(ino);
+            self.invalidate(Invalidation::Inode { ino });
+        }
+    }
+
+    // 按 inode 记录的路径重新读取源文件的属性
+    fn refresh_from_origin(&mut self, ino: u64) {
+        let meta = match self.get_inode(ino) {
+            Some(inode) => self.remote_file_manager.get_attr(
+                inode.attr.name.clone(),
+                origin_dir(&self.source_dir, &inode.attr.path),
+            ),
+            None => return,
+        };
+        if let Ok(meta) = meta {
+            self.refresh_attr(ino, &meta);
+        }
+    }
+}
+
+// FUSE 请求的具体实现, 不依赖 fuser 的 Request 和 Reply, 出错时返回 errno
+impl RFuseFS {
+    /// 查找文件夹下的文件, 对应 lookup
+    pub fn lookup_entry(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+    ) -> Result<FileAttr, libc::c_int> {
+        self.apply_completed_writes();
+        if name.len() > MAX_NAME_LENGTH as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+
+        let parent_inode = self.get_inode(parent).ok_or(ENOENT)?;
+        if !parent_inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::X_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+
+        // debug!(
+        //     "[RFuseFS][lookup] -> Look up a directory entry and get its attributes. {:?}",
+        //     name
+        // );
+        let ino = self.lookup_name(parent, name).ok_or(ENOENT)?;
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        Ok(self.file_attr(inode))
+    }
+
+    /// 修改文件属性, 对应 setattr
+    pub fn set_attr(
+        &mut self,
+        caller: Caller,
+        ino: u64,
+        changes: SetAttr,
+    ) -> Result<FileAttr, libc::c_int> {
+        self.apply_completed_writes();
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
 
         // 确认权限
-        if inode.attr.uid != req.uid()
+        if inode.attr.uid != caller.uid
             && !check_access(
                 inode.attr.uid,
                 inode.attr.gid,
                 inode.attr.permissions,
-                req.uid(),
-                req.gid(),
+                caller.uid,
+                caller.gid,
                 libc::W_OK,
             )
         {
-            reply.error(libc::EACCES);
-            return;
+            return Err(libc::EACCES);
         }
 
-        if let Some(mode) = mode {
-            inode.attr.permissions = mode as u16;
+        // 后端修改成功之后才更新 inode 表, 例如超出容量时保持原来的大小
+        let mut attr = inode.attr.clone();
+        if let Some(mode) = changes.mode {
+            attr.permissions = mode as u16;
         }
-        if let Some(size) = size {
+        if let Some(size) = changes.size {
             // NOTE: 当文件大小为空时，这里会直接设置文件大小为0，不会调用write方法
-            inode.attr.size = size;
+            attr.size = size;
+        }
+        if let Some(mtime) = changes.mtime {
+            attr.mtime = match mtime {
+                TimeOrNow::SpecificTime(time) => time,
+                fuser::TimeOrNow::Now => SystemTime::now(),
+            };
         }
-        if let Some(mtime) = mtime {
-            inode.attr.mtime = match mtime {
+
+        if let Some(atime) = changes.atime {
+            attr.atime = match atime {
                 TimeOrNow::SpecificTime(time) => time,
                 fuser::TimeOrNow::Now => SystemTime::now(),
             };
         }
 
-        if let Some(atime) = atime {
-            inode.attr.atime = match atime {
-                TimeOrNow::SpecificTime(time) => time,
-                fuser::TimeOrNow::Now => SystemTime::now(),
-            };
+        // if let Some(ctime) = ctime {
+        //     inode.attr.ctime = ctime;
+        // }
+
+        if let Some(uid) = changes.uid {
+            attr.uid = uid;
+        }
+
+        if let Some(gid) = changes.gid {
+            attr.gid = gid;
+        }
+
+        // debug!(
+        //     "[RFuseFS][setattr] -> Set file attributes. {:?}",
+        //     inode.attr.name
+        // );
+        match self.remote_file_manager.set_attr(ino, &attr) {
+            Ok(_) => {}
+            Err(e) => {
+                debug!("[RFuseFS][setattr] -> Set file attributes. {}", e);
+                return Err(e.errno());
+            }
+        };
+        if changes.size.is_some() {
+            self.mark_truncated(ino);
+        }
+        let inode = self.inodes.get_mut(&ino).ok_or(ENOENT)?;
+        inode.attr = attr;
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        Ok(self.file_attr(inode))
+    }
+
+    /// 从打开的文件中读取数据, 对应 read, 读到文件末尾时返回的数据会变短
+    pub fn read_data(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+    ) -> Result<Vec<u8>, libc::c_int> {
+        self.read_task(ino, fh, offset, size)?.run()
+    }
+
+    /// 准备一次读取, 返回的任务不再访问 inode 表, 可以在其他线程中执行
+    pub fn read_task(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+    ) -> Result<ReadTask, libc::c_int> {
+        if offset < 0 {
+            return Err(libc::EINVAL);
+        }
+        self.apply_completed_writes();
+        // if fh != ino {
+        //     return Err(EACCES);
+        // }
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        debug!("[RFuseFS][read] -> Read data. {:?}", inode.attr.name);
+        let file_size = inode.attr.size;
+
+        let read_size = min(size as u64, file_size.saturating_sub(offset as u64));
+        Ok(ReadTask {
+            io: self.file_io(ino, fh)?,
+            offset: offset as u64,
+            size: read_size as usize,
+            file_size,
+        })
+    }
+
+    /// 从打开的文件中读取数据, 后端完成之后调用 reply
+    ///
+    /// 有异步后端时在 runtime 的任务中回复, 有线程池时在工作线程中回复, 否则在当前线程中回复
+    pub fn spawn_read(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        reply: impl FnOnce(Result<Vec<u8>, libc::c_int>) + Send + 'static,
+    ) {
+        let task = match self.read_task(ino, fh, offset, size) {
+            Ok(task) => task,
+            Err(errno) => return reply(Err(errno)),
+        };
+        if let Some(async_backend) = &self.async_backend {
+            let read = task.into_async(&async_backend.backend);
+            async_backend
+                .runtime
+                .spawn(async move { reply(read.await) });
+            return;
+        }
+        let job = move || reply(task.run());
+        match &self.workers {
+            Some(workers) => workers.execute(job),
+            None => job(),
+        }
+    }
+
+    /// 列出文件夹中 offset 之后的目录项, 对应 readdir, 包括 `.` 和 `..`
+    ///
+    /// 返回 (偏移, ino, 类型, 名字), 下一次从最后一项的偏移继续读取
+    pub fn read_dir_entries(
+        &mut self,
+        ino: u64,
+        offset: i64,
+    ) -> Result<Vec<(i64, u64, FileType, OsString)>, libc::c_int> {
+        if offset < 0 {
+            return Err(libc::EINVAL);
+        }
+        self.ensure_loaded(ino).map_err(|e| e.errno())?;
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        if !inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+        // 根目录的 parent_ino 是它自己
+        let parent = inode.parent_ino;
+        let mut entries = vec![
+            (1, ino, FileType::Directory, OsString::from(".")),
+            (2, parent, FileType::Directory, OsString::from("..")),
+        ];
+        entries.retain(|(entry_offset, ..)| *entry_offset > offset);
+
+        // 目录项的偏移在插入时分配, 读取过程中其他目录项的增删不会影响后续的偏移
+        // 硬链接使用被链接 inode 的类型
+        let children = inode
+            .entries
+            .iter_from(offset.max(FIRST_ENTRY_OFFSET as i64 - 1) as u64)
+            .filter_map(|(entry_offset, entry)| {
+                self.get_inode(entry.ino).map(|child| {
+                    (
+                        entry_offset as i64,
+                        entry.ino,
+                        child.attr.kind.into(),
+                        entry.name.clone(),
+                    )
+                })
+            });
+        entries.extend(children);
+
+        Ok(entries)
+    }
+
+    /// 创建文件夹, 对应 mkdir
+    pub fn make_dir(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+    ) -> Result<FileAttr, libc::c_int> {
+        if name.len() > MAX_NAME_LENGTH as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+        // 父文件夹还没有读取时, 同名的文件可能只存在于源目录中
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+        if self.lookup_name(parent, name).is_some() {
+            return Err(libc::EEXIST);
+        }
+
+        let parent_inode = self.inodes.get_mut(&parent).ok_or(ENOENT)?;
+        if !parent_inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        parent_inode.attr.mtime = SystemTime::now();
+
+        let new_path = children_path(parent_inode);
+
+        let mut attr = InodeAttributes::new(name, InodeKind::Directory, new_path.clone());
+
+        attr.permissions = (mode & !umask & 0o7777) as u16;
+
+        let mut new_inode = Inode::new(parent, attr.clone());
+        let new_file_meta = match self
+            .remote_file_manager
+            .mk_dir(&attr, origin_dir(&self.source_dir, &attr.path))
+        {
+            Ok(meta) => meta,
+            Err(e) => {
+                debug!("[RFuseFS][mkdir] -> Create a directory. {}", e);
+                return Err(e.errno());
+            }
+        };
+        // 这里直接回写了inode的信息
+        new_inode.parent_ino = parent;
+        new_inode.ino = new_file_meta.ino();
+        new_inode.attr = new_file_meta.attr;
+        new_inode.attr.path = new_path;
+
+        debug!(
+            "[RFuseFS][mkdir] -> Create a directory. {}",
+            Path::new(&new_inode.attr.path)
+                .join(&new_inode.attr.name)
+                .display()
+        );
+
+        parent_inode
+            .entries
+            .insert_child(new_inode.attr.name.clone(), new_inode.ino);
+        self.inodes.insert(new_inode.ino, new_inode.clone());
+        Ok(new_inode.file_attr())
+    }
+
+    /// 创建并打开文件, 对应 create, 返回文件属性和句柄
+    pub fn create_file(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+    ) -> Result<(FileAttr, u64), libc::c_int> {
+        debug!("[RFuseFS][create] -> Create and open a file. {:?}", name);
+        if name.len() > MAX_NAME_LENGTH as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+        let access_mask = match flags & libc::O_ACCMODE {
+            libc::O_RDONLY => libc::R_OK,
+            libc::O_WRONLY => libc::W_OK,
+            libc::O_RDWR => libc::R_OK | libc::W_OK,
+            _ => return Err(libc::EINVAL),
+        };
+
+        // 文件已经存在时, O_EXCL 返回 EEXIST, 否则按照 open 处理
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+        if let Some(ino) = self.lookup_name(parent, name) {
+            if flags & libc::O_EXCL != 0 {
+                return Err(libc::EEXIST);
+            }
+            let inode = self.get_inode(ino).ok_or(ENOENT)?;
+            if inode.is_dir() {
+                return Err(libc::EISDIR);
+            }
+            if !check_access(
+                inode.attr.uid,
+                inode.attr.gid,
+                inode.attr.permissions,
+                caller.uid,
+                caller.gid,
+                access_mask,
+            ) {
+                return Err(libc::EACCES);
+            }
+            let fh = self.open_file(ino, flags)?;
+            let inode = self.get_inode(ino).ok_or(ENOENT)?;
+            return Ok((self.file_attr(inode), fh));
+        }
+
+        let parent_inode = self.inodes.get_mut(&parent).ok_or(ENOENT)?;
+        if !parent_inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+
+        // 确认权限
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        parent_inode.attr.mtime = SystemTime::now();
+        let path = children_path(parent_inode);
+        // 这里的 attr 只是占位, 后续会被 RemoteFileManager 回写为真实数据
+        let mut attr = InodeAttributes::new(name, InodeKind::File, path.clone());
+        attr.permissions = (mode & !umask & 0o7777) as u16;
+        let mut new_inode = Inode::new(parent, attr.clone());
+        let new_ino = new_inode.ino;
+        let new_file_meta = match self.remote_file_manager.new_file(
+            new_ino,
+            attr.clone(),
+            origin_dir(&self.source_dir, &attr.path),
+        ) {
+            Ok(meta) => meta,
+            Err(e) => {
+                debug!("[RFuseFS][create] -> Create and open a file. {}", e);
+                return Err(e.errno());
+            }
+        };
+        // 这里直接回写了inode的信息
+        new_inode.parent_ino = parent;
+        new_inode.ino = new_file_meta.ino();
+        new_inode.attr = new_file_meta.attr;
+        new_inode.attr.path = path; // 注意这里的 path 应该是自己管理的地址而不是, 信息源的地址
+
+        parent_inode
+            .entries
+            .insert_child(new_inode.attr.name.clone(), new_inode.ino);
+        self.inodes.insert(new_inode.ino, new_inode.clone());
+
+        let fh = self.open_file(new_inode.ino, flags)?;
+        Ok((new_inode.file_attr(), fh))
+    }
+
+    /// 向打开的文件写入数据, 对应 write, 返回写入的字节数
+    pub fn write_data(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+    ) -> Result<u32, libc::c_int> {
+        let written = self.write_task(ino, fh, offset, data)?.run()?;
+        self.apply_completed_writes();
+        Ok(written)
+    }
+
+    /// 准备一次写入, 返回的任务可以在其他线程中执行, 完成后文件大小在下一个请求开始时更新
+    pub fn write_task(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+    ) -> Result<WriteTask, libc::c_int> {
+        if offset < 0 {
+            return Err(libc::EINVAL);
+        }
+        self.apply_completed_writes();
+
+        // fio 测试不要开这个，这个只能测
+        // debug!(
+        //     "[RFuseFS][write] -> Write data. ino: {}, offset: {}, data: {:?}",
+        //     ino,
+        //     offset,
+        //     String::from_utf8(data.to_vec()).unwrap()
+        // );
+
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        debug!("write() -> Write data. {:?}", inode.attr.name);
+        let size = inode.attr.size;
+
+        // O_APPEND 打开的文件总是写到文件末尾, 还没有完成的追加写已经预留了末尾的位置
+        let offset = match self.is_append(fh) {
+            true => {
+                let end = self
+                    .append_ends
+                    .get(&ino)
+                    .map_or(size, |end| size.max(*end));
+                self.append_ends.insert(ino, end + data.len() as u64);
+                end
+            }
+            false => offset as u64,
+        };
+
+        let io = self.file_io(ino, fh)?;
+        self.writes_in_flight += 1;
+        Ok(WriteTask {
+            io,
+            ino,
+            offset,
+            data: data.to_vec(),
+            size_gen: self.size_gen,
+            done: self.write_done.clone(),
+        })
+    }
+
+    // 文件被截断, 之前预留的追加位置和还没有完成的写入都不能再扩大文件
+    fn mark_truncated(&mut self, ino: u64) {
+        self.append_ends.remove(&ino);
+        self.size_gen += 1;
+        self.truncated.insert(ino, self.size_gen);
+    }
+
+    /// 向打开的文件写入数据, 后端完成之后调用 reply, 回复的线程和 [`Self::spawn_read`] 相同
+    pub fn spawn_write(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        reply: impl FnOnce(Result<u32, libc::c_int>) + Send + 'static,
+    ) {
+        let task = match self.write_task(ino, fh, offset, data) {
+            Ok(task) => task,
+            Err(errno) => return reply(Err(errno)),
+        };
+        if let Some(async_backend) = &self.async_backend {
+            let write = task.into_async(&async_backend.backend);
+            async_backend
+                .runtime
+                .spawn(async move { reply(write.await) });
+            return;
+        }
+        let job = move || reply(task.run());
+        match &self.workers {
+            Some(workers) => workers.execute(job),
+            None => {
+                job();
+                self.apply_completed_writes();
+            }
+        }
+    }
+
+    // fh 以 O_APPEND 打开
+    fn is_append(&self, fh: u64) -> bool {
+        self.handles
+            .get(&fh)
+            .is_some_and(|open_file| open_file.flags & libc::O_APPEND != 0)
+    }
+
+    /// 校验文件权限, 对应 access
+    pub fn check_permission(&self, caller: Caller, ino: u64, mask: i32) -> Result<(), libc::c_int> {
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        if check_access(
+            inode.attr.uid,
+            inode.attr.gid,
+            inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            mask,
+        ) {
+            Ok(())
+        } else {
+            Err(libc::EACCES)
+        }
+    }
+
+    // 与 setattr 一致, 只有所有者或者有写权限的用户可以修改扩展属性
+    fn check_xattr_write(&self, caller: Caller, ino: u64) -> Result<(), libc::c_int> {
+        if !self.xattr {
+            return Err(libc::ENOSYS);
+        }
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        if inode.attr.uid != caller.uid
+            && !check_access(
+                inode.attr.uid,
+                inode.attr.gid,
+                inode.attr.permissions,
+                caller.uid,
+                caller.gid,
+                libc::W_OK,
+            )
+        {
+            return Err(libc::EACCES);
+        }
+        Ok(())
+    }
+
+    // 读取扩展属性之前的检查
+    fn check_xattr_read(&self, ino: u64) -> Result<(), libc::c_int> {
+        if !self.xattr {
+            return Err(libc::ENOSYS);
+        }
+        self.get_inode(ino).ok_or(ENOENT)?;
+        Ok(())
+    }
+
+    /// 读取扩展属性, 对应 getxattr
+    pub fn get_xattr(&self, ino: u64, name: &OsStr) -> Result<Vec<u8>, libc::c_int> {
+        self.check_xattr_read(ino)?;
+        let name = name.to_str().ok_or(RFUSE_ENOATTR)?;
+        match self.remote_file_manager.get_xattr(ino, name) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                debug!("[RFuseFS][getxattr] -> Get an extended attribute. {}", e);
+                Err(e.errno())
+            }
+        }
+    }
+
+    /// 设置扩展属性, 对应 setxattr
+    pub fn set_xattr(
+        &self,
+        caller: Caller,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), libc::c_int> {
+        self.check_xattr_write(caller, ino)?;
+        // 后端的扩展属性名只支持 UTF-8
+        let name = name.to_str().ok_or(libc::EINVAL)?;
+        match self.remote_file_manager.set_xattr(ino, name, value, flags) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                debug!("[RFuseFS][setxattr] -> Set an extended attribute. {}", e);
+                Err(e.errno())
+            }
+        }
+    }
+
+    /// 读取扩展属性, 有异步后端时在 runtime 的任务中调用 reply, 否则在当前线程中回复
+    pub fn spawn_get_xattr(
+        &self,
+        ino: u64,
+        name: &OsStr,
+        reply: impl FnOnce(Result<Vec<u8>, libc::c_int>) + Send + 'static,
+    ) {
+        let async_backend = match &self.async_backend {
+            Some(async_backend) => async_backend,
+            None => return reply(self.get_xattr(ino, name)),
+        };
+        if let Err(errno) = self.check_xattr_read(ino) {
+            return reply(Err(errno));
+        }
+        let name = match name.to_str() {
+            Some(name) => name.to_string(),
+            None => return reply(Err(RFUSE_ENOATTR)),
+        };
+        let get_xattr = match self.remote_file_manager.tmp_file(ino) {
+            Some(tf) => async_backend.backend.get_xattr(tf, name),
+            None => return reply(Err(ENOENT)),
+        };
+        async_backend.runtime.spawn(async move {
+            reply(get_xattr.await.map_err(|e| {
+                debug!("[RFuseFS][getxattr] -> Get an extended attribute. {}", e);
+                e.errno()
+            }))
+        });
+    }
+
+    /// 列出扩展属性名, 对应 listxattr, 每个名字以 `\0` 结尾
+    pub fn list_xattr(&self, ino: u64) -> Result<Vec<u8>, libc::c_int> {
+        self.check_xattr_read(ino)?;
+        match self.remote_file_manager.list_xattr(ino) {
+            Ok(names) => Ok(names),
+            Err(e) => {
+                debug!(
+                    "[RFuseFS][listxattr] -> List extended attribute names. {}",
+                    e
+                );
+                Err(e.errno())
+            }
+        }
+    }
+
+    /// 列出扩展属性名, 回复的线程和 [`Self::spawn_get_xattr`] 相同
+    pub fn spawn_list_xattr(
+        &self,
+        ino: u64,
+        reply: impl FnOnce(Result<Vec<u8>, libc::c_int>) + Send + 'static,
+    ) {
+        let async_backend = match &self.async_backend {
+            Some(async_backend) => async_backend,
+            None => return reply(self.list_xattr(ino)),
+        };
+        if let Err(errno) = self.check_xattr_read(ino) {
+            return reply(Err(errno));
+        }
+        let list_xattr = match self.remote_file_manager.tmp_file(ino) {
+            Some(tf) => async_backend.backend.list_xattr(tf),
+            None => return reply(Err(ENOENT)),
+        };
+        async_backend.runtime.spawn(async move {
+            reply(list_xattr.await.map_err(|e| {
+                debug!(
+                    "[RFuseFS][listxattr] -> List extended attribute names. {}",
+                    e
+                );
+                e.errno()
+            }))
+        });
+    }
+
+    /// 删除扩展属性, 对应 removexattr
+    pub fn remove_xattr(&self, caller: Caller, ino: u64, name: &OsStr) -> Result<(), libc::c_int> {
+        self.check_xattr_write(caller, ino)?;
+        let name = name.to_str().ok_or(RFUSE_ENOATTR)?;
+        match self.remote_file_manager.remove_xattr(ino, name) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                debug!(
+                    "[RFuseFS][removexattr] -> Remove an extended attribute. {}",
+                    e
+                );
+                Err(e.errno())
+            }
+        }
+    }
+
+    /// 文件改名或者移动, 对应 rename
+    ///
+    /// 新名字已经存在时按照 rename(2) 的规则覆盖, flags 可以是 `RFUSE_RENAME_NOREPLACE` (不覆盖)
+    /// 或者 `RFUSE_RENAME_EXCHANGE` (原子交换两个目录项)
+    pub fn rename_entry(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+    ) -> Result<(), libc::c_int> {
+        if newname.len() > MAX_NAME_LENGTH as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+        debug!("[RFuseFS][rename] -> flags: {}", flags);
+        let exchange = flags & RFUSE_RENAME_EXCHANGE != 0;
+        let noreplace = flags & RFUSE_RENAME_NOREPLACE != 0;
+        if flags & !(RFUSE_RENAME_EXCHANGE | RFUSE_RENAME_NOREPLACE) != 0 || (exchange && noreplace)
+        {
+            return Err(libc::EINVAL);
+        }
+
+        let parent_inode = self.get_inode(parent).ok_or(ENOENT)?.clone();
+        let new_parent_inode = self.get_inode(newparent).ok_or(ENOENT)?.clone();
+        if !parent_inode.is_dir() || !new_parent_inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+        self.ensure_loaded(newparent).map_err(|e| e.errno())?;
+
+        // 旧文件的inode
+        let inode = self
+            .lookup_name(parent, name)
+            .and_then(|ino| self.get_inode(ino))
+            .ok_or(ENOENT)?
+            .clone();
+        // 新名字上已经存在的文件
+        let existing = self
+            .lookup_name(newparent, newname)
+            .and_then(|ino| self.get_inode(ino))
+            .cloned();
+
+        // 确认是否有当前文件夹权限
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        // "Sticky bit" handling
+        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
+            && caller.uid != 0
+            && caller.uid != parent_inode.attr.uid
+            && caller.uid != inode.attr.uid
+        {
+            return Err(libc::EACCES);
+        }
+
+        debug!(
+            "[RFuseFS][rename] -> Rename a file. {} -> {}",
+            Path::new(&inode.attr.path).join(&inode.attr.name).display(),
+            Path::new(&children_path(&new_parent_inode))
+                .join(newname)
+                .display()
+        );
+
+        // 确认是否有新的文件夹的权限
+        if !check_access(
+            new_parent_inode.attr.uid,
+            new_parent_inode.attr.gid,
+            new_parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        // "Sticky bit" handling in new_parent
+        if let Some(existing_inode) = &existing {
+            if new_parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
+                && caller.uid != 0
+                && caller.uid != new_parent_inode.attr.uid
+                && caller.uid != existing_inode.attr.uid
+            {
+                return Err(libc::EACCES);
+            }
+        }
+
+        // Only move an existing directory to a new parent, if we have write access to it,
+        // because that will change the ".." link in it
+        if inode.is_dir()
+            && parent != newparent
+            && !check_access(
+                inode.attr.uid,
+                inode.attr.gid,
+                inode.attr.permissions,
+                caller.uid,
+                caller.gid,
+                libc::W_OK,
+            )
+        {
+            return Err(libc::EACCES);
+        }
+
+        match &existing {
+            // 新旧名字是同一个文件时什么都不做
+            Some(target) if target.ino == inode.ino => return Ok(()),
+            Some(_) if noreplace => return Err(libc::EEXIST),
+            None if exchange => return Err(ENOENT),
+            Some(target) if !exchange => {
+                // 覆盖时两边的类型必须相同, 文件夹只能覆盖空文件夹
+                if inode.is_dir() && !target.is_dir() {
+                    return Err(libc::ENOTDIR);
+                }
+                if !inode.is_dir() && target.is_dir() {
+                    return Err(libc::EISDIR);
+                }
+                if target.is_dir() {
+                    self.ensure_loaded(target.ino).map_err(|e| e.errno())?;
+                    if self
+                        .get_inode(target.ino)
+                        .is_some_and(|dir| !dir.entries.is_empty())
+                    {
+                        return Err(libc::ENOTEMPTY);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        // 文件夹不能移动到自己的子孙下, 交换时另一边也一样
+        if inode.is_dir() && self.is_ancestor(inode.ino, newparent) {
+            return Err(libc::EINVAL);
+        }
+        if let Some(target) = existing.as_ref().filter(|t| exchange && t.is_dir()) {
+            if self.is_ancestor(target.ino, parent) {
+                return Err(libc::EINVAL);
+            }
+        }
+
+        // 真正修改文件
+        let new_time = SystemTime::now();
+        let new_path = children_path(&new_parent_inode);
+        let primary = self.is_primary_entry(parent, name, inode.ino);
+        let result = if primary {
+            self.remote_file_manager.rename(
+                inode.ino,
+                newname.to_os_string(),
+                origin_dir(&self.source_dir, &new_path),
+                &new_time,
+                flags,
+            )
+        } else {
+            // 重命名的是硬链接, 只修改文件夹中的记录
+            self.remote_file_manager.rename_link(
+                name.to_os_string(),
+                origin_dir(&self.source_dir, &children_path(&parent_inode)),
+                origin_dir(&self.source_dir, &new_path).join(newname),
+                &new_time,
+                flags,
+            )
+        };
+        if let Err(e) = result {
+            debug!("[RFuseFS][rename] -> Rename a file. {}", e);
+            return Err(e.errno());
+        }
+
+        // 新旧文件夹可能是同一个, 所以直接修改 inode 表中的记录
+        match existing {
+            Some(target) if exchange => {
+                let source_primary = self.take_entry(parent, name, inode.ino);
+                let target_primary = self.take_entry(newparent, newname, target.ino);
+                self.put_entry(newparent, newname, inode.ino, source_primary);
+                self.put_entry(parent, name, target.ino, target_primary);
+                if target_primary && target.is_dir() {
+                    self.update_descendant_paths(target.ino);
+                }
+                if let Some(target) = self.inodes.get_mut(&target.ino) {
+                    target.attr.ctime = new_time;
+                }
+            }
+            existing => {
+                // 被覆盖的文件从新文件夹中摘除
+                if let Some(target) = existing {
+                    self.detach_entry(newparent, newname, target.ino);
+                }
+                let source_primary = self.take_entry(parent, name, inode.ino);
+                self.put_entry(newparent, newname, inode.ino, source_primary);
+            }
+        }
+        // 文件夹的子孙记录的是完整路径, 需要整体换到新的位置
+        if inode.is_dir() {
+            self.update_descendant_paths(inode.ino);
+        }
+        if let Some(inode) = self.inodes.get_mut(&inode.ino) {
+            inode.attr.ctime = new_time;
+        }
+        for dir in [parent, newparent] {
+            if let Some(dir) = self.inodes.get_mut(&dir) {
+                dir.attr.mtime = new_time;
+                dir.attr.ctime = new_time;
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除空文件夹, 对应 rmdir
+    ///
+    /// 源目录中的文件夹不会被递归删除, inode 表过期时由源目录返回 `ENOTEMPTY`
+    pub fn remove_dir_entry(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+    ) -> Result<(), libc::c_int> {
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+        let ino = self.lookup_name(parent, name).ok_or(ENOENT)?;
+
+        // 做一些异常处理, 判断是否为空之前需要先读取文件夹
+        self.ensure_loaded(ino).map_err(|e| e.errno())?;
+        let inode = self.get_inode(ino).ok_or(ENOENT)?;
+        // 不是文件夹类型
+        if !inode.is_dir() {
+            return Err(libc::ENOTDIR);
+        }
+        // 文件夹不为空
+        if !inode.entries.is_empty() {
+            return Err(libc::ENOTEMPTY);
+        }
+        let mut parent_inode = self.get_inode(parent).ok_or(ENOENT)?.clone();
+
+        // 确认是否有当前文件夹权限
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        // "Sticky bit" handling
+        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
+            && caller.uid != 0
+            && caller.uid != parent_inode.attr.uid
+            && caller.uid != inode.attr.uid
+        {
+            return Err(libc::EACCES);
+        }
+
+        let new_time = SystemTime::now();
+        match self.remote_file_manager.remove_dir(ino, &new_time) {
+            Ok(_) => {}
+            Err(e) => {
+                debug!("[RFuseFS][rmdir] -> Remove a directory. {}", e);
+                return Err(e.errno());
+            }
+        };
+        parent_inode.entries.remove_child(ino);
+        parent_inode.attr.mtime = new_time;
+        parent_inode.attr.ctime = new_time;
+        self.write_inode(&parent_inode);
+        self.inodes.remove(&ino);
+        Ok(())
+    }
+
+    /// 删除文件或者硬链接, 对应 unlink
+    ///
+    /// 删除的是最后一个名字并且文件仍然打开时, inode 保留到最后一次 release,
+    /// 通过后端句柄的读写不受影响; 没有后端句柄的文件在删除后按路径读写会返回 `ENOENT`
+    pub fn unlink_entry(
+        &mut self,
+        caller: Caller,
+        parent: u64,
+        name: &OsStr,
+    ) -> Result<(), libc::c_int> {
+        let name = name.to_os_string();
+        self.ensure_loaded(parent).map_err(|e| e.errno())?;
+        let ino = self.lookup_name(parent, &name).ok_or(ENOENT)?;
+
+        let parent_inode = self.inodes.get_mut(&parent).ok_or(ENOENT)?;
+
+        if !check_access(
+            parent_inode.attr.uid,
+            parent_inode.attr.gid,
+            parent_inode.attr.permissions,
+            caller.uid,
+            caller.gid,
+            libc::W_OK,
+        ) {
+            return Err(libc::EACCES);
+        }
+
+        let uid = caller.uid;
+        // "Sticky bit" handling
+        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
+            && uid != 0
+            && uid != parent_inode.attr.uid
+        // && uid != inode.attr.uid
+        {
+            return Err(libc::EACCES);
+        }
+
+        let new_time = SystemTime::now();
+
+        // 删除的是硬链接, 被链接的文件保留
+        if parent_inode
+            .entries
+            .get(&name)
+            .is_some_and(|entry| !entry.primary)
+        {
+            match self.remote_file_manager.remove_link(
+                name.clone(),
+                origin_dir(&self.source_dir, &children_path(parent_inode)),
+                &new_time,
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    debug!("[RFuseFS][unlink] -> Remove a hard link. {}", e);
+                    return Err(e.errno());
+                }
+            };
+            parent_inode.entries.remove(&name);
+            parent_inode.attr.mtime = new_time;
+            parent_inode.attr.ctime = new_time;
+            if let Some(inode) = self.inodes.get_mut(&ino) {
+                inode.nlink -= 1;
+                inode.attr.ctime = new_time;
+            }
+            return Ok(());
+        }
+
+        match self.remote_file_manager.remove_file(ino, &new_time) {
+            Ok(_) => {}
+            Err(e) => {
+                debug!("[RFuseFS][unlink] -> Remove a file. {}", e);
+                return Err(e.errno());
+            }
+        };
+        parent_inode.entries.remove_child(ino);
+        parent_inode.attr.mtime = new_time;
+        parent_inode.attr.ctime = new_time;
+        if let Some(inode) = self.inodes.get_mut(&ino) {
+            inode.attr.ctime = new_time;
+        }
+        // 还有其他硬链接时 inode 不能删除
+        if self.get_inode(ino).is_some_and(|inode| inode.nlink > 1) {
+            self.promote_hard_link(ino);
+        } else {
+            self.drop_inode(ino);
+        }
+        Ok(())
+    }
+
+    /// 释放打开的文件或者文件夹, 对应 release 和 releasedir
+    pub fn release_handle(&mut self, fh: u64) {
+        let ino = match self.handles.remove(&fh) {
+            Some(open_file) => open_file.ino,
+            None => return,
+        };
+        // 已经删除的文件在最后一个句柄释放之后才从 inode 表中移除
+        if self.unlinked.contains(&ino) && !self.is_open(ino) {
+            self.unlinked.remove(&ino);
+            self.inodes.remove(&ino);
+        }
+    }
+}
+
+impl Filesystem for RFuseFS {
+    fn init(
+        &mut self,
+        _req: &Request,
+        config: &mut fuser::KernelConfig,
+    ) -> Result<(), libc::c_int> {
+        info!("[RFuseFS][init] -> Initialize filesystem.");
+        if self.cache.writeback_cache {
+            if let Err(unsupported) = config.add_capabilities(FUSE_WRITEBACK_CACHE) {
+                error!(
+                    "[RFuseFS][init] -> Kernel does not support writeback cache. {:#x}",
+                    unsupported
+                );
+                self.cache.writeback_cache = false;
+            }
+        }
+
+        match self
+            .remote_file_manager
+            .initialize_fs(&mut self.inodes, self.source_dir.clone())
+        {
+            Ok(_) => {}
+            Err(e) => {
+                debug!("[RFuseFS][init] -> Initialize filesystem. {}", e);
+                return Err(libc::EIO);
+            }
+        };
+        // 只有根目录在挂载时读取
+        self.ensure_loaded(FUSE_ROOT_ID).map_err(|e| e.errno())?;
+        // debug!("Inodes: {:?}", self.inodes);
+        Ok(())
+    }
+
+    fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        // info!("[RFuseFS][lookup] -> Look up a directory entry by name.");
+        match self.lookup_entry(req.into(), parent, name) {
+            Ok(attr) => reply.entry(&self.cache.entry_ttl, &attr, 0),
+            // ino 为 0 的目录项让内核缓存这个名字不存在
+            Err(ENOENT) if !self.cache.negative_ttl.is_zero() => {
+                reply.entry(&self.cache.negative_ttl, &negative_entry(), 0)
+            }
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        // info!("[RFuseFS][getattr] -> Get attributes of a file.");
+        self.apply_completed_writes();
+        match self.get_inode(ino) {
+            Some(inode) => {
+                // debug!(
+                //     "[RFuseFS][getattr] -> Get file attributes. {}",
+                //     inode.attr.name.clone()
+                // );
+                reply.attr(&self.cache.attr_ttl, &self.file_attr(inode))
+            }
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        info!("[RFuseFS][setattr] -> Set attributes of a file.");
+        let changes = SetAttr {
+            mode,
+            uid,
+            gid,
+            size,
+            atime,
+            mtime,
+        };
+        match self.set_attr(req.into(), ino, changes) {
+            Ok(attr) => reply.attr(&self.cache.attr_ttl, &attr),
+            Err(errno) => reply.error(errno),
         }
+    }
 
-        // if let Some(ctime) = ctime {
-        //     inode.attr.ctime = ctime;
-        // }
-
-        if let Some(uid) = uid {
-            inode.attr.uid = uid;
-        }
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        info!("[RFuseFS][readlink] -> Read symbolic link.");
+        match self.get_inode(ino) {
+            Some(inode) => {
+                if inode.attr.kind != InodeKind::Symlink {
+                    reply.error(libc::EINVAL);
+                    return;
+                }
+            }
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
 
-        if let Some(gid) = gid {
-            inode.attr.gid = gid;
+        if let Some(async_backend) = &self.async_backend {
+            let read_link = match self.remote_file_manager.tmp_file(ino) {
+                Some(tf) => async_backend.backend.read_link(tf),
+                None => {
+                    reply.error(ENOENT);
+                    return;
+                }
+            };
+            async_backend.runtime.spawn(async move {
+                match read_link.await {
+                    Ok(target) => reply.data(target.as_os_str().as_bytes()),
+                    Err(e) => {
+                        debug!("[RFuseFS][readlink] -> Read symbolic link. {}", e);
+                        reply.error(e.errno());
+                    }
+                }
+            });
+            return;
         }
 
-        // debug!(
-        //     "[RFuseFS][setattr] -> Set file attributes. {}",
-        //     inode.attr.name.clone()
-        // );
-        match self.remote_file_manager.set_attr(ino, &inode.attr) {
-            Ok(_) => {
-                reply.attr(&Duration::new(0, 0), &inode.file_attr());
-            }
+        match self.remote_file_manager.read_link(ino) {
+            Ok(target) => reply.data(target.as_os_str().as_bytes()),
             Err(e) => {
-                debug!("[RFuseFS][setattr] -> Set file attributes. {}", e);
-                reply.error(libc::EIO);
+                debug!("[RFuseFS][readlink] -> Read symbolic link. {}", e);
+                reply.error(e.errno());
             }
         };
     }
 
     fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        self.apply_completed_writes();
         let (access_mask, _read, _write) = match flags & libc::O_ACCMODE {
             libc::O_RDONLY => {
                 // Behavior is undefined, but most filesystems return EACCES
@@ -268,13 +2329,10 @@ impl Filesystem for RFuseFS {
             }
         };
 
-        match self.inodes.get_mut(&ino) {
+        match self.inodes.get(&ino) {
             Some(inode) => {
-                debug!(
-                    "[RFuseFS][open] -> Open a file. {}",
-                    inode.attr.name.clone()
-                );
-                if check_access(
+                debug!("[RFuseFS][open] -> Open a file. {:?}", inode.attr.name);
+                if !check_access(
                     inode.attr.uid,
                     inode.attr.gid,
                     inode.attr.permissions,
@@ -282,21 +2340,31 @@ impl Filesystem for RFuseFS {
                     req.gid(),
                     access_mask,
                 ) {
-                    let open_flags = if self.direct_io { FOPEN_DIRECT_IO } else { 0 };
-                    reply.opened(ino, open_flags);
+                    reply.error(libc::EACCES);
+                    return;
                 }
             }
             None => {
                 reply.error(ENOENT);
+                return;
             }
         };
+
+        let fh = match self.open_file(ino, flags) {
+            Ok(fh) => fh,
+            Err(errno) => {
+                reply.error(errno);
+                return;
+            }
+        };
+        reply.opened(fh, self.cache.open_flags());
     }
 
     fn read(
         &mut self,
         _req: &Request,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         size: u32,
         _flags: i32,
@@ -308,34 +2376,10 @@ impl Filesystem for RFuseFS {
             "[RFuseFS][read] {:?} offset={:?} size={:?}",
             ino, offset, size
         );
-        assert!(offset >= 0);
-        // if fh != ino {
-        //     reply.error(EACCES);
-        //     return;
-        // }
-        let file_size = match self.get_inode(ino) {
-            Some(inode) => {
-                debug!("[RFuseFS][read] -> Read data. {}", inode.attr.name.clone());
-                inode.attr.size
-            }
-            None => {
-                reply.error(ENOENT);
-                return;
-            }
-        };
-
-        let read_size = min(size, file_size.saturating_sub(offset as u64) as u32);
-        let mut buf = vec![0; read_size as usize];
-        match self.remote_file_manager.read(ino, &mut buf, offset as u64) {
-            Ok(_) => {}
-            Err(e) => {
-                debug!("[RFuseFS][read] -> Read data. {}", e);
-                reply.error(libc::EIO);
-                return;
-            }
-        };
-
-        reply.data(&buf);
+        self.spawn_read(ino, fh, offset, size, move |result| match result {
+            Ok(data) => reply.data(&data),
+            Err(errno) => reply.error(errno),
+        });
     }
 
     fn readdir(
@@ -347,26 +2391,15 @@ impl Filesystem for RFuseFS {
         mut reply: ReplyDirectory,
     ) {
         // info!("[RFuseFS][readdir] -> Read directory entries.");
-        let inode = self.get_inode(ino).unwrap();
-        let mut entires = vec![
-            (ino, FileType::Directory, ".".to_owned()),
-            (ino, FileType::Directory, "..".to_owned()),
-        ];
-
-        let children: Vec<(u64, FileType, String)> = inode
-            .children_ino
-            .clone()
-            .iter()
-            .map(|ino| {
-                let inode = self.get_inode(*ino).unwrap();
-                (inode.ino, inode.attr.kind.into(), inode.attr.name.clone())
-            })
-            .collect();
-
-        entires.extend(children);
-
-        for (index, (ino, kind, name)) in entires.into_iter().enumerate().skip(offset as usize) {
-            if reply.add(ino, (index + 1) as i64, kind, name) {
+        let entries = match self.read_dir_entries(ino, offset) {
+            Ok(entries) => entries,
+            Err(errno) => {
+                reply.error(errno);
+                return;
+            }
+        };
+        for (entry_offset, ino, kind, name) in entries {
+            if reply.add(ino, entry_offset, kind, name) {
                 break;
             }
         }
@@ -376,67 +2409,10 @@ impl Filesystem for RFuseFS {
 
     fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
         info!("[RFuseFS][rmdir] -> Remove a directory.");
-        let name = name.to_str().unwrap().to_string();
-        let ino = match self.lookup_name(parent, &name) {
-            Some(ino) => ino,
-            None => {
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-
-        // 做一些异常处理
-        let inode = self.get_inode(ino).unwrap();
-        // 不是文件夹类型
-        if !inode.is_dir() {
-            reply.error(libc::ENOTDIR);
-            return;
-        }
-        // 文件夹不为空
-        if !inode.children_ino.is_empty() {
-            reply.error(libc::ENOTEMPTY);
-            return;
-        }
-        let mut parent_inode = self.get_inode(parent).unwrap().clone();
-
-        // 确认是否有当前文件夹权限
-        if !check_access(
-            parent_inode.attr.uid,
-            parent_inode.attr.gid,
-            parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::W_OK,
-        ) {
-            reply.error(libc::EACCES);
-            return;
-        }
-
-        // "Sticky bit" handling
-        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
-            && req.uid() != 0
-            && req.uid() != parent_inode.attr.uid
-            && req.uid() != inode.attr.uid
-        {
-            reply.error(libc::EACCES);
-            return;
+        match self.remove_dir_entry(req.into(), parent, name) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
         }
-
-        let new_time = SystemTime::now();
-        parent_inode.remove_child(ino);
-        match self.remote_file_manager.remove_dir(ino, &new_time) {
-            Ok(_) => {}
-            Err(e) => {
-                debug!("[RFuseFS][rmdir] -> Remove a directory. {}", e);
-                reply.error(libc::EIO);
-                return;
-            }
-        };
-        parent_inode.attr.mtime = new_time;
-        parent_inode.attr.ctime = new_time;
-        self.write_inode(&parent_inode);
-        self.inodes.remove(&ino);
-        reply.ok();
     }
 
     fn mkdir(
@@ -445,17 +2421,51 @@ impl Filesystem for RFuseFS {
         parent: u64,
         name: &OsStr,
         mode: u32,
-        _umask: u32,
+        umask: u32,
         reply: ReplyEntry,
     ) {
         info!("[RFuseFS][mkdir] -> Create a directory.");
-        let name = name.to_str().unwrap().to_string();
+        match self.make_dir(req.into(), parent, name, mode, umask) {
+            Ok(attr) => reply.entry(&self.cache.entry_ttl, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        info!("[RFuseFS][symlink] -> Create a symbolic link.");
+        if link_name.len() > MAX_NAME_LENGTH as usize {
+            reply.error(libc::ENAMETOOLONG);
+            return;
+        }
+
+        let name = link_name.to_os_string();
+        if let Err(e) = self.ensure_loaded(parent) {
+            reply.error(e.errno());
+            return;
+        }
         if self.lookup_name(parent, &name).is_some() {
             reply.error(libc::EEXIST);
             return;
         }
 
-        let parent_inode = self.inodes.get_mut(&parent).unwrap();
+        let parent_inode = match self.inodes.get_mut(&parent) {
+            Some(inode) => inode,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        if !parent_inode.is_dir() {
+            reply.error(libc::ENOTDIR);
+            return;
+        }
 
         if !check_access(
             parent_inode.attr.uid,
@@ -470,246 +2480,160 @@ impl Filesystem for RFuseFS {
         }
 
         parent_inode.attr.mtime = SystemTime::now();
-        assert!(parent_inode.is_dir());
-
-        let new_path: String = {
-            if parent_inode.attr.name.is_empty() {
-                parent_inode.attr.path.clone()
-            } else {
-                parent_inode.attr.path.clone() + &parent_inode.attr.name + "/"
-            }
-        };
 
-        let mut attr = InodeAttributes::new(name.clone(), InodeKind::Directory, new_path.clone());
-
-        attr.permissions = mode as u16;
+        let new_path = children_path(parent_inode);
 
+        let attr = InodeAttributes::new(name.clone(), InodeKind::Symlink, new_path.clone());
         let mut new_inode = Inode::new(parent, attr.clone());
-        let new_file_meta = match self
-            .remote_file_manager
-            .mk_dir(&attr, self.source_dir.clone() + &attr.path)
-        {
+        let new_link_meta = match self.remote_file_manager.symlink(
+            &attr,
+            origin_dir(&self.source_dir, &attr.path),
+            target,
+        ) {
             Ok(meta) => meta,
             Err(e) => {
-                debug!("[RFuseFS][mkdir] -> Create a directory. {}", e);
-                reply.error(libc::EIO);
+                debug!("[RFuseFS][symlink] -> Create a symbolic link. {}", e);
+                reply.error(e.errno());
                 return;
             }
         };
         // 这里直接回写了inode的信息
-        new_inode.parent_ino = parent;
-        new_inode.ino = new_file_meta.ino();
-        new_inode.attr = new_file_meta.attr;
+        new_inode.ino = new_link_meta.ino();
+        new_inode.attr = new_link_meta.attr;
         new_inode.attr.path = new_path;
 
         debug!(
-            "[RFuseFS][mkdir] -> Create a directory. {}",
-            new_inode.attr.path.clone() + &new_inode.attr.name
+            "[RFuseFS][symlink] -> Create a symbolic link. {} -> {}",
+            Path::new(&new_inode.attr.path)
+                .join(&new_inode.attr.name)
+                .display(),
+            target.display()
         );
 
-        parent_inode.insert_child(new_inode.ino);
+        parent_inode
+            .entries
+            .insert_child(new_inode.attr.name.clone(), new_inode.ino);
         self.inodes.insert(new_inode.ino, new_inode.clone());
-        reply.entry(&Duration::new(0, 0), &new_inode.file_attr(), 0);
+        reply.entry(&self.cache.entry_ttl, &new_inode.file_attr(), 0);
     }
 
-    fn rename(
+    fn link(
         &mut self,
         req: &Request<'_>,
-        parent: u64,
-        name: &OsStr,
+        ino: u64,
         newparent: u64,
         newname: &OsStr,
-        #[cfg(target_os = "linux")] flags: u32,
-        #[cfg(not(target_os = "linux"))] _flags: u32,
-        reply: fuser::ReplyEmpty,
+        reply: ReplyEntry,
     ) {
-        debug!("[RFuseFS][rename] -> Rename a file.");
+        info!("[RFuseFS][link] -> Create a hard link.");
+        self.apply_completed_writes();
+        if newname.len() > MAX_NAME_LENGTH as usize {
+            reply.error(libc::ENAMETOOLONG);
+            return;
+        }
 
-        // 旧文件的inode
-        let mut inode = match self.lookup_name(parent, name.to_str().unwrap()) {
-            Some(ino) => self.get_inode(ino).unwrap().clone(),
-            None => {
-                reply.error(libc::ENOENT);
+        // 不允许给文件夹创建硬链接
+        match self.get_inode(ino) {
+            Some(inode) if inode.is_dir() => {
+                reply.error(libc::EPERM);
                 return;
             }
-        };
-
-        // 新文件的inode
-        let mut parent_inode = match self.get_inode(parent) {
-            Some(ino) => ino.clone(),
+            Some(_) => {}
             None => {
-                reply.error(libc::ENOENT);
+                reply.error(ENOENT);
                 return;
             }
         };
-
-        // 确认是否有当前文件夹权限
-        if !check_access(
-            parent_inode.attr.uid,
-            parent_inode.attr.gid,
-            parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::W_OK,
-        ) {
-            reply.error(libc::EACCES);
+        if self.get_inode(newparent).is_none() {
+            reply.error(ENOENT);
             return;
         }
 
-        #[cfg(target_os = "linux")]
-        debug!(
-            "[RFuseFS][rename] -> flags: {}",
-            flags & libc::RENAME_EXCHANGE
-        );
-
-        // "Sticky bit" handling
-        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
-            && req.uid() != 0
-            && req.uid() != parent_inode.attr.uid
-            && req.uid() != inode.attr.uid
-        {
-            reply.error(libc::EACCES);
+        let name = newname.to_os_string();
+        if let Err(e) = self.ensure_loaded(newparent) {
+            reply.error(e.errno());
             return;
         }
-
-        let new_name = newname.to_str().unwrap().to_string();
-        if self.lookup_name(parent, &new_name).is_some() {
+        if self.lookup_name(newparent, &name).is_some() {
             reply.error(libc::EEXIST);
             return;
         }
 
-        // 新的父文件夹
-        let mut new_parent_inode = match self.get_inode(newparent) {
-            Some(inode) => inode.clone(),
+        let new_parent_inode = match self.inodes.get_mut(&newparent) {
+            Some(inode) => inode,
             None => {
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-
-        debug!(
-            "[RFuseFS][rename] -> Rename a file. name: {} ",
-            inode.attr.name,
-        );
-
-        debug!(
-            "[RFuseFS][rename] -> Rename a file. {} -> {}",
-            inode.attr.path.clone() + &inode.attr.name,
-            new_parent_inode.attr.path.clone() + &new_parent_inode.attr.name + &new_name
-        );
-
-        // 确认是否有新的文件夹的权限
-        if !check_access(
-            new_parent_inode.attr.uid,
-            new_parent_inode.attr.gid,
-            new_parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::W_OK,
-        ) {
-            reply.error(libc::EACCES);
-            return;
-        }
-
-        // "Sticky bit" handling in new_parent
-        if new_parent_inode.attr.permissions & RFUSE_S_ISVTX != 0 {
-            if let Some(existing_attrs) = self.lookup_name(newparent, newname.to_str().unwrap()) {
-                let existing_inode = self.get_inode(existing_attrs).unwrap();
-                if req.uid() != 0
-                    && req.uid() != new_parent_inode.attr.uid
-                    && req.uid() != existing_inode.attr.uid
-                {
-                    reply.error(libc::EACCES);
-                    return;
-                }
-            }
-        }
-
-        // TODO: 后续可以支持一下这个用的不多，它是用来交换两个文件夹名字的
-        // https://github.com/cberner/fuser/blob/99aa528056f02cbcfc5283a16c6f05643f536271/examples/simple.rs#L1172-L1220
-        #[cfg(target_os = "linux")]
-        if flags & libc::RENAME_EXCHANGE != 0 {
-            reply.error(libc::ENOSYS);
-            return;
-        }
-
-        // 这里好像是不为空的文件夹不允许改名
-        // Only overwrite an existing directory if it's empty
-        if let Some(new_name_attrs) = self.lookup_name(newparent, newname.to_str().unwrap()) {
-            let existing_inode = self.get_inode(new_name_attrs).unwrap();
-            if existing_inode.is_dir() && !existing_inode.children_ino.is_empty() {
-                reply.error(libc::ENOTEMPTY);
-                return;
-            }
-        }
-
-        // Only move an existing directory to a new parent, if we have write access to it,
-        // because that will change the ".." link in it
-        if inode.is_dir()
-            && parent != newparent
-            && !check_access(
-                inode.attr.uid,
-                inode.attr.gid,
-                inode.attr.permissions,
-                req.uid(),
-                req.gid(),
-                libc::W_OK,
-            )
-        {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        if !check_access(
+            new_parent_inode.attr.uid,
+            new_parent_inode.attr.gid,
+            new_parent_inode.attr.permissions,
+            req.uid(),
+            req.gid(),
+            libc::W_OK,
+        ) {
             reply.error(libc::EACCES);
             return;
         }
 
-        // 真正修改文件
-        let new_time = SystemTime::now();
-        let new_path = {
-            if new_parent_inode.attr.name.is_empty() {
-                new_parent_inode.attr.path.clone()
-            } else {
-                new_parent_inode.attr.path.clone() + &new_parent_inode.attr.name + "/"
-            }
-        };
-        match self.remote_file_manager.rename(
-            inode.ino,
-            new_name.clone(),
-            self.source_dir.clone() + &new_path,
-            &new_time,
+        match self.remote_file_manager.link(
+            ino,
+            name.clone(),
+            origin_dir(&self.source_dir, &children_path(new_parent_inode)),
         ) {
             Ok(_) => {}
             Err(e) => {
-                debug!("[RFuseFS][rename] -> Rename a file. {}", e);
-                reply.error(libc::EIO);
+                debug!("[RFuseFS][link] -> Create a hard link. {}", e);
+                reply.error(e.errno());
                 return;
             }
         };
 
-        // 修改为新的名字
-        inode.attr.name = new_name;
-        inode.attr.path = new_path;
-        // 修改时间
-        // inode.attr.mtime = new_time;
-        inode.attr.ctime = new_time;
-        // 回写到缓存
-        self.write_inode(&inode);
-        // 删除旧文件夹的内容
-        parent_inode.remove_child(inode.ino);
-        // parent_inode.attr.mtime = new_time;
-        parent_inode.attr.ctime = new_time;
-        self.write_inode(&parent_inode);
-        // 更新新文件夹的内容
-        new_parent_inode.children_ino.push(inode.ino);
-        // new_parent_inode.attr.mtime = new_time;
+        let new_time = SystemTime::now();
+        new_parent_inode.entries.insert_link(name, ino);
+        new_parent_inode.attr.mtime = new_time;
         new_parent_inode.attr.ctime = new_time;
-        self.write_inode(&new_parent_inode);
 
-        reply.ok();
+        let inode = match self.inodes.get_mut(&ino) {
+            Some(inode) => inode,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        inode.nlink += 1;
+        inode.attr.ctime = new_time;
+        reply.entry(&self.cache.entry_ttl, &inode.file_attr(), 0);
+    }
+
+    fn rename(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        #[cfg(target_os = "linux")] flags: u32,
+        #[cfg(not(target_os = "linux"))] _flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        debug!("[RFuseFS][rename] -> Rename a file.");
+        // 其他系统的 flags 取值与 Linux 不同, 不支持
+        #[cfg(not(target_os = "linux"))]
+        let flags = 0;
+        match self.rename_entry(req.into(), parent, name, newparent, newname, flags) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
     }
 
     fn write(
         &mut self,
         _req: &Request<'_>,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         data: &[u8],
         write_flags: u32,
@@ -721,50 +2645,14 @@ impl Filesystem for RFuseFS {
             "[RFuseFS][write] -> Write data to an open file. ino: {}",
             ino
         );
-        assert!(offset >= 0);
-
-        // fio 测试不要开这个，这个只能测
-        // debug!(
-        //     "[RFuseFS][write] -> Write data. ino: {}, offset: {}, data: {:?}",
-        //     ino,
-        //     offset,
-        //     String::from_utf8(data.to_vec()).unwrap()
-        // );
-
         debug!(
             "[RFuseFS][write] -> Write data. write_flags: {}, flags: {}, ino: {}",
             write_flags, flags, ino
         );
-
-        let inode = match self.inodes.get_mut(&ino) {
-            Some(inode) => {
-                debug!("write() -> Write data. {}", inode.attr.name.clone());
-                inode
-            }
-            None => {
-                reply.error(libc::ENOENT);
-                return;
-            }
-        };
-
-        let write_time = SystemTime::now();
-        match self
-            .remote_file_manager
-            .write_file(ino, data, &write_time, offset as u64)
-        {
-            Ok(_) => {}
-            Err(e) => {
-                debug!("[RFuseFS][write] -> Write data. {}", e);
-                reply.error(libc::EIO);
-                return;
-            }
-        };
-        inode.attr.mtime = write_time;
-        inode.attr.ctime = write_time;
-        if data.len() + offset as usize > inode.attr.size as usize {
-            inode.attr.size = (data.len() + offset as usize) as u64;
-        }
-        reply.written(data.len() as u32);
+        self.spawn_write(ino, fh, offset, data, move |result| match result {
+            Ok(written) => reply.written(written),
+            Err(errno) => reply.error(errno),
+        });
     }
 
     fn create(
@@ -772,167 +2660,222 @@ impl Filesystem for RFuseFS {
         req: &Request<'_>,
         parent: u64,
         name: &OsStr,
-        _mode: u32,
-        _umask: u32,
+        mode: u32,
+        umask: u32,
         flags: i32,
         reply: ReplyCreate,
     ) {
         info!("[RFuseFS][create] -> Create and open a file.");
-        let name = name.to_str().unwrap().to_string();
-        debug!("[RFuseFS][create] -> Create and open a file. {}", name);
-        if self.lookup_name(parent, &name).is_some() {
-            reply.error(libc::EEXIST);
-            return;
+        match self.create_file(req.into(), parent, name, mode, umask, flags) {
+            Ok((attr, fh)) => {
+                reply.created(&self.cache.entry_ttl, &attr, 0, fh, self.cache.open_flags())
+            }
+            Err(errno) => reply.error(errno),
         }
+    }
 
-        match flags & libc::O_ACCMODE {
-            libc::O_RDONLY => (true, false),
-            libc::O_WRONLY => (false, true),
-            libc::O_RDWR => (true, true),
-            _ => {
-                reply.error(libc::EINVAL);
-                return;
+    // 关闭文件前调用, 每次 close 都会触发
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        info!("[RFuseFS][flush] -> Flush an open file. ino: {}", ino);
+        match self.file_handle(ino, fh).map(|handle| handle.flush()) {
+            Some(Err(e)) => {
+                debug!("[RFuseFS][flush] -> Flush an open file. {}", e);
+                reply.error(e.errno());
             }
+            _ => reply.ok(),
         };
+    }
 
-        let parent_inode = self.inodes.get_mut(&parent).unwrap();
-
-        // 确认权限
-        if !check_access(
-            parent_inode.attr.uid,
-            parent_inode.attr.gid,
-            parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::W_OK,
-        ) {
-            reply.error(libc::EACCES);
-            return;
-        }
+    // 最后一次 close 后释放句柄
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        info!("[RFuseFS][release] -> Release an open file. ino: {}", ino);
+        self.release_handle(fh);
+        reply.ok();
+    }
 
-        parent_inode.attr.mtime = SystemTime::now();
-        assert!(parent_inode.is_dir());
-        let path = {
-            if parent_inode.attr.name.is_empty() {
-                parent_inode.attr.path.clone()
-            } else {
-                parent_inode.attr.path.clone() + &parent_inode.attr.name + "/"
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        info!(
+            "[RFuseFS][fsync] -> Synchronize file contents. ino: {}",
+            ino
+        );
+        match self
+            .file_handle(ino, fh)
+            .map(|handle| handle.fsync(datasync))
+        {
+            Some(Err(e)) => {
+                debug!("[RFuseFS][fsync] -> Synchronize file contents. {}", e);
+                reply.error(e.errno());
             }
+            _ => reply.ok(),
         };
-        // 这里的 attr 只是占位, 后续会被 RemoteFileManager 回写为真实数据
-        let attr = InodeAttributes::new(name.clone(), InodeKind::File, path.clone());
-        let mut new_inode = Inode::new(parent, attr.clone());
-        let new_ino = new_inode.ino;
-        let new_file_meta = match self.remote_file_manager.new_file(
-            new_ino,
-            attr.clone(),
-            self.source_dir.clone() + &attr.path,
-        ) {
-            Ok(meta) => meta,
-            Err(e) => {
-                debug!("[RFuseFS][create] -> Create and open a file. {}", e);
-                reply.error(libc::EIO);
+    }
+
+    fn opendir(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        info!("[RFuseFS][opendir] -> Open a directory. ino: {}", ino);
+        self.apply_completed_writes();
+        let access_mask = match flags & libc::O_ACCMODE {
+            libc::O_RDONLY => libc::R_OK,
+            libc::O_WRONLY => libc::W_OK,
+            _ => libc::R_OK | libc::W_OK,
+        };
+        match self.get_inode(ino) {
+            Some(inode) => {
+                if !inode.is_dir() {
+                    reply.error(libc::ENOTDIR);
+                    return;
+                }
+                if !check_access(
+                    inode.attr.uid,
+                    inode.attr.gid,
+                    inode.attr.permissions,
+                    req.uid(),
+                    req.gid(),
+                    access_mask,
+                ) {
+                    reply.error(libc::EACCES);
+                    return;
+                }
+            }
+            None => {
+                reply.error(ENOENT);
                 return;
             }
         };
-        // 这里直接回写了inode的信息
-        new_inode.parent_ino = parent;
-        new_inode.ino = new_file_meta.ino();
-        new_inode.attr = new_file_meta.attr;
-        new_inode.attr.path = path; // 注意这里的 path 应该是自己管理的地址而不是, 信息源的地址
-
-        parent_inode.insert_child(new_inode.ino);
-        self.inodes.insert(new_inode.ino, new_inode.clone());
-        reply.created(
-            &Duration::new(0, 0),
-            &new_inode.file_attr(),
-            0,
-            new_inode.ino,
-            0,
-        );
+        // 文件夹的读取都在 RFuseFS 的缓存中完成, 不需要后端句柄
+        let fh = self.insert_handle(ino, flags, None);
+        reply.opened(fh, 0);
     }
 
-    // 校验文件权限
-    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
-        info!("[RFuseFS][access] -> Check file access permissions.");
-        let inode = self.get_inode(ino).unwrap();
-        let mode = inode.attr.permissions;
-        if check_access(
-            inode.attr.uid,
-            inode.attr.gid,
-            mode,
-            req.uid(),
-            req.gid(),
-            mask,
-        ) {
-            reply.ok();
-        } else {
-            reply.error(libc::EACCES);
-        }
+    fn releasedir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        _flags: i32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        info!(
+            "[RFuseFS][releasedir] -> Release an open directory. ino: {}",
+            ino
+        );
+        self.release_handle(fh);
+        reply.ok();
     }
 
-    // 删除文件
-    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
-        info!("[RFuseFS][unlink] -> Remove a file.");
-        let name = name.to_str().unwrap().to_string();
-        let ino = match self.lookup_name(parent, &name) {
-            Some(ino) => ino,
+    // 查询文件系统的容量信息, 统一以挂载根目录所在的文件系统为准
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+        info!("[RFuseFS][statfs] -> Get file system statistics.");
+        let root_path = match self.get_inode(FUSE_ROOT_ID) {
+            Some(root) => origin_dir(&self.source_dir, &children_path(root)),
             None => {
-                reply.error(libc::ENOENT);
+                reply.error(ENOENT);
                 return;
             }
         };
-
-        let parent_inode = match self.inodes.get_mut(&parent) {
-            Some(ino) => ino,
-            None => {
-                reply.error(libc::ENOENT);
-                return;
+        if let Some(async_backend) = &self.async_backend {
+            let tf = TmpFile::new(OsString::new(), root_path);
+            let statfs = async_backend.backend.statfs(Arc::new(tf));
+            async_backend.runtime.spawn(async move {
+                let stat = statfs.await.unwrap_or_else(|e| {
+                    debug!("[RFuseFS][statfs] -> Get file system statistics. {}", e);
+                    StatFs::default()
+                });
+                reply_statfs(reply, stat);
+            });
+            return;
+        }
+        let stat = match self.remote_file_manager.statfs(root_path) {
+            Ok(stat) => stat,
+            Err(e) => {
+                // 后端不支持时返回默认值, 保证 df 等工具可以正常工作
+                debug!("[RFuseFS][statfs] -> Get file system statistics. {}", e);
+                StatFs::default()
             }
         };
+        reply_statfs(reply, stat);
+    }
 
-        // debug!(
-        //     "[RFuseFS][unlink] -> Remove a file. parent_inode: {:#?}",
-        //     parent_inode
-        // );
+    fn setxattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        flags: i32,
+        _position: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        info!("[RFuseFS][setxattr] -> Set an extended attribute.");
+        match self.set_xattr(req.into(), ino, name, value, flags) {
+            Ok(_) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
 
-        if !check_access(
-            parent_inode.attr.uid,
-            parent_inode.attr.gid,
-            parent_inode.attr.permissions,
-            req.uid(),
-            req.gid(),
-            libc::W_OK,
-        ) {
-            reply.error(libc::EACCES);
-            return;
+    fn getxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        info!("[RFuseFS][getxattr] -> Get an extended attribute.");
+        self.spawn_get_xattr(ino, name, move |value| reply_xattr(reply, size, value));
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        info!("[RFuseFS][listxattr] -> List extended attribute names.");
+        self.spawn_list_xattr(ino, move |names| reply_xattr(reply, size, names));
+    }
+
+    fn removexattr(&mut self, req: &Request<'_>, ino: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        info!("[RFuseFS][removexattr] -> Remove an extended attribute.");
+        match self.remove_xattr(req.into(), ino, name) {
+            Ok(_) => reply.ok(),
+            Err(errno) => reply.error(errno),
         }
+    }
 
-        let uid = req.uid();
-        // "Sticky bit" handling
-        if parent_inode.attr.permissions & RFUSE_S_ISVTX != 0
-            && uid != 0
-            && uid != parent_inode.attr.uid
-        // && uid != inode.attr.uid
-        {
-            reply.error(libc::EACCES);
-            return;
+    // 校验文件权限
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
+        info!("[RFuseFS][access] -> Check file access permissions.");
+        match self.check_permission(req.into(), ino, mask) {
+            Ok(_) => reply.ok(),
+            Err(errno) => reply.error(errno),
         }
+    }
 
-        let new_time = SystemTime::now();
-        parent_inode.remove_child(ino);
-        match self.remote_file_manager.remove_file(ino, &new_time) {
-            Ok(_) => {}
-            Err(e) => {
-                debug!("[RFuseFS][unlink] -> Remove a file. {}", e);
-                reply.error(libc::EIO);
-                return;
-            }
-        };
-        parent_inode.attr.mtime = new_time;
-        parent_inode.attr.ctime = new_time;
-        self.inodes.remove(&ino);
-        reply.ok();
+    // 删除文件
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        info!("[RFuseFS][unlink] -> Remove a file.");
+        match self.unlink_entry(req.into(), parent, name) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
     }
 
     fn destroy(&mut self) {
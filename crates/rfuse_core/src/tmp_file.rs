@@ -1,10 +1,20 @@
 use core::fmt;
-use std::{sync::RwLock, time::SystemTime};
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
 
 use log::warn;
 
-use crate::inode::{Inode, InodeAttributes};
+use crate::{
+    common::{BLOCK_SIZE, MAX_NAME_LENGTH, RFUSE_ENOATTR},
+    inode::{Inode, InodeAttributes},
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TmpFileError {
     ReadError,
     WriteError,
@@ -15,6 +25,31 @@ pub enum TmpFileError {
     MakeDirError,
     RemoveDirError,
     ChangeTimeError,
+    XattrNotFound,
+    XattrNotSupported,
+    XattrError,
+    // 源文件系统返回的系统错误, 保留原始的 errno, 例如 ENOSPC, EEXIST
+    Errno(i32),
+}
+
+impl TmpFileError {
+    /// 转换 io::Error, 有系统错误码时保留错误码, 否则 (例如读到文件末尾) 使用 fallback
+    pub fn from_io(e: &io::Error, fallback: TmpFileError) -> Self {
+        match e.raw_os_error() {
+            Some(errno) => TmpFileError::Errno(errno),
+            None => fallback,
+        }
+    }
+
+    /// 回复给内核的错误码
+    pub fn errno(&self) -> i32 {
+        match *self {
+            TmpFileError::Errno(errno) => errno,
+            TmpFileError::XattrNotFound => RFUSE_ENOATTR,
+            TmpFileError::XattrNotSupported => libc::ENOTSUP,
+            _ => libc::EIO,
+        }
+    }
 }
 
 impl fmt::Display for TmpFileError {
@@ -29,19 +64,111 @@ impl fmt::Display for TmpFileError {
             TmpFileError::MakeDirError => write!(f, "MakeDirError"),
             TmpFileError::RemoveDirError => write!(f, "RemoveDirError"),
             TmpFileError::ChangeTimeError => write!(f, "ChangeTimeError"),
+            TmpFileError::XattrNotFound => write!(f, "XattrNotFound"),
+            TmpFileError::XattrNotSupported => write!(f, "XattrNotSupported"),
+            TmpFileError::XattrError => write!(f, "XattrError"),
+            TmpFileError::Errno(errno) => {
+                write!(f, "Errno({})", io::Error::from_raw_os_error(errno))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TmpFileError {}
+
+/// 文件系统的容量信息, 对应 `statvfs` 的返回值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatFs {
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub bsize: u32,
+    pub namelen: u32,
+    pub frsize: u32,
+}
+
+impl Default for StatFs {
+    fn default() -> Self {
+        Self {
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: 0,
+            ffree: 0,
+            bsize: BLOCK_SIZE,
+            namelen: MAX_NAME_LENGTH,
+            frsize: BLOCK_SIZE,
         }
     }
 }
 
+/// 文件打开期间后端持有的句柄, 在 release 时被释放, 同一个句柄可能在多个工作线程中同时读写
+pub trait FileHandle: Send + Sync {
+    // 从句柄读取数据, 需要读满 buf
+    fn read_exact(&self, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError>;
+
+    // 通过句柄写入数据, 不会截断文件
+    fn write(&self, data: &[u8], write_time: &SystemTime, offset: u64) -> Result<(), TmpFileError>;
+
+    // 对应 close 时的 flush
+    fn flush(&self) -> Result<(), TmpFileError> {
+        Ok(())
+    }
+
+    // 将数据同步到存储, datasync 为 true 时只同步数据
+    fn fsync(&self, datasync: bool) -> Result<(), TmpFileError> {
+        let _ = datasync;
+        Ok(())
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct TmpFile {
-    pub file_name: String,
-    pub path: String,
-    pub lock: RwLock<()>,
+    pub file_name: OsString,
+    // 文件所在文件夹的完整路径
+    pub path: PathBuf,
+    // 同一个文件的读写锁, 改名之后仍然使用同一个锁
+    pub lock: Arc<RwLock<()>>,
+    // tmp_file_map 中的 ino, 为 0 时是只按路径使用的临时 TmpFile
+    pub ino: u64,
+}
+
+impl TmpFile {
+    pub fn new(file_name: OsString, path: PathBuf) -> Self {
+        Self {
+            file_name,
+            path,
+            lock: Arc::new(RwLock::new(())),
+            ino: 0,
+        }
+    }
+
+    pub fn with_ino(self, ino: u64) -> Self {
+        Self { ino, ..self }
+    }
+
+    /// 同一个文件的新名字, 和改名之前的 TmpFile 共用读写锁, 还在进行的读写结束之前新的读写需要等待
+    pub fn renamed(&self, file_name: OsString, path: PathBuf) -> Self {
+        Self {
+            file_name,
+            path,
+            lock: self.lock.clone(),
+            ino: self.ino,
+        }
+    }
+
+    // 文件的完整路径, file_name 为空时就是所在文件夹
+    pub fn full_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(&self.file_name);
+        PathBuf::from(path)
+    }
 }
 
-pub trait TmpFileTrait: Send {
+pub trait TmpFileTrait: Send + Sync {
     // 写入文件
     fn write(
         &self,
@@ -57,6 +184,30 @@ pub trait TmpFileTrait: Send {
         Err(TmpFileError::WriteError)
     }
 
+    // 打开文件, 返回 None 表示后端不持有句柄, 读写仍然按路径进行
+    fn open(&self, tf: &TmpFile, flags: i32) -> Result<Option<Box<dyn FileHandle>>, TmpFileError> {
+        let _ = (tf, flags);
+        Ok(None)
+    }
+
+    // 获取源文件的属性, 返回的 inode 使用后端的 ino, 软链接返回链接本身的属性
+    fn get_attr(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, get_attr()",
+            tf
+        );
+        Err(TmpFileError::ReadError)
+    }
+
+    // 读取文件夹下的所有文件
+    fn read_dir(&self, tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, read_dir()",
+            tf
+        );
+        Err(TmpFileError::ReadError)
+    }
+
     // 读取文件所有内容
     fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
         warn!(
@@ -75,6 +226,19 @@ pub trait TmpFileTrait: Send {
         Err(TmpFileError::ReadError)
     }
 
+    // 读取 inode 表中大小为 file_size 的文件, 缓存层用它限制预读的范围, 不需要再向后端查询文件大小
+    // 返回读取的字节数, 文件在这期间变短时可以少于 buf 的长度
+    fn read_sized(
+        &self,
+        tf: &TmpFile,
+        buf: &mut [u8],
+        offset: u64,
+        file_size: u64,
+    ) -> Result<usize, TmpFileError> {
+        let _ = file_size;
+        self.read_exact(tf, buf, offset).map(|_| buf.len())
+    }
+
     // 设置属性
     fn set_attr(&self, tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
         warn!(
@@ -84,25 +248,27 @@ pub trait TmpFileTrait: Send {
         Err(TmpFileError::SetAttrError)
     }
 
-    // 修改文件名
+    // 修改文件名, new_path 上已有的文件会被覆盖
+    // flags 为 RFUSE_RENAME_NOREPLACE 时不覆盖, 为 RFUSE_RENAME_EXCHANGE 时交换两个文件
     fn rename(
         &self,
         tf: &TmpFile,
-        new_path: String,
+        new_path: &Path,
         rename_time: &SystemTime,
+        flags: u32,
     ) -> Result<(), TmpFileError> {
         warn!(
-            "[TmpFileTrait][Not Implemented] node: {:#?}, rename(new_path: {:#?}, rename_time: {:#?})",
-            tf, new_path, rename_time
+            "[TmpFileTrait][Not Implemented] node: {:#?}, rename(new_path: {:#?}, rename_time: {:#?}, flags: {:#?})",
+            tf, new_path, rename_time, flags
         );
         Err(TmpFileError::RenameError)
     }
 
-    // 创建文件
-    fn create_file(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+    // 创建文件, mode 为已经去掉 umask 的权限
+    fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
         warn!(
-            "[TmpFileTrait][Not Implemented] node: {:#?}, create_file()",
-            tf
+            "[TmpFileTrait][Not Implemented] node: {:#?}, create_file(mode: {:#o})",
+            tf, mode
         );
         Err(TmpFileError::CreateError)
     }
@@ -125,6 +291,81 @@ pub trait TmpFileTrait: Send {
         Err(TmpFileError::MakeDirError)
     }
 
+    // 创建软链接, target 为链接指向的地址
+    fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, symlink(target: {:#?})",
+            tf, target
+        );
+        Err(TmpFileError::CreateError)
+    }
+
+    // 读取软链接指向的地址
+    fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, read_link()",
+            tf
+        );
+        Err(TmpFileError::ReadError)
+    }
+
+    // 创建硬链接, new_path 为新名字的完整路径
+    fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, link(new_path: {:#?})",
+            tf, new_path
+        );
+        Err(TmpFileError::CreateError)
+    }
+
+    // 读取扩展属性
+    fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, get_xattr(name: {:#?})",
+            tf, name
+        );
+        Err(TmpFileError::XattrNotSupported)
+    }
+
+    // 设置扩展属性, flags 为 XATTR_CREATE 或 XATTR_REPLACE
+    fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, set_xattr(name: {:#?}, value: {:#?}, flags: {:#?})",
+            tf, name, value, flags
+        );
+        Err(TmpFileError::XattrNotSupported)
+    }
+
+    // 列出扩展属性, 返回以 `\0` 分隔的属性名
+    fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, list_xattr()",
+            tf
+        );
+        Err(TmpFileError::XattrNotSupported)
+    }
+
+    // 删除扩展属性
+    fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        warn!(
+            "[TmpFileTrait][Not Implemented] node: {:#?}, remove_xattr(name: {:#?})",
+            tf, name
+        );
+        Err(TmpFileError::XattrNotSupported)
+    }
+
+    // 获取文件所在文件系统的容量信息
+    fn statfs(&self, tf: &TmpFile) -> Result<StatFs, TmpFileError> {
+        warn!("[TmpFileTrait][Not Implemented] node: {:#?}, statfs()", tf);
+        Err(TmpFileError::ReadError)
+    }
+
     // 删除目录
     fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
         warn!(
@@ -133,4 +374,9 @@ pub trait TmpFileTrait: Send {
         );
         Err(TmpFileError::RemoveDirError)
     }
+
+    // 源文件在 RFuseFS 之外被修改, 丢弃这个文件缓存的内容, 没有缓存的后端不需要处理
+    fn invalidate_cache(&self, tf: &TmpFile) {
+        let _ = tf;
+    }
 }
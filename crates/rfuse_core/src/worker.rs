@@ -0,0 +1,232 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+use log::{debug, error};
+
+use crate::{
+    async_tmp_file::AsyncTmpFileTrait,
+    tmp_file::{FileHandle, TmpFile, TmpFileError, TmpFileTrait},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// 交给异步后端的读写任务
+pub type TaskFuture<T> = Pin<Box<dyn Future<Output = Result<T, libc::c_int>> + Send + 'static>>;
+
+/// 执行文件读写的线程池, inode 表的修改仍然在 fuser 的请求线程中串行进行
+///
+/// fuser 只在一个线程中以 `&mut self` 分发请求, 所以 inode 表不分片也不加锁, 元数据请求按顺序执行,
+/// 把 inode 表改成读写锁或者分片不在线程池的范围内;
+/// 耗时的读写 (包括追加写) 都交给线程池, 同一个文件的读写由 TmpFile 中的读写锁保护
+///
+/// 释放时会等待已经提交的任务执行完, 保证每个请求都得到回复
+pub struct WorkerPool {
+    sender: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = (0..size)
+            .map(|index| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("rfuse-worker-{}", index))
+                    .spawn(move || loop {
+                        // 只在取任务时持有锁, 执行任务时其他线程可以继续取任务
+                        let job = match receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break, // 线程池已经被释放
+                        };
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            error!("[WorkerPool][execute] job panicked");
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            threads,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.threads.len()
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            if sender.send(Box::new(job)).is_err() {
+                error!("[WorkerPool][execute] all workers have exited");
+            }
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // 关闭队列后线程处理完剩下的任务就会退出
+        self.sender.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 读写文件的方式, 在请求线程中准备好之后可以交给其他线程执行
+#[derive(Clone)]
+pub enum FileIo {
+    // 通过 open 时后端返回的句柄
+    Handle(Arc<dyn FileHandle>),
+    // 按路径读写, 同一个文件共用 TmpFile 中的读写锁
+    Path(Arc<dyn TmpFileTrait>, Arc<TmpFile>),
+}
+
+impl FileIo {
+    // 返回读取的字节数
+    fn read(&self, buf: &mut [u8], offset: u64, file_size: u64) -> Result<usize, TmpFileError> {
+        match self {
+            FileIo::Handle(handle) => handle.read_exact(buf, offset).map(|_| buf.len()),
+            FileIo::Path(tmp_file_trait, tf) => {
+                tmp_file_trait.read_sized(tf, buf, offset, file_size)
+            }
+        }
+    }
+
+    fn write(&self, data: &[u8], write_time: &SystemTime, offset: u64) -> Result<(), TmpFileError> {
+        match self {
+            FileIo::Handle(handle) => handle.write(data, write_time, offset),
+            FileIo::Path(tmp_file_trait, tf) => tmp_file_trait.write(tf, data, write_time, offset),
+        }
+    }
+}
+
+/// 一次 read 请求, 读取的长度已经按文件大小截断
+pub struct ReadTask {
+    pub(crate) io: FileIo,
+    pub(crate) offset: u64,
+    pub(crate) size: usize,
+    pub(crate) file_size: u64, // inode 表中的文件大小
+}
+
+impl ReadTask {
+    pub fn run(self) -> Result<Vec<u8>, libc::c_int> {
+        let mut buf = vec![0; self.size];
+        match self.io.read(&mut buf, self.offset, self.file_size) {
+            Ok(len) => {
+                buf.truncate(len);
+                Ok(buf)
+            }
+            Err(e) => {
+                debug!("[RFuseFS][read] -> Read data. {}", e);
+                Err(e.errno())
+            }
+        }
+    }
+
+    /// 把读取交给异步后端, 有后端句柄时通过句柄读取, 否则按路径读取
+    pub fn into_async(self, backend: &Arc<dyn AsyncTmpFileTrait>) -> TaskFuture<Vec<u8>> {
+        let read = match self.io {
+            FileIo::Handle(handle) => backend.read_handle(handle, self.offset, self.size),
+            FileIo::Path(_, tf) => backend.read(tf, self.offset, self.size, self.file_size),
+        };
+        Box::pin(async move {
+            read.await.map_err(|e| {
+                debug!("[RFuseFS][read] -> Read data. {}", e);
+                e.errno()
+            })
+        })
+    }
+}
+
+// 写入完成后需要更新到 inode 表的大小和修改时间, 写入失败时只通知 ino
+pub(crate) struct WriteDone {
+    pub ino: u64,
+    pub end: u64,
+    pub size_gen: u64, // 任务创建时 RFuseFS 的截断计数, 之后又截断过时不更新大小
+    pub write_time: SystemTime,
+    pub ok: bool,
+}
+
+/// 一次 write 请求, 写入成功后先把新的大小通知给 RFuseFS 再返回,
+/// 所以内核收到回复之后发出的请求都能看到这次写入
+pub struct WriteTask {
+    pub(crate) io: FileIo,
+    pub(crate) ino: u64,
+    pub(crate) offset: u64,
+    pub(crate) data: Vec<u8>,
+    pub(crate) size_gen: u64,
+    pub(crate) done: Sender<WriteDone>,
+}
+
+impl WriteTask {
+    /// 返回写入的字节数
+    pub fn run(self) -> Result<u32, libc::c_int> {
+        let write_time = SystemTime::now();
+        match self.io.write(&self.data, &write_time, self.offset) {
+            Ok(_) => Ok(self.finish(write_time)),
+            Err(e) => {
+                debug!("[RFuseFS][write] -> Write data. {}", e);
+                Err(self.fail(e.errno()))
+            }
+        }
+    }
+
+    /// 把写入交给异步后端, 有后端句柄时通过句柄写入, 否则按路径写入
+    pub fn into_async(self, backend: &Arc<dyn AsyncTmpFileTrait>) -> TaskFuture<u32> {
+        let write_time = SystemTime::now();
+        let data = self.data.clone();
+        let write = match &self.io {
+            FileIo::Handle(handle) => {
+                backend.write_handle(handle.clone(), data, write_time, self.offset)
+            }
+            FileIo::Path(_, tf) => backend.write(tf.clone(), data, write_time, self.offset),
+        };
+        Box::pin(async move {
+            match write.await {
+                Ok(_) => Ok(self.finish(write_time)),
+                Err(e) => {
+                    debug!("[RFuseFS][write] -> Write data. {}", e);
+                    Err(self.fail(e.errno()))
+                }
+            }
+        })
+    }
+
+    // 通知 RFuseFS 更新文件大小, 返回写入的字节数
+    fn finish(&self, write_time: SystemTime) -> u32 {
+        // RFuseFS 已经被释放时不需要再更新
+        let _ = self.done.send(WriteDone {
+            ino: self.ino,
+            end: self.offset + self.data.len() as u64,
+            size_gen: self.size_gen,
+            write_time,
+            ok: true,
+        });
+        self.data.len() as u32
+    }
+
+    // 通知 RFuseFS 写入失败, 原样返回错误码
+    fn fail(&self, errno: libc::c_int) -> libc::c_int {
+        let _ = self.done.send(WriteDone {
+            ino: self.ino,
+            end: self.offset,
+            size_gen: self.size_gen,
+            write_time: SystemTime::now(),
+            ok: false,
+        });
+        errno
+    }
+}
@@ -1,19 +1,31 @@
 use log::{debug, error};
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+use nix::fcntl::{renameat2, RenameFlags};
 use nix::sys::{
-    stat::{fchmodat, utimensat, FchmodatFlags, Mode, UtimensatFlags},
+    stat::{fchmodat, futimens, utimensat, FchmodatFlags, Mode, UtimensatFlags},
+    statvfs::statvfs,
     time::TimeSpec,
 };
 use nix::unistd::{chown, truncate, Gid, Uid};
 use rfuse_core::tmp_file::TmpFile; // 这里的 TmpFile 不用来做锁定，只是用来传输基本的数据
 use rfuse_core::{
     inode::{Inode, InodeAttributes, InodeKind},
-    tmp_file::TmpFileError,
+    tmp_file::{StatFs, TmpFileError},
     utils::i64_to_system_time,
 };
 use std::{
+    ffi::{CStr, CString, OsString},
     fs,
-    io::{Read, Seek, SeekFrom, Write},
-    os::unix::fs::{FileExt, MetadataExt, PermissionsExt},
+    io::{self, Read},
+    os::{
+        fd::AsRawFd,
+        unix::{
+            ffi::OsStringExt,
+            fs::{self as unix_fs, lchown, FileExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+        },
+    },
+    path::{Path, PathBuf},
+    ptr,
     time::SystemTime,
 };
 
@@ -21,18 +33,137 @@ use crate::utils::system_time_to_timespec;
 
 // 这个文件主要是抽象对文件的操作, 方便在后续远程调用时使用同种接口
 
-fn change_time(path: &str, atime: &TimeSpec, mtime: &TimeSpec) -> Result<(), TmpFileError> {
-    // 将时间戳应用到文件
-    match utimensat(None, path, atime, mtime, UtimensatFlags::FollowSymlink) {
+fn change_time(path: &Path, atime: &TimeSpec, mtime: &TimeSpec) -> Result<(), TmpFileError> {
+    // 将时间戳应用到文件, 软链接修改的是链接本身的时间
+    change_time_with(path, atime, mtime, UtimensatFlags::NoFollowSymlink)
+}
+
+fn change_time_with(
+    path: &Path,
+    atime: &TimeSpec,
+    mtime: &TimeSpec,
+    flags: UtimensatFlags,
+) -> Result<(), TmpFileError> {
+    match utimensat(None, path, atime, mtime, flags) {
         Ok(_) => {
             debug!("Successfully set file attributes.");
             Ok(())
         }
         Err(e) => {
             error!("[LocalDisk][set_attr] Failed to set file attributes: {}", e);
-            Err(TmpFileError::ChangeTimeError)
+            Err(TmpFileError::Errno(e as i32))
+        }
+    }
+}
+
+// 根据文件的 meta 信息生成 inode, 这里所有的数据都是临时数据, 会在 RFuseFS 中被使用
+fn meta_to_inode(file_name: OsString, path: PathBuf, meta: &fs::Metadata) -> Inode {
+    let kind = if meta.is_dir() {
+        InodeKind::Directory
+    } else if meta.file_type().is_symlink() {
+        InodeKind::Symlink
+    } else {
+        InodeKind::File
+    };
+    let mut attr = InodeAttributes::new(file_name, kind, path);
+    attr.atime = i64_to_system_time(meta.atime());
+    attr.ctime = i64_to_system_time(meta.ctime());
+    attr.mtime = i64_to_system_time(meta.mtime());
+    attr.size = meta.size();
+    attr.permissions = meta.permissions().mode() as u16;
+    attr.uid = meta.uid();
+    attr.gid = meta.gid();
+
+    let mut inode = Inode::new(0, attr);
+    inode.ino = meta.ino();
+    inode
+}
+
+// 获取文件属性, 软链接返回链接本身的属性
+pub fn get_attr(tf: &TmpFile) -> Result<Inode, TmpFileError> {
+    match fs::symlink_metadata(tf.full_path()) {
+        // 挂载在源目录中的其他文件系统和 read_dir 一样不可见
+        Ok(meta) if !tf.file_name.is_empty() && !same_device(&tf.path, &meta) => {
+            debug!(
+                "[LocalDisk][get_attr] Skip mount point: {}",
+                tf.full_path().display()
+            );
+            Err(TmpFileError::Errno(libc::ENOENT))
+        }
+        Ok(meta) => Ok(meta_to_inode(tf.file_name.clone(), tf.path.clone(), &meta)),
+        // 源目录的改动事件到达时文件可能已经被删除, 这是正常情况
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("[LocalDisk][get_attr] File not found: {}", e);
+            Err(TmpFileError::Errno(libc::ENOENT))
+        }
+        Err(e) => {
+            error!("[LocalDisk][get_attr] Failed to get file meta: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::ReadError))
+        }
+    }
+}
+
+// 文件和所在的文件夹是否在同一个文件系统中, 文件夹不存在时交给后续的操作报错
+fn same_device(dir: &Path, meta: &fs::Metadata) -> bool {
+    fs::metadata(dir).map_or(true, |dir| dir.dev() == meta.dev())
+}
+
+// 读取文件夹下的所有文件
+pub fn read_dir(tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+    let dir_path = tf.full_path();
+    let mut children_path = dir_path.clone().into_os_string();
+    children_path.push("/");
+    let children_path = PathBuf::from(children_path);
+    let entries = match fs::read_dir(&dir_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("[LocalDisk][read_dir] Failed to read dir: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
+        }
+    };
+    // inode 号只在同一个文件系统中唯一, 所以不进入挂载在源目录中的其他文件系统
+    let dir_dev = match fs::metadata(&dir_path) {
+        Ok(meta) => meta.dev(),
+        Err(e) => {
+            error!("[LocalDisk][read_dir] Failed to get dir meta: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
+        }
+    };
+    let mut children = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("[LocalDisk][read_dir] Failed to read dir entry: {}", e);
+                return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
+            }
+        };
+        let meta = match entry.metadata() {
+            Ok(meta) => meta,
+            Err(e) => {
+                error!("[LocalDisk][read_dir] Failed to get file meta: {}", e);
+                return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
+            }
+        };
+        if meta.dev() != dir_dev {
+            debug!(
+                "[LocalDisk][read_dir] Skip mount point: {}",
+                entry.path().display()
+            );
+            continue;
         }
+        children.push(meta_to_inode(
+            entry.file_name(),
+            children_path.clone(),
+            &meta,
+        ));
     }
+    debug!(
+        "[LocalDisk][read_dir] Successfully read {} entries from {}",
+        children.len(),
+        dir_path.display()
+    );
+    Ok(children)
 }
 
 pub fn write(
@@ -41,46 +172,45 @@ pub fn write(
     write_time: &SystemTime,
     offset: u64,
 ) -> Result<(), TmpFileError> {
-    let mut file = fs::OpenOptions::new()
-        .append(false)
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(tf.path.clone() + tf.file_name.as_str())
-        .unwrap();
-    debug!("write to file: {}", tf.path.clone() + tf.file_name.as_str());
-    file.seek(SeekFrom::Start(offset)).unwrap();
-    match file.write_all(data) {
+    // 不截断文件, 截断只通过 set_attr 修改 size 实现
+    let file = match fs::OpenOptions::new().write(true).open(tf.full_path()) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("[LocalDisk][write] Failed to open file: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::WriteError));
+        }
+    };
+    debug!("write to file: {}", tf.full_path().display());
+    match file.write_all_at(data, offset) {
         Ok(()) => {
             debug!("Successfully write {} bytes to the file.", data.len());
         }
         Err(e) => {
             error!("[LocalDisk][write] Failed to write to file: {}", e);
-            return Err(TmpFileError::WriteError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::WriteError));
         }
     };
-    let _ = file.flush();
 
-    match change_time(
-        &(tf.path.clone() + tf.file_name.as_str()),
+    // 修改打开的文件本身的时间, 路径可能是 /proc/self/fd 下打开的文件
+    match futimens(
+        file.as_raw_fd(),
         &system_time_to_timespec(write_time),
         &system_time_to_timespec(write_time),
     ) {
-        Ok(_) => {}
-        Err(e) => return Err(e),
-    };
-    Ok(())
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("[LocalDisk][write] Failed to change time: {}", e);
+            Err(TmpFileError::Errno(e as i32))
+        }
+    }
 }
 
 pub fn read_all(tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
-    let mut file = match fs::OpenOptions::new()
-        .read(true)
-        .open(tf.path.clone() + tf.file_name.as_str())
-    {
+    let mut file = match fs::OpenOptions::new().read(true).open(tf.full_path()) {
         Ok(file) => file,
         Err(e) => {
             error!("[LocalDisk][read_all] Failed to open file: {}", e);
-            return Err(TmpFileError::ReadError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
         }
     };
     let mut buf: Vec<u8> = Vec::new();
@@ -90,21 +220,18 @@ pub fn read_all(tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
         }
         Err(e) => {
             error!("[LocalDisk][read_all] Failed to read from file: {}", e);
-            return Err(TmpFileError::ReadError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
         }
     };
     Ok(buf)
 }
 
 pub fn read_exact(tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
-    let file = match fs::OpenOptions::new()
-        .read(true)
-        .open(tf.path.clone() + tf.file_name.as_str())
-    {
+    let file = match fs::OpenOptions::new().read(true).open(tf.full_path()) {
         Ok(f) => f,
         Err(e) => {
             error!("[LocalDisk][read_exact] Failed to open file: {}", e);
-            return Err(TmpFileError::ReadError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
         }
     };
 
@@ -116,13 +243,109 @@ pub fn read_exact(tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFi
         }
         Err(e) => {
             error!("[LocalDisk][read_exact] Failed to read from file: {}", e);
-            Err(TmpFileError::ReadError)
+            Err(TmpFileError::from_io(&e, TmpFileError::ReadError))
+        }
+    }
+}
+
+// 按照 open 的访问模式打开原始文件, 句柄在 release 之前一直保持打开
+pub fn open(tf: &TmpFile, flags: i32) -> Result<fs::File, TmpFileError> {
+    let (read, write) = match flags & libc::O_ACCMODE {
+        libc::O_WRONLY => (false, true),
+        libc::O_RDWR => (true, true),
+        _ => (true, false),
+    };
+    // 不传递 O_APPEND, 写入统一使用 pwrite, 偏移由内核给出
+    match fs::OpenOptions::new()
+        .read(read)
+        .write(write)
+        .open(tf.full_path())
+    {
+        Ok(f) => {
+            debug!(
+                "[LocalDisk][open] Successfully open file: {:?}",
+                tf.file_name
+            );
+            Ok(f)
+        }
+        Err(e) => {
+            error!("[LocalDisk][open] Failed to open file: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::ReadError))
+        }
+    }
+}
+
+pub fn read_exact_at(file: &fs::File, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+    match file.read_exact_at(buf, offset) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            error!("[LocalDisk][read_exact_at] Failed to read from file: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::ReadError))
+        }
+    }
+}
+
+pub fn write_at(
+    file: &fs::File,
+    data: &[u8],
+    write_time: &SystemTime,
+    offset: u64,
+) -> Result<(), TmpFileError> {
+    match file.write_all_at(data, offset) {
+        Ok(()) => {}
+        Err(e) => {
+            error!("[LocalDisk][write_at] Failed to write to file: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::WriteError));
+        }
+    };
+    match futimens(
+        file.as_raw_fd(),
+        &system_time_to_timespec(write_time),
+        &system_time_to_timespec(write_time),
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("[LocalDisk][write_at] Failed to change time: {}", e);
+            Err(TmpFileError::Errno(e as i32))
+        }
+    }
+}
+
+pub fn fsync(file: &fs::File, datasync: bool) -> Result<(), TmpFileError> {
+    let result = if datasync {
+        file.sync_data()
+    } else {
+        file.sync_all()
+    };
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            error!("[LocalDisk][fsync] Failed to sync file: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::WriteError))
         }
     }
 }
 
 pub fn set_attr(tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
-    let full_path = tf.path.clone() + &tf.file_name;
+    let full_path = tf.full_path();
+
+    // 软链接的权限和大小没有意义, 只修改链接本身的用户和时间
+    if attr.kind == InodeKind::Symlink {
+        match lchown(&full_path, Some(attr.uid), Some(attr.gid)) {
+            Ok(_) => {
+                debug!("Successfully set symlink owner.");
+            }
+            Err(e) => {
+                error!("[LocalDisk][set_attr]Failed to set symlink owner: {}", e);
+                return Err(TmpFileError::from_io(&e, TmpFileError::SetAttrError));
+            }
+        };
+        return change_time(
+            &full_path,
+            &system_time_to_timespec(&attr.atime),
+            &system_time_to_timespec(&attr.mtime),
+        );
+    }
 
     #[cfg(target_os = "linux")]
     let permissions = attr.permissions as u32;
@@ -133,7 +356,7 @@ pub fn set_attr(tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError
     // 设置权限
     match fchmodat(
         None,
-        full_path.as_str(),
+        &full_path,
         Mode::from_bits_retain(permissions),
         FchmodatFlags::FollowSymlink,
     ) {
@@ -142,13 +365,13 @@ pub fn set_attr(tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError
         }
         Err(e) => {
             error!("[LocalDisk][set_attr]Failed to set file permissions: {}", e);
-            return Err(TmpFileError::SetAttrError);
+            return Err(TmpFileError::Errno(e as i32));
         }
     }
 
     // 设置用户和组
     match chown(
-        full_path.as_str(),
+        &full_path,
         Some(Uid::from_raw(attr.uid)),
         Some(Gid::from_raw(attr.gid)),
     ) {
@@ -157,61 +380,87 @@ pub fn set_attr(tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError
         }
         Err(e) => {
             error!("[LocalDisk][set_attr]Failed to set file owner: {}", e);
-            return Err(TmpFileError::SetAttrError);
+            return Err(TmpFileError::Errno(e as i32));
         }
     };
 
     // 设置文件大小
-    match truncate(full_path.as_str(), attr.size as i64) {
+    match truncate(&full_path, attr.size as i64) {
         Ok(_) => {
             debug!("Successfully set file size.");
         }
         Err(e) => {
             error!("[LocalDisk][set_attr]Failed to set file size: {}", e);
-            return Err(TmpFileError::SetAttrError);
+            return Err(TmpFileError::Errno(e as i32));
         }
     };
 
     let access_time = system_time_to_timespec(&attr.atime);
     let modification_time = system_time_to_timespec(&attr.mtime);
 
-    // 将时间戳应用到文件
-    match change_time(full_path.as_str(), &access_time, &modification_time) {
+    // 将时间戳应用到文件, 这里不是软链接, 和上面一样跟随路径, 路径可能是 /proc/self/fd 下打开的文件
+    match change_time_with(
+        &full_path,
+        &access_time,
+        &modification_time,
+        UtimensatFlags::FollowSymlink,
+    ) {
         Ok(_) => Ok(()),
         Err(e) => Err(e),
     }
 }
 
+// 不覆盖和交换两种模式需要 renameat2 保证原子性
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn rename_with_flags(from: &Path, to: &Path, flags: u32) -> Result<(), TmpFileError> {
+    if flags == 0 {
+        return fs::rename(from, to)
+            .map_err(|e| TmpFileError::from_io(&e, TmpFileError::RenameError));
+    }
+    renameat2(None, from, None, to, RenameFlags::from_bits_retain(flags))
+        .map_err(|e| TmpFileError::Errno(e as i32))
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn rename_with_flags(from: &Path, to: &Path, flags: u32) -> Result<(), TmpFileError> {
+    if flags != 0 {
+        return Err(TmpFileError::Errno(libc::EINVAL));
+    }
+    fs::rename(from, to).map_err(|e| TmpFileError::from_io(&e, TmpFileError::RenameError))
+}
+
 pub fn rename(
     tf: &TmpFile,
-    new_path: String,
+    new_path: &Path,
     rename_time: &SystemTime,
+    flags: u32,
 ) -> Result<(), TmpFileError> {
     debug!(
-        "[LocalDisk][rename] file from {} to {}",
-        tf.path.clone() + &tf.file_name,
-        new_path
+        "[LocalDisk][rename] file from {} to {}, flags: {}",
+        tf.full_path().display(),
+        new_path.display(),
+        flags
     );
-    // 重命名文件
-    match fs::rename(tf.path.clone() + &tf.file_name, &new_path) {
+    // 重命名文件, 目标已经存在时按照 flags 覆盖或者交换
+    match rename_with_flags(&tf.full_path(), new_path, flags) {
         Ok(_) => {}
         Err(e) => {
             error!("[LocalDisk][rename] Failed to rename file: {}", e);
-            return Err(TmpFileError::RenameError);
+            return Err(e);
         }
     };
 
-    let file_meta = match fs::metadata(&new_path) {
+    let file_meta = match fs::symlink_metadata(new_path) {
         Ok(file) => file,
         Err(e) => {
             error!("[LocalDisk][rename] Failed to get file meta: {}", e);
-            return Err(TmpFileError::ReadError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
         }
     };
 
     // 修改重命名后的文件时间
     match change_time(
-        new_path.as_str(),
+        new_path,
         &system_time_to_timespec(&i64_to_system_time(file_meta.atime())),
         &system_time_to_timespec(rename_time),
     ) {
@@ -223,7 +472,7 @@ pub fn rename(
 
     // 将时间戳应用到原地址的上层文件夹
     match change_time(
-        tf.path.as_str(),
+        &tf.path,
         &system_time_to_timespec(&i64_to_system_time(file_meta.atime())),
         &system_time_to_timespec(rename_time),
     ) {
@@ -232,43 +481,99 @@ pub fn rename(
     }
 }
 
-pub fn create_file(tf: &TmpFile) -> Result<Inode, TmpFileError> {
-    match fs::File::create(tf.path.clone() + &tf.file_name) {
-        Ok(f) => {
-            // 这里所有的数据都是临时数据, 会在 RFuseFS 中被使用
-            let mut attr =
-                InodeAttributes::new(tf.file_name.clone(), InodeKind::File, tf.path.clone());
+pub fn create_file(tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+    let file = match fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(tf.full_path())
+    {
+        Ok(f) => f,
+        Err(e) => {
+            error!("[LocalDisk][create_file] Failed to create file: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::CreateError));
+        }
+    };
+    // 服务端进程的 umask 会影响创建时的权限, 这里重新设置为请求的权限
+    match file.set_permissions(fs::Permissions::from_mode(mode)) {
+        Ok(_) => {}
+        Err(e) => {
+            error!("[LocalDisk][create_file] Failed to set permissions: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::CreateError));
+        }
+    };
+    match file.metadata() {
+        Ok(meta) => Ok(meta_to_inode(tf.file_name.clone(), tf.path.clone(), &meta)),
+        Err(e) => {
+            error!("[LocalDisk][create_file] Failed to get metadata: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::CreateError))
+        }
+    }
+}
 
-            let meta = f.metadata().unwrap();
-            attr.ctime = i64_to_system_time(meta.ctime());
-            attr.mtime = i64_to_system_time(meta.mtime());
-            attr.size = meta.size();
-            attr.permissions = meta.permissions().mode() as u16;
-            attr.uid = meta.uid();
-            attr.gid = meta.gid();
+pub fn symlink(tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+    let full_path = tf.full_path();
+    match unix_fs::symlink(target, &full_path) {
+        Ok(_) => {
+            debug!(
+                "[LocalDisk][symlink] Successfully create symlink. {} -> {}",
+                full_path.display(),
+                target.display()
+            );
+        }
+        Err(e) => {
+            error!("[LocalDisk][symlink] Failed to create symlink: {}", e);
+            return Err(TmpFileError::from_io(&e, TmpFileError::CreateError));
+        }
+    };
+    match fs::symlink_metadata(&full_path) {
+        Ok(meta) => Ok(meta_to_inode(tf.file_name.clone(), tf.path.clone(), &meta)),
+        Err(e) => {
+            error!("[LocalDisk][symlink] Failed to get metadata: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::CreateError))
+        }
+    }
+}
 
-            let mut inode = Inode::new(0, attr);
-            inode.ino = meta.ino();
-            Ok(inode)
+pub fn read_link(tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+    match fs::read_link(tf.full_path()) {
+        Ok(target) => Ok(target),
+        Err(e) => {
+            error!("[LocalDisk][read_link] Failed to read link: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::ReadError))
+        }
+    }
+}
+
+pub fn link(tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+    match fs::hard_link(tf.full_path(), new_path) {
+        Ok(_) => {
+            debug!(
+                "[LocalDisk][link] Successfully create hard link. {} -> {}",
+                new_path.display(),
+                tf.full_path().display()
+            );
+            Ok(())
         }
         Err(e) => {
-            error!("[LocalDisk][create_file] Failed to create file: {}", e);
-            Err(TmpFileError::CreateError)
+            error!("[LocalDisk][link] Failed to create hard link: {}", e);
+            Err(TmpFileError::from_io(&e, TmpFileError::CreateError))
         }
     }
 }
 
 pub fn remove_file(tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
-    match fs::remove_file(tf.path.clone() + &tf.file_name) {
+    match fs::remove_file(tf.full_path()) {
         Ok(_) => {
             debug!(
                 "[LocalDisk][remove_file] Successfully remove file. {}",
-                tf.path.clone() + &tf.file_name
+                tf.full_path().display()
             );
         }
         Err(e) => {
             error!("[LocalDisk][remove_file] Failed to remove file: {}", e);
-            return Err(TmpFileError::RemoveError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::RemoveError));
         }
     };
 
@@ -277,12 +582,12 @@ pub fn remove_file(tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFil
         Ok(file) => file,
         Err(e) => {
             error!("[LocalDisk][remove_file] Failed to get file meta: {}", e);
-            return Err(TmpFileError::ReadError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::ReadError));
         }
     };
 
     match change_time(
-        tf.path.as_str(),
+        &tf.path,
         &system_time_to_timespec(&i64_to_system_time(file_meta.atime())),
         &system_time_to_timespec(rm_file_time),
     ) {
@@ -292,7 +597,7 @@ pub fn remove_file(tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFil
 }
 
 pub fn make_dir(tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
-    let full_path = tf.path.clone() + &tf.file_name;
+    let full_path = tf.full_path();
 
     match fs::create_dir(&full_path) {
         Ok(_) => {
@@ -300,14 +605,14 @@ pub fn make_dir(tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
         }
         Err(e) => {
             error!("[LocalDisk][make_dir] Failed to make dir: {}", e);
-            return Err(TmpFileError::MakeDirError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::MakeDirError));
         }
     };
     let meta = match fs::metadata(&full_path) {
         Ok(meta) => meta,
         Err(e) => {
             error!("[LocalDisk][make_dir] Failed to get metadata: {}", e);
-            return Err(TmpFileError::MakeDirError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::MakeDirError));
         }
     };
     let mut perms = meta.permissions();
@@ -318,42 +623,54 @@ pub fn make_dir(tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
         }
         Err(e) => {
             error!("[LocalDisk][make_dir] Failed to set dir permissions: {}", e);
-            return Err(TmpFileError::MakeDirError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::MakeDirError));
         }
     };
     match fs::metadata(&full_path) {
-        Ok(meta) => {
-            // 这里所有的数据都是临时数据, 会在 RFuseFS 中被使用
-            let mut attr =
-                InodeAttributes::new(tf.file_name.clone(), InodeKind::Directory, tf.path.clone());
-            attr.ctime = i64_to_system_time(meta.ctime());
-            attr.mtime = i64_to_system_time(meta.mtime());
-            attr.size = meta.size();
-            attr.permissions = meta.permissions().mode() as u16;
-            attr.uid = meta.uid();
-            attr.gid = meta.gid();
-            let mut inode = Inode::new(0, attr);
-            inode.ino = meta.ino();
-            Ok(inode)
-        }
+        Ok(meta) => Ok(meta_to_inode(tf.file_name.clone(), tf.path.clone(), &meta)),
         Err(e) => {
             error!("[LocalDisk][make_dir] Failed to get metadata: {}", e);
-            Err(TmpFileError::MakeDirError)
+            Err(TmpFileError::from_io(&e, TmpFileError::MakeDirError))
+        }
+    }
+}
+
+// fsblkcnt_t 在 macos 上是 u32, 这里统一转换为 u64
+#[allow(clippy::useless_conversion)]
+pub fn statfs(tf: &TmpFile) -> Result<StatFs, TmpFileError> {
+    match statvfs(tf.full_path().as_path()) {
+        Ok(stat) => Ok(StatFs {
+            blocks: u64::from(stat.blocks()),
+            bfree: u64::from(stat.blocks_free()),
+            bavail: u64::from(stat.blocks_available()),
+            files: u64::from(stat.files()),
+            ffree: u64::from(stat.files_free()),
+            bsize: stat.block_size() as u32,
+            namelen: stat.name_max() as u32,
+            frsize: stat.fragment_size() as u32,
+        }),
+        Err(e) => {
+            error!(
+                "[LocalDisk][statfs] Failed to get file system statistics: {}",
+                e
+            );
+            Err(TmpFileError::Errno(e as i32))
         }
     }
 }
 
 pub fn remove_dir(tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
-    match fs::remove_dir_all(tf.path.clone() + &tf.file_name) {
+    // 不能递归删除, inode 表可能已经过期, 源目录中还有文件时返回 ENOTEMPTY
+    match fs::remove_dir(tf.full_path()) {
         Ok(_) => {
             debug!(
                 "[LocalDisk][remove_dir] Successfully remove dir. {}",
-                tf.path.clone() + &tf.file_name
+                tf.full_path().display()
             );
         }
         Err(e) => {
             error!("[LocalDisk][remove_dir] Failed to remove dir: {}", e);
-            return Err(TmpFileError::RemoveDirError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::RemoveDirError));
         }
     };
 
@@ -362,12 +679,12 @@ pub fn remove_dir(tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileE
         Ok(file) => file,
         Err(e) => {
             error!("[LocalDisk][remove_dir] Failed to get meta dir: {}", e);
-            return Err(TmpFileError::RemoveDirError);
+            return Err(TmpFileError::from_io(&e, TmpFileError::RemoveDirError));
         }
     };
 
     match change_time(
-        tf.path.as_str(),
+        &tf.path,
         &system_time_to_timespec(&i64_to_system_time(file_meta.atime())),
         &system_time_to_timespec(rm_dir_time),
     ) {
@@ -375,3 +692,138 @@ pub fn remove_dir(tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileE
         Err(e) => Err(e),
     }
 }
+
+// 扩展属性相关的系统调用, 都不跟随软链接
+
+fn c_string(s: impl Into<Vec<u8>>) -> Result<CString, TmpFileError> {
+    CString::new(s).map_err(|e| {
+        error!("[LocalDisk][xattr] Invalid string: {}", e);
+        TmpFileError::XattrError
+    })
+}
+
+fn xattr_result(ret: isize) -> io::Result<usize> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+fn buf_ptr(buf: &mut [u8]) -> *mut libc::c_void {
+    if buf.is_empty() {
+        ptr::null_mut()
+    } else {
+        buf.as_mut_ptr() as *mut libc::c_void
+    }
+}
+
+fn sys_getxattr(path: &CStr, name: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    let ret = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf_ptr(buf), buf.len()) };
+    #[cfg(target_os = "macos")]
+    let ret = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buf_ptr(buf),
+            buf.len(),
+            0,
+            libc::XATTR_NOFOLLOW,
+        )
+    };
+    xattr_result(ret)
+}
+
+fn sys_setxattr(path: &CStr, name: &CStr, value: &[u8], flags: i32) -> io::Result<usize> {
+    let value_ptr = value.as_ptr() as *const libc::c_void;
+    #[cfg(target_os = "linux")]
+    let ret =
+        unsafe { libc::lsetxattr(path.as_ptr(), name.as_ptr(), value_ptr, value.len(), flags) };
+    #[cfg(target_os = "macos")]
+    let ret = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value_ptr,
+            value.len(),
+            0,
+            flags | libc::XATTR_NOFOLLOW,
+        )
+    };
+    xattr_result(ret as isize)
+}
+
+fn sys_listxattr(path: &CStr, buf: &mut [u8]) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    let ret =
+        unsafe { libc::llistxattr(path.as_ptr(), buf_ptr(buf) as *mut libc::c_char, buf.len()) };
+    #[cfg(target_os = "macos")]
+    let ret = unsafe {
+        libc::listxattr(
+            path.as_ptr(),
+            buf_ptr(buf) as *mut libc::c_char,
+            buf.len(),
+            libc::XATTR_NOFOLLOW,
+        )
+    };
+    xattr_result(ret)
+}
+
+fn sys_removexattr(path: &CStr, name: &CStr) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    let ret = unsafe { libc::lremovexattr(path.as_ptr(), name.as_ptr()) };
+    #[cfg(target_os = "macos")]
+    let ret = unsafe { libc::removexattr(path.as_ptr(), name.as_ptr(), libc::XATTR_NOFOLLOW) };
+    xattr_result(ret as isize)
+}
+
+fn xattr_error(op: &str, e: io::Error) -> TmpFileError {
+    match e.raw_os_error() {
+        #[cfg(target_os = "linux")]
+        Some(libc::ENODATA) => TmpFileError::XattrNotFound,
+        #[cfg(target_os = "macos")]
+        Some(libc::ENOATTR) => TmpFileError::XattrNotFound,
+        Some(libc::ENOTSUP) => TmpFileError::XattrNotSupported,
+        _ => {
+            error!("[LocalDisk][{}] Failed: {}", op, e);
+            TmpFileError::from_io(&e, TmpFileError::XattrError)
+        }
+    }
+}
+
+pub fn get_xattr(tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+    let path = c_string(tf.full_path().into_os_string().into_vec())?;
+    let name = c_string(name)?;
+    // 先获取属性值的长度, 再读取内容
+    let size = sys_getxattr(&path, &name, &mut []).map_err(|e| xattr_error("get_xattr", e))?;
+    let mut buf = vec![0u8; size];
+    let size = sys_getxattr(&path, &name, &mut buf).map_err(|e| xattr_error("get_xattr", e))?;
+    buf.truncate(size);
+    Ok(buf)
+}
+
+pub fn set_xattr(tf: &TmpFile, name: &str, value: &[u8], flags: i32) -> Result<(), TmpFileError> {
+    let path = c_string(tf.full_path().into_os_string().into_vec())?;
+    let name = c_string(name)?;
+    sys_setxattr(&path, &name, value, flags).map_err(|e| xattr_error("set_xattr", e))?;
+    debug!("[LocalDisk][set_xattr] Successfully set xattr.");
+    Ok(())
+}
+
+pub fn list_xattr(tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+    let path = c_string(tf.full_path().into_os_string().into_vec())?;
+    let size = sys_listxattr(&path, &mut []).map_err(|e| xattr_error("list_xattr", e))?;
+    let mut buf = vec![0u8; size];
+    let size = sys_listxattr(&path, &mut buf).map_err(|e| xattr_error("list_xattr", e))?;
+    buf.truncate(size);
+    Ok(buf)
+}
+
+pub fn remove_xattr(tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+    let path = c_string(tf.full_path().into_os_string().into_vec())?;
+    let name = c_string(name)?;
+    sys_removexattr(&path, &name).map_err(|e| xattr_error("remove_xattr", e))?;
+    debug!("[LocalDisk][remove_xattr] Successfully remove xattr.");
+    Ok(())
+}
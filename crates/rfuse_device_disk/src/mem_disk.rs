@@ -1,3 +1,737 @@
-pub fn test() {
-    println!("test");
+use log::{debug, error, info};
+use rfuse_core::tmp_file::TmpFile; // 这里的 TmpFile 不用来做锁定，只是用来传输基本的数据
+use rfuse_core::{
+    common::{RFUSE_RENAME_EXCHANGE, RFUSE_RENAME_NOREPLACE},
+    dir_entries::DirEntries,
+    inode::{root_node, Inode, InodeAttributes, InodeKind},
+    remote_fs::{RemoteFileInitializeError, RemoteFileManager},
+    tmp_file::TmpFileError,
+};
+use std::{
+    cmp::min,
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use fuser::FUSE_ROOT_ID;
+
+// 这个文件主要是用内存模拟一块磁盘, 接口与 local_disk 保持一致
+
+#[derive(Clone, Debug)]
+pub struct MemNode {
+    pub ino: u64,
+    pub kind: InodeKind,
+    pub data: Vec<u8>,
+    pub atime: SystemTime,
+    pub mtime: SystemTime,
+    pub ctime: SystemTime,
+    pub permissions: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemNode {
+    fn new(ino: u64, kind: InodeKind, permissions: u16, uid: u32, gid: u32) -> Self {
+        let now = SystemTime::now();
+        Self {
+            ino,
+            kind,
+            data: Vec::new(),
+            atime: now,
+            mtime: now,
+            ctime: now,
+            permissions,
+            uid,
+            gid,
+            xattrs: BTreeMap::new(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        match self.kind {
+            InodeKind::Directory => rfuse_core::common::BLOCK_SIZE as u64,
+            // 软链接的大小为链接地址的长度
+            InodeKind::File | InodeKind::Symlink => self.data.len() as u64,
+        }
+    }
+
+    // 不使用 InodeAttributes::new, 避免每次都从全局分配器取一个 ino
+    fn attributes(&self, name: OsString, path: impl Into<OsString>) -> InodeAttributes {
+        InodeAttributes {
+            id: self.ino.to_string(),
+            size: self.size(),
+            name,
+            kind: self.kind,
+            path: path.into(),
+            atime: self.atime,
+            mtime: self.mtime,
+            ctime: self.ctime,
+            permissions: self.permissions,
+            uid: self.uid,
+            gid: self.gid,
+        }
+    }
+
+    fn to_inode(&self, name: OsString, path: OsString) -> Inode {
+        Inode {
+            ino: self.ino,
+            parent_ino: 0,
+            entries: DirEntries::default(),
+            nlink: rfuse_core::common::DEFAULT_HARD_LINKS,
+            loaded: true,
+            attr: self.attributes(name, path),
+        }
+    }
+}
+
+/// 内存磁盘, 所有节点以完整路径 (不带末尾的 `/`) 作为 key, 路径可以包含非 UTF-8 的字节
+#[derive(Debug)]
+pub struct MemDisk {
+    nodes: Mutex<HashMap<OsString, MemNode>>,
+    // 所有节点的数据一共可以使用的字节数
+    capacity: u64,
+    // 已经使用的字节数, 只在持有 nodes 的锁时修改
+    used: AtomicU64,
+    // 下一个节点的 ino, 每个内存磁盘单独分配
+    next_ino: AtomicU64,
+    root_permissions: u16,
+    uid: u32,
+    gid: u32,
+}
+
+// 去掉末尾的 `/`, 保证同一个文件只有一种 key
+fn node_key(path: &OsStr) -> OsString {
+    let mut key = path.as_bytes();
+    while let Some(rest) = key.strip_suffix(b"/") {
+        key = rest;
+    }
+    OsStr::from_bytes(key).to_os_string()
+}
+
+fn full_key(tf: &TmpFile) -> OsString {
+    node_key(tf.full_path().as_os_str())
+}
+
+fn parent_key(tf: &TmpFile) -> OsString {
+    node_key(tf.path.as_os_str())
+}
+
+// 文件夹下所有子孙节点的 key 的前缀
+fn children_prefix(key: &OsStr) -> OsString {
+    let mut prefix = key.to_os_string();
+    prefix.push("/");
+    prefix
+}
+
+fn has_prefix(key: &OsStr, prefix: &OsStr) -> bool {
+    key.as_bytes().starts_with(prefix.as_bytes())
+}
+
+impl MemDisk {
+    pub fn new(root_permissions: u16, uid: u32, gid: u32, capacity: u64) -> Self {
+        Self {
+            nodes: Mutex::new(HashMap::new()),
+            capacity,
+            used: AtomicU64::new(0),
+            next_ino: AtomicU64::new(FUSE_ROOT_ID + 1),
+            root_permissions,
+            uid,
+            gid,
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    fn alloc_ino(&self) -> u64 {
+        self.next_ino.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // 把节点的数据调整到 len 个字节, 超出容量时不修改节点
+    // 单个文件就超出容量时返回 EFBIG, 否则返回 ENOSPC
+    fn resize_data(&self, node: &mut MemNode, len: u64) -> Result<(), TmpFileError> {
+        let old = node.data.len() as u64;
+        if len > self.capacity {
+            return Err(TmpFileError::Errno(libc::EFBIG));
+        }
+        let used = self.used();
+        if len > old && used - old + len > self.capacity {
+            return Err(TmpFileError::Errno(libc::ENOSPC));
+        }
+        node.data.resize(len as usize, 0);
+        self.used.store(used - old + len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // 删除节点之后释放它的数据占用的容量
+    fn release(&self, node: Option<MemNode>) {
+        if let Some(node) = node {
+            self.used
+                .fetch_sub(node.data.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    // 更新上层文件夹的时间
+    fn touch_parent(nodes: &mut HashMap<OsString, MemNode>, tf: &TmpFile, time: &SystemTime) {
+        if let Some(parent) = nodes.get_mut(&parent_key(tf)) {
+            parent.mtime = *time;
+            parent.ctime = *time;
+        }
+    }
+
+    // 新建文件夹或者软链接之前检查: 名字不能已经存在, 上层文件夹必须存在
+    fn check_new_entry(
+        nodes: &HashMap<OsString, MemNode>,
+        tf: &TmpFile,
+        key: &OsStr,
+    ) -> Result<(), TmpFileError> {
+        if nodes.contains_key(key) {
+            return Err(TmpFileError::Errno(libc::EEXIST));
+        }
+        if !nodes.contains_key(&parent_key(tf)) {
+            return Err(TmpFileError::Errno(libc::ENOENT));
+        }
+        Ok(())
+    }
+
+    pub fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = match nodes.get_mut(&full_key(tf)) {
+            Some(n) if n.kind == InodeKind::File => n,
+            _ => {
+                error!("[MemDisk][write] file not found: {:?}", full_key(tf));
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        let end = match offset.checked_add(data.len() as u64) {
+            Some(end) => end,
+            None => return Err(TmpFileError::Errno(libc::EFBIG)),
+        };
+        // 稀疏写入, 中间的空洞用 0 填充
+        if (node.data.len() as u64) < end {
+            if let Err(e) = self.resize_data(node, end) {
+                error!(
+                    "[MemDisk][write] Failed to write {}..{}: {}",
+                    offset, end, e
+                );
+                return Err(e);
+            }
+        }
+        node.data[offset as usize..end as usize].copy_from_slice(data);
+        node.atime = *write_time;
+        node.mtime = *write_time;
+        node.ctime = *write_time;
+        debug!("Successfully write {} bytes to the file.", data.len());
+        Ok(())
+    }
+
+    pub fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(&full_key(tf)) {
+            Some(n) if n.kind == InodeKind::File => Ok(n.data.clone()),
+            _ => {
+                error!("[MemDisk][read_all] file not found: {:?}", full_key(tf));
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    // 与 pread(2) 一样返回读取的字节数, 读到文件末尾时少于 buf 的长度
+    pub fn read(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<usize, TmpFileError> {
+        let nodes = self.nodes.lock().unwrap();
+        let node = match nodes.get(&full_key(tf)) {
+            Some(n) if n.kind == InodeKind::File => n,
+            _ => {
+                error!("[MemDisk][read] file not found: {:?}", full_key(tf));
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        let len = node.data.len() as u64;
+        let start = min(offset, len) as usize;
+        let end = min(offset.saturating_add(buf.len() as u64), len) as usize;
+        buf[..end - start].copy_from_slice(&node.data[start..end]);
+        debug!("Successfully read {} bytes from the file.", end - start);
+        Ok(end - start)
+    }
+
+    pub fn set_attr(&self, tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = match nodes.get_mut(&full_key(tf)) {
+            Some(n) => n,
+            None => {
+                error!("[MemDisk][set_attr] file not found: {:?}", full_key(tf));
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        // 设置文件大小, 先检查容量, 失败时不修改其他属性
+        if node.kind == InodeKind::File {
+            if let Err(e) = self.resize_data(node, attr.size) {
+                error!(
+                    "[MemDisk][set_attr] Failed to resize to {}: {}",
+                    attr.size, e
+                );
+                return Err(e);
+            }
+        }
+        node.permissions = attr.permissions;
+        node.uid = attr.uid;
+        node.gid = attr.gid;
+        node.atime = attr.atime;
+        node.mtime = attr.mtime;
+        node.ctime = SystemTime::now();
+        Ok(())
+    }
+
+    // 取出节点以及文件夹下所有的子孙节点, 子孙节点的 key 只保留相对部分
+    fn take_subtree(
+        nodes: &mut HashMap<OsString, MemNode>,
+        key: &OsStr,
+    ) -> Option<(MemNode, Vec<(OsString, MemNode)>)> {
+        let node = nodes.remove(key)?;
+        let mut children = Vec::new();
+        if node.kind == InodeKind::Directory {
+            let prefix = children_prefix(key);
+            let keys: Vec<OsString> = nodes
+                .keys()
+                .filter(|k| has_prefix(k, &prefix))
+                .cloned()
+                .collect();
+            for k in keys {
+                let child = nodes.remove(&k).unwrap();
+                let rest = OsStr::from_bytes(&k.as_bytes()[prefix.len()..]).to_os_string();
+                children.push((rest, child));
+            }
+        }
+        Some((node, children))
+    }
+
+    fn put_subtree(
+        nodes: &mut HashMap<OsString, MemNode>,
+        key: OsString,
+        subtree: (MemNode, Vec<(OsString, MemNode)>),
+    ) {
+        let (node, children) = subtree;
+        let prefix = children_prefix(&key);
+        for (rest, child) in children {
+            let mut moved = prefix.clone();
+            moved.push(rest);
+            nodes.insert(moved, child);
+        }
+        nodes.insert(key, node);
+    }
+
+    pub fn rename(
+        &self,
+        tf: &TmpFile,
+        new_path: &Path,
+        rename_time: &SystemTime,
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
+        let old_key = full_key(tf);
+        let new_key = node_key(new_path.as_os_str());
+        debug!(
+            "[MemDisk][rename] file from {:?} to {:?}, flags: {}",
+            old_key, new_key, flags
+        );
+
+        let mut nodes = self.nodes.lock().unwrap();
+        let old_kind = match nodes.get(&old_key) {
+            Some(n) => n.kind,
+            None => {
+                error!("[MemDisk][rename] file not found: {:?}", old_key);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        if old_key == new_key {
+            return Ok(());
+        }
+        match Path::new(&new_key).parent() {
+            Some(parent) if nodes.contains_key(&node_key(parent.as_os_str())) => {}
+            _ => {
+                error!("[MemDisk][rename] parent not found: {:?}", new_key);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        }
+        // 文件夹不能移动到自己的子孙节点下
+        if has_prefix(&new_key, &children_prefix(&old_key)) {
+            return Err(TmpFileError::Errno(libc::EINVAL));
+        }
+
+        let target_kind = nodes.get(&new_key).map(|n| n.kind);
+        if flags & RFUSE_RENAME_EXCHANGE != 0 {
+            // 交换两个节点, 文件夹连同子孙节点一起交换
+            if target_kind.is_none() {
+                error!("[MemDisk][rename] exchange target not found: {:?}", new_key);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+            if has_prefix(&old_key, &children_prefix(&new_key)) {
+                return Err(TmpFileError::Errno(libc::EINVAL));
+            }
+            let mut source = Self::take_subtree(&mut nodes, &old_key).unwrap();
+            let mut target = Self::take_subtree(&mut nodes, &new_key).unwrap();
+            source.0.mtime = *rename_time;
+            target.0.mtime = *rename_time;
+            Self::put_subtree(&mut nodes, new_key, source);
+            Self::put_subtree(&mut nodes, old_key, target);
+            Self::touch_parent(&mut nodes, tf, rename_time);
+            return Ok(());
+        }
+
+        if let Some(kind) = target_kind {
+            if flags & RFUSE_RENAME_NOREPLACE != 0 {
+                return Err(TmpFileError::Errno(libc::EEXIST));
+            }
+            // 与 rename(2) 一致, 覆盖时两边的类型必须相同, 文件夹只能覆盖空文件夹
+            match (
+                old_kind == InodeKind::Directory,
+                kind == InodeKind::Directory,
+            ) {
+                (true, false) => return Err(TmpFileError::Errno(libc::ENOTDIR)),
+                (false, true) => return Err(TmpFileError::Errno(libc::EISDIR)),
+                (true, true) => {
+                    let prefix = children_prefix(&new_key);
+                    if nodes.keys().any(|k| has_prefix(k, &prefix)) {
+                        return Err(TmpFileError::Errno(libc::ENOTEMPTY));
+                    }
+                }
+                (false, false) => {}
+            }
+            self.release(nodes.remove(&new_key));
+        }
+
+        let mut source = Self::take_subtree(&mut nodes, &old_key).unwrap();
+        source.0.mtime = *rename_time;
+        Self::put_subtree(&mut nodes, new_key, source);
+
+        // 将时间戳应用到原地址的上层文件夹
+        Self::touch_parent(&mut nodes, tf, rename_time);
+        Ok(())
+    }
+
+    pub fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        if !nodes.contains_key(&parent_key(tf)) {
+            error!(
+                "[MemDisk][create_file] parent not found: {}",
+                tf.path.display()
+            );
+            return Err(TmpFileError::Errno(libc::ENOENT));
+        }
+
+        // 这里所有的数据都是临时数据, 会在 RFuseFS 中被使用
+        let node = MemNode::new(
+            self.alloc_ino(),
+            InodeKind::File,
+            mode as u16,
+            self.uid,
+            self.gid,
+        );
+        let inode = node.to_inode(tf.file_name.clone(), tf.path.clone().into_os_string());
+        // 与 fs::File::create 保持一致, 已存在的文件会被清空
+        self.release(nodes.insert(full_key(tf), node));
+        Self::touch_parent(&mut nodes, tf, &SystemTime::now());
+        Ok(inode)
+    }
+
+    pub fn remove_file(&self, tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get(&full_key(tf)) {
+            Some(n) if n.kind != InodeKind::Directory => {}
+            _ => {
+                error!("[MemDisk][remove_file] file not found: {:?}", full_key(tf));
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        self.release(nodes.remove(&full_key(tf)));
+        debug!(
+            "[MemDisk][remove_file] Successfully remove file. {:?}",
+            full_key(tf)
+        );
+
+        // 将时间戳应用到上层文件夹
+        Self::touch_parent(&mut nodes, tf, rm_file_time);
+        Ok(())
+    }
+
+    pub fn make_dir(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let key = full_key(tf);
+        if let Err(e) = Self::check_new_entry(&nodes, tf, &key) {
+            error!("[MemDisk][make_dir] Failed to make dir: {:?}, {}", key, e);
+            return Err(e);
+        }
+
+        let node = MemNode::new(
+            self.alloc_ino(),
+            InodeKind::Directory,
+            mode as u16,
+            self.uid,
+            self.gid,
+        );
+        let inode = node.to_inode(tf.file_name.clone(), tf.path.clone().into_os_string());
+        nodes.insert(key, node);
+        Self::touch_parent(&mut nodes, tf, &SystemTime::now());
+        debug!("[MemDisk][make_dir] Successfully make dir.");
+        Ok(inode)
+    }
+
+    pub fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let key = full_key(tf);
+        if let Err(e) = Self::check_new_entry(&nodes, tf, &key) {
+            error!(
+                "[MemDisk][symlink] Failed to create symlink: {:?}, {}",
+                key, e
+            );
+            return Err(e);
+        }
+
+        // 链接地址保存在 data 中
+        let mut node = MemNode::new(
+            self.alloc_ino(),
+            InodeKind::Symlink,
+            0o777,
+            self.uid,
+            self.gid,
+        );
+        let target = target.as_os_str().as_bytes();
+        if let Err(e) = self.resize_data(&mut node, target.len() as u64) {
+            error!(
+                "[MemDisk][symlink] Failed to create symlink: {:?}, {}",
+                key, e
+            );
+            return Err(e);
+        }
+        node.data.copy_from_slice(target);
+        let inode = node.to_inode(tf.file_name.clone(), tf.path.clone().into_os_string());
+        nodes.insert(key, node);
+        Self::touch_parent(&mut nodes, tf, &SystemTime::now());
+        debug!("[MemDisk][symlink] Successfully create symlink.");
+        Ok(inode)
+    }
+
+    // 节点的数据不共享, 不支持硬链接, 与不支持硬链接的文件系统上的 link(2) 一样返回 EPERM
+    pub fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        error!(
+            "[MemDisk][link] hard links are not supported: {:?} -> {}",
+            full_key(tf),
+            new_path.display()
+        );
+        Err(TmpFileError::Errno(libc::EPERM))
+    }
+
+    pub fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(&full_key(tf)) {
+            Some(n) if n.kind == InodeKind::Symlink => {
+                Ok(PathBuf::from(OsStr::from_bytes(&n.data)))
+            }
+            _ => {
+                error!("[MemDisk][read_link] symlink not found: {:?}", full_key(tf));
+                Err(TmpFileError::Errno(libc::EINVAL))
+            }
+        }
+    }
+
+    pub fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(&full_key(tf)) {
+            Some(n) => n
+                .xattrs
+                .get(name)
+                .cloned()
+                .ok_or(TmpFileError::XattrNotFound),
+            None => {
+                error!("[MemDisk][get_xattr] file not found: {:?}", full_key(tf));
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = match nodes.get_mut(&full_key(tf)) {
+            Some(n) => n,
+            None => {
+                error!("[MemDisk][set_xattr] file not found: {:?}", full_key(tf));
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        let exists = node.xattrs.contains_key(name);
+        if flags & libc::XATTR_CREATE != 0 && exists {
+            return Err(TmpFileError::Errno(libc::EEXIST));
+        }
+        if flags & libc::XATTR_REPLACE != 0 && !exists {
+            return Err(TmpFileError::XattrNotFound);
+        }
+        node.xattrs.insert(name.to_string(), value.to_vec());
+        node.ctime = SystemTime::now();
+        Ok(())
+    }
+
+    pub fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(&full_key(tf)) {
+            // 属性名以 `\0` 分隔
+            Some(n) => Ok(n
+                .xattrs
+                .keys()
+                .flat_map(|name| name.bytes().chain(std::iter::once(0)))
+                .collect()),
+            None => {
+                error!("[MemDisk][list_xattr] file not found: {:?}", full_key(tf));
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get_mut(&full_key(tf)) {
+            Some(n) => match n.xattrs.remove(name) {
+                Some(_) => {
+                    n.ctime = SystemTime::now();
+                    Ok(())
+                }
+                None => Err(TmpFileError::XattrNotFound),
+            },
+            None => {
+                error!("[MemDisk][remove_xattr] file not found: {:?}", full_key(tf));
+                Err(TmpFileError::Errno(libc::ENOENT))
+            }
+        }
+    }
+
+    pub fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let key = full_key(tf);
+        match nodes.get(&key) {
+            Some(n) if n.kind == InodeKind::Directory => {}
+            Some(_) => {
+                error!("[MemDisk][remove_dir] not a dir: {:?}", key);
+                return Err(TmpFileError::Errno(libc::ENOTDIR));
+            }
+            None => {
+                error!("[MemDisk][remove_dir] dir not found: {:?}", key);
+                return Err(TmpFileError::Errno(libc::ENOENT));
+            }
+        };
+        let prefix = children_prefix(&key);
+        if nodes.keys().any(|k| has_prefix(k, &prefix)) {
+            error!("[MemDisk][remove_dir] dir not empty: {:?}", key);
+            return Err(TmpFileError::Errno(libc::ENOTEMPTY));
+        }
+        nodes.remove(&key);
+        debug!("[MemDisk][remove_dir] Successfully remove dir. {:?}", key);
+
+        // 将时间戳应用到上层文件夹
+        Self::touch_parent(&mut nodes, tf, rm_dir_time);
+        Ok(())
+    }
+
+    /// 根据内存中的数据初始化 inode 表
+    ///
+    /// 内存磁盘会在重新挂载之间保留, 所以这里是用已有的节点重建整棵树
+    pub fn init_fs(
+        &self,
+        file_manager: &mut RemoteFileManager,
+        inodes: &mut HashMap<u64, Inode>,
+        source_dir: PathBuf,
+    ) -> Result<(), RemoteFileInitializeError> {
+        let root_key = node_key(source_dir.as_os_str());
+        let mut nodes = self.nodes.lock().unwrap();
+
+        // 第一次挂载时创建根节点
+        let root = nodes.entry(root_key.clone()).or_insert_with(|| {
+            MemNode::new(
+                FUSE_ROOT_ID,
+                InodeKind::Directory,
+                self.root_permissions,
+                self.uid,
+                self.gid,
+            )
+        });
+        let mut root_inode = root_node("", "/".to_string(), root.permissions, root.uid, root.gid);
+        root_inode.attr.atime = root.atime;
+        root_inode.attr.mtime = root.mtime;
+        root_inode.attr.ctime = root.ctime;
+        inodes.insert(FUSE_ROOT_ID, root_inode);
+
+        // 按路径深度排序, 保证父节点先于子节点插入
+        let prefix = children_prefix(&root_key);
+        let mut entries: Vec<(&OsString, &MemNode)> = nodes
+            .iter()
+            .filter(|(k, _)| has_prefix(k, &prefix))
+            .collect();
+        entries.sort_by_key(|(k, _)| k.as_bytes().iter().filter(|b| **b == b'/').count());
+
+        // 相对路径 (不带末尾的 `/`) -> ino
+        let mut dir_ino: HashMap<&[u8], u64> = HashMap::new();
+        dir_ino.insert(b"", FUSE_ROOT_ID);
+
+        for (key, node) in entries {
+            let relative = &key.as_bytes()[prefix.len()..];
+            let (parent_rel, name) = match relative.iter().rposition(|b| *b == b'/') {
+                Some(i) => (&relative[..i], &relative[i + 1..]),
+                None => (&b""[..], relative),
+            };
+            let name = OsStr::from_bytes(name);
+            let parent_ino = match dir_ino.get(parent_rel) {
+                Some(ino) => *ino,
+                None => {
+                    error!("[MemDisk][init_fs] parent not found: {:?}", key);
+                    return Err(RemoteFileInitializeError::Error);
+                }
+            };
+            let mut path = OsString::from("/");
+            if !parent_rel.is_empty() {
+                path.push(OsStr::from_bytes(parent_rel));
+                path.push("/");
+            }
+            debug!("mount_file: {:?}{:?}", path, name);
+
+            let mut inode = node.to_inode(name.to_os_string(), path.clone());
+            inode.parent_ino = parent_ino;
+            if node.kind == InodeKind::Directory {
+                dir_ino.insert(relative, node.ino);
+            }
+            inodes
+                .get_mut(&parent_ino)
+                .unwrap()
+                .entries
+                .insert_child(name, node.ino);
+            inodes.insert(node.ino, inode);
+            let mut origin_dir = source_dir.clone().into_os_string();
+            origin_dir.push(&path);
+            file_manager.add_file(node.ino, name.to_os_string(), PathBuf::from(origin_dir));
+        }
+
+        info!("File system init success.");
+        Ok(())
+    }
 }
@@ -0,0 +1,214 @@
+use core::fmt;
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, Read, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// 单帧的最大长度, 防止对端发送错误的长度导致分配过多内存
+pub const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+/// 单个响应中文件数据的最大长度, 更大的文件需要分段读取
+pub const MAX_DATA_SIZE: u32 = MAX_FRAME_SIZE / 2;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    UnexpectedEof,
+    FrameTooLarge(u32),
+    UnknownOp(u8),
+    InvalidUtf8,
+    VersionMismatch(u32),
+    UnexpectedResponse,
+    InvalidTime,
+    HandshakeRejected,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Io(e) => write!(f, "Io({})", e),
+            ProtocolError::UnexpectedEof => write!(f, "UnexpectedEof"),
+            ProtocolError::FrameTooLarge(len) => write!(f, "FrameTooLarge({})", len),
+            ProtocolError::UnknownOp(op) => write!(f, "UnknownOp({})", op),
+            ProtocolError::InvalidUtf8 => write!(f, "InvalidUtf8"),
+            ProtocolError::VersionMismatch(v) => write!(f, "VersionMismatch({})", v),
+            ProtocolError::UnexpectedResponse => write!(f, "UnexpectedResponse"),
+            ProtocolError::InvalidTime => write!(f, "InvalidTime"),
+            ProtocolError::HandshakeRejected => write!(f, "HandshakeRejected"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        ProtocolError::Io(e)
+    }
+}
+
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn put_u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_i64(&mut self, v: i64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_bytes(&mut self, v: &[u8]) {
+        self.put_u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn put_str(&mut self, v: &str) {
+        self.put_bytes(v.as_bytes());
+    }
+
+    // 路径和文件名按原始字节传输, 不要求是 UTF-8
+    pub fn put_os_str(&mut self, v: &OsStr) {
+        self.put_bytes(v.as_bytes());
+    }
+
+    // 时间按 (秒, 纳秒) 编码, 秒可以为负数
+    pub fn put_time(&mut self, v: &SystemTime) {
+        let (secs, nanos) = match v.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                if d.subsec_nanos() == 0 {
+                    (-(d.as_secs() as i64), 0)
+                } else {
+                    (-(d.as_secs() as i64) - 1, 1_000_000_000 - d.subsec_nanos())
+                }
+            }
+        };
+        self.put_i64(secs);
+        self.put_u32(nanos);
+    }
+}
+
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.buf.len() - self.pos < len {
+            return Err(ProtocolError::UnexpectedEof);
+        }
+        let v = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(v)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16, ProtocolError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, ProtocolError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64, ProtocolError> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_bytes(&mut self) -> Result<Vec<u8>, ProtocolError> {
+        let len = self.get_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn get_str(&mut self) -> Result<String, ProtocolError> {
+        String::from_utf8(self.get_bytes()?).map_err(|_| ProtocolError::InvalidUtf8)
+    }
+
+    pub fn get_os_string(&mut self) -> Result<OsString, ProtocolError> {
+        Ok(OsString::from_vec(self.get_bytes()?))
+    }
+
+    pub fn get_time(&mut self) -> Result<SystemTime, ProtocolError> {
+        let secs = self.get_i64()?;
+        let nanos = self.get_u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(ProtocolError::InvalidTime);
+        }
+        // 对端发来的时间可能超出 SystemTime 的范围, 溢出时返回错误而不是 panic
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|t| t.checked_add(Duration::from_nanos(nanos as u64)))
+        };
+        time.ok_or(ProtocolError::InvalidTime)
+    }
+}
+
+/// 写入一帧: 4 字节大端长度 + 负载
+pub fn write_frame<W: Write>(w: &mut W, payload: &[u8]) -> io::Result<()> {
+    w.write_all(&(payload.len() as u32).to_be_bytes())?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+/// 读取一帧, 对端正常关闭连接时返回 `None`
+pub fn read_frame<R: Read>(r: &mut R) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let mut len = [0u8; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = check_frame_len(u32::from_be_bytes(len))?;
+    let mut payload = vec![0u8; len];
+    r.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+pub fn check_frame_len(len: u32) -> Result<usize, ProtocolError> {
+    if len > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge(len));
+    }
+    Ok(len as usize)
+}
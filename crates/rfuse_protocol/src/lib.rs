@@ -0,0 +1,10 @@
+//! rfuse 的网络协议
+//!
+//! 每一帧由 4 字节大端长度和负载组成, 负载的第一个字段是 8 字节的请求 id,
+//! 服务端会在响应中原样返回, 之后是操作码和各操作自己的字段。
+
+pub mod codec;
+pub mod message;
+
+pub const PROTOCOL_VERSION: u32 = 4;
+pub const DEFAULT_PORT: u16 = 7878;
@@ -0,0 +1,766 @@
+use std::{ffi::OsString, path::PathBuf, time::SystemTime};
+
+use rfuse_core::{
+    dir_entries::DirEntries,
+    inode::{Inode, InodeAttributes, InodeKind},
+    tmp_file::{StatFs, TmpFileError},
+};
+
+use crate::codec::{Decoder, Encoder, ProtocolError};
+
+// 操作码
+const OP_HANDSHAKE: u8 = 0;
+const OP_GET_ATTR: u8 = 1;
+const OP_LOOKUP: u8 = 2;
+const OP_READ_DIR: u8 = 3;
+const OP_READ: u8 = 4;
+const OP_READ_ALL: u8 = 5;
+const OP_WRITE: u8 = 6;
+const OP_CREATE: u8 = 7;
+const OP_MAKE_DIR: u8 = 8;
+const OP_RENAME: u8 = 9;
+const OP_UNLINK: u8 = 10;
+const OP_REMOVE_DIR: u8 = 11;
+const OP_SET_ATTR: u8 = 12;
+const OP_SYMLINK: u8 = 13;
+const OP_READ_LINK: u8 = 14;
+const OP_LINK: u8 = 15;
+const OP_GET_XATTR: u8 = 16;
+const OP_SET_XATTR: u8 = 17;
+const OP_LIST_XATTR: u8 = 18;
+const OP_REMOVE_XATTR: u8 = 19;
+const OP_STATFS: u8 = 20;
+
+// 响应码
+const RE_HANDSHAKE: u8 = 0;
+const RE_OK: u8 = 1;
+const RE_ATTR: u8 = 2;
+const RE_ENTRIES: u8 = 3;
+const RE_DATA: u8 = 4;
+const RE_ERROR: u8 = 5;
+const RE_STATFS: u8 = 6;
+
+/// 远程文件的属性, 对应 [`InodeAttributes`] 中需要在网络上传输的部分
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteAttr {
+    pub ino: u64,
+    pub kind: InodeKind,
+    pub size: u64,
+    pub atime: SystemTime,
+    pub mtime: SystemTime,
+    pub ctime: SystemTime,
+    pub permissions: u16,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl RemoteAttr {
+    pub fn from_inode(inode: &Inode) -> Self {
+        let attr = &inode.attr;
+        Self {
+            ino: inode.ino,
+            kind: attr.kind,
+            size: attr.size,
+            atime: attr.atime,
+            mtime: attr.mtime,
+            ctime: attr.ctime,
+            permissions: attr.permissions,
+            uid: attr.uid,
+            gid: attr.gid,
+        }
+    }
+
+    pub fn from_attributes(ino: u64, attr: &InodeAttributes) -> Self {
+        Self {
+            ino,
+            kind: attr.kind,
+            size: attr.size,
+            atime: attr.atime,
+            mtime: attr.mtime,
+            ctime: attr.ctime,
+            permissions: attr.permissions,
+            uid: attr.uid,
+            gid: attr.gid,
+        }
+    }
+
+    pub fn to_attributes(&self, name: OsString, path: OsString) -> InodeAttributes {
+        let mut attr = InodeAttributes::new(name, self.kind, path);
+        attr.size = self.size;
+        attr.atime = self.atime;
+        attr.mtime = self.mtime;
+        attr.ctime = self.ctime;
+        attr.permissions = self.permissions;
+        attr.uid = self.uid;
+        attr.gid = self.gid;
+        attr
+    }
+
+    pub fn to_inode(&self, parent_ino: u64, name: OsString, path: OsString) -> Inode {
+        Inode {
+            ino: self.ino,
+            parent_ino,
+            entries: DirEntries::default(),
+            nlink: rfuse_core::common::DEFAULT_HARD_LINKS,
+            // 文件夹的子文件在第一次访问时读取
+            loaded: self.kind != InodeKind::Directory,
+            attr: self.to_attributes(name, path),
+        }
+    }
+
+    fn encode(&self, e: &mut Encoder) {
+        e.put_u64(self.ino);
+        e.put_u8(match self.kind {
+            InodeKind::File => 0,
+            InodeKind::Directory => 1,
+            InodeKind::Symlink => 2,
+        });
+        e.put_u64(self.size);
+        e.put_time(&self.atime);
+        e.put_time(&self.mtime);
+        e.put_time(&self.ctime);
+        e.put_u16(self.permissions);
+        e.put_u32(self.uid);
+        e.put_u32(self.gid);
+    }
+
+    fn decode(d: &mut Decoder) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            ino: d.get_u64()?,
+            kind: match d.get_u8()? {
+                0 => InodeKind::File,
+                2 => InodeKind::Symlink,
+                _ => InodeKind::Directory,
+            },
+            size: d.get_u64()?,
+            atime: d.get_time()?,
+            mtime: d.get_time()?,
+            ctime: d.get_time()?,
+            permissions: d.get_u16()?,
+            uid: d.get_u32()?,
+            gid: d.get_u32()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: OsString,
+    pub attr: RemoteAttr,
+}
+
+/// 客户端发送的请求, 所有路径都是相对于服务端共享目录的路径, 以 `/` 开头
+///
+/// 路径和文件名可以包含任意字节, 只有扩展属性名要求是 UTF-8
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// 连接建立后的第一个请求, 服务端配置了 token 时必须一致
+    Handshake {
+        version: u32,
+        token: String,
+    },
+    GetAttr {
+        path: PathBuf,
+    },
+    Lookup {
+        parent: PathBuf,
+        name: OsString,
+    },
+    ReadDir {
+        path: PathBuf,
+    },
+    Read {
+        path: PathBuf,
+        offset: u64,
+        size: u32,
+    },
+    ReadAll {
+        path: PathBuf,
+    },
+    Write {
+        path: PathBuf,
+        offset: u64,
+        data: Vec<u8>,
+        write_time: SystemTime,
+    },
+    Create {
+        path: PathBuf,
+        mode: u32,
+    },
+    MakeDir {
+        path: PathBuf,
+        mode: u32,
+    },
+    Rename {
+        path: PathBuf,
+        new_path: PathBuf,
+        rename_time: SystemTime,
+        // RENAME_NOREPLACE 或 RENAME_EXCHANGE
+        flags: u32,
+    },
+    Unlink {
+        path: PathBuf,
+        rm_file_time: SystemTime,
+    },
+    RemoveDir {
+        path: PathBuf,
+        rm_dir_time: SystemTime,
+    },
+    SetAttr {
+        path: PathBuf,
+        attr: RemoteAttr,
+    },
+    Symlink {
+        path: PathBuf,
+        target: PathBuf,
+    },
+    ReadLink {
+        path: PathBuf,
+    },
+    Link {
+        path: PathBuf,
+        new_path: PathBuf,
+    },
+    GetXattr {
+        path: PathBuf,
+        name: String,
+    },
+    SetXattr {
+        path: PathBuf,
+        name: String,
+        value: Vec<u8>,
+        flags: i32,
+    },
+    ListXattr {
+        path: PathBuf,
+    },
+    RemoveXattr {
+        path: PathBuf,
+        name: String,
+    },
+    StatFs {
+        path: PathBuf,
+    },
+}
+
+/// 服务端返回的响应
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    Handshake { version: u32 },
+    Ok,
+    Attr(RemoteAttr),
+    Entries(Vec<DirEntry>),
+    Data(Vec<u8>),
+    StatFs(StatFs),
+    Error(TmpFileError),
+}
+
+fn error_code(e: &TmpFileError) -> u8 {
+    match e {
+        TmpFileError::ReadError => 0,
+        TmpFileError::WriteError => 1,
+        TmpFileError::RenameError => 2,
+        TmpFileError::RemoveError => 3,
+        TmpFileError::CreateError => 4,
+        TmpFileError::SetAttrError => 5,
+        TmpFileError::MakeDirError => 6,
+        TmpFileError::RemoveDirError => 7,
+        TmpFileError::ChangeTimeError => 8,
+        TmpFileError::XattrNotFound => 9,
+        TmpFileError::XattrNotSupported => 10,
+        TmpFileError::XattrError => 11,
+        TmpFileError::Errno(_) => 12,
+    }
+}
+
+// 系统错误码跟在错误类型后面
+fn put_error(e: &mut Encoder, err: &TmpFileError) {
+    e.put_u8(error_code(err));
+    if let TmpFileError::Errno(errno) = err {
+        e.put_u32(*errno as u32);
+    }
+}
+
+fn get_error(d: &mut Decoder) -> Result<TmpFileError, ProtocolError> {
+    Ok(match d.get_u8()? {
+        12 => TmpFileError::Errno(d.get_u32()? as i32),
+        code => error_from_code(code),
+    })
+}
+
+fn error_from_code(code: u8) -> TmpFileError {
+    match code {
+        0 => TmpFileError::ReadError,
+        1 => TmpFileError::WriteError,
+        2 => TmpFileError::RenameError,
+        3 => TmpFileError::RemoveError,
+        4 => TmpFileError::CreateError,
+        5 => TmpFileError::SetAttrError,
+        6 => TmpFileError::MakeDirError,
+        7 => TmpFileError::RemoveDirError,
+        9 => TmpFileError::XattrNotFound,
+        10 => TmpFileError::XattrNotSupported,
+        11 => TmpFileError::XattrError,
+        _ => TmpFileError::ChangeTimeError,
+    }
+}
+
+impl Request {
+    pub fn encode(&self, id: u64) -> Vec<u8> {
+        let mut e = Encoder::new();
+        e.put_u64(id);
+        match self {
+            Request::Handshake { version, token } => {
+                e.put_u8(OP_HANDSHAKE);
+                e.put_u32(*version);
+                e.put_str(token);
+            }
+            Request::GetAttr { path } => {
+                e.put_u8(OP_GET_ATTR);
+                e.put_os_str(path.as_os_str());
+            }
+            Request::Lookup { parent, name } => {
+                e.put_u8(OP_LOOKUP);
+                e.put_os_str(parent.as_os_str());
+                e.put_os_str(name);
+            }
+            Request::ReadDir { path } => {
+                e.put_u8(OP_READ_DIR);
+                e.put_os_str(path.as_os_str());
+            }
+            Request::Read { path, offset, size } => {
+                e.put_u8(OP_READ);
+                e.put_os_str(path.as_os_str());
+                e.put_u64(*offset);
+                e.put_u32(*size);
+            }
+            Request::ReadAll { path } => {
+                e.put_u8(OP_READ_ALL);
+                e.put_os_str(path.as_os_str());
+            }
+            Request::Write {
+                path,
+                offset,
+                data,
+                write_time,
+            } => {
+                e.put_u8(OP_WRITE);
+                e.put_os_str(path.as_os_str());
+                e.put_u64(*offset);
+                e.put_bytes(data);
+                e.put_time(write_time);
+            }
+            Request::Create { path, mode } => {
+                e.put_u8(OP_CREATE);
+                e.put_os_str(path.as_os_str());
+                e.put_u32(*mode);
+            }
+            Request::MakeDir { path, mode } => {
+                e.put_u8(OP_MAKE_DIR);
+                e.put_os_str(path.as_os_str());
+                e.put_u32(*mode);
+            }
+            Request::Rename {
+                path,
+                new_path,
+                rename_time,
+                flags,
+            } => {
+                e.put_u8(OP_RENAME);
+                e.put_os_str(path.as_os_str());
+                e.put_os_str(new_path.as_os_str());
+                e.put_time(rename_time);
+                e.put_u32(*flags);
+            }
+            Request::Unlink { path, rm_file_time } => {
+                e.put_u8(OP_UNLINK);
+                e.put_os_str(path.as_os_str());
+                e.put_time(rm_file_time);
+            }
+            Request::RemoveDir { path, rm_dir_time } => {
+                e.put_u8(OP_REMOVE_DIR);
+                e.put_os_str(path.as_os_str());
+                e.put_time(rm_dir_time);
+            }
+            Request::SetAttr { path, attr } => {
+                e.put_u8(OP_SET_ATTR);
+                e.put_os_str(path.as_os_str());
+                attr.encode(&mut e);
+            }
+            Request::Symlink { path, target } => {
+                e.put_u8(OP_SYMLINK);
+                e.put_os_str(path.as_os_str());
+                e.put_os_str(target.as_os_str());
+            }
+            Request::ReadLink { path } => {
+                e.put_u8(OP_READ_LINK);
+                e.put_os_str(path.as_os_str());
+            }
+            Request::Link { path, new_path } => {
+                e.put_u8(OP_LINK);
+                e.put_os_str(path.as_os_str());
+                e.put_os_str(new_path.as_os_str());
+            }
+            Request::GetXattr { path, name } => {
+                e.put_u8(OP_GET_XATTR);
+                e.put_os_str(path.as_os_str());
+                e.put_str(name);
+            }
+            Request::SetXattr {
+                path,
+                name,
+                value,
+                flags,
+            } => {
+                e.put_u8(OP_SET_XATTR);
+                e.put_os_str(path.as_os_str());
+                e.put_str(name);
+                e.put_bytes(value);
+                e.put_u32(*flags as u32);
+            }
+            Request::ListXattr { path } => {
+                e.put_u8(OP_LIST_XATTR);
+                e.put_os_str(path.as_os_str());
+            }
+            Request::RemoveXattr { path, name } => {
+                e.put_u8(OP_REMOVE_XATTR);
+                e.put_os_str(path.as_os_str());
+                e.put_str(name);
+            }
+            Request::StatFs { path } => {
+                e.put_u8(OP_STATFS);
+                e.put_os_str(path.as_os_str());
+            }
+        }
+        e.finish()
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<(u64, Self), ProtocolError> {
+        let mut d = Decoder::new(buf);
+        let id = d.get_u64()?;
+        let request = match d.get_u8()? {
+            OP_HANDSHAKE => Request::Handshake {
+                version: d.get_u32()?,
+                token: d.get_str()?,
+            },
+            OP_GET_ATTR => Request::GetAttr {
+                path: d.get_os_string()?.into(),
+            },
+            OP_LOOKUP => Request::Lookup {
+                parent: d.get_os_string()?.into(),
+                name: d.get_os_string()?,
+            },
+            OP_READ_DIR => Request::ReadDir {
+                path: d.get_os_string()?.into(),
+            },
+            OP_READ => Request::Read {
+                path: d.get_os_string()?.into(),
+                offset: d.get_u64()?,
+                size: d.get_u32()?,
+            },
+            OP_READ_ALL => Request::ReadAll {
+                path: d.get_os_string()?.into(),
+            },
+            OP_WRITE => Request::Write {
+                path: d.get_os_string()?.into(),
+                offset: d.get_u64()?,
+                data: d.get_bytes()?,
+                write_time: d.get_time()?,
+            },
+            OP_CREATE => Request::Create {
+                path: d.get_os_string()?.into(),
+                mode: d.get_u32()?,
+            },
+            OP_MAKE_DIR => Request::MakeDir {
+                path: d.get_os_string()?.into(),
+                mode: d.get_u32()?,
+            },
+            OP_RENAME => Request::Rename {
+                path: d.get_os_string()?.into(),
+                new_path: d.get_os_string()?.into(),
+                rename_time: d.get_time()?,
+                flags: d.get_u32()?,
+            },
+            OP_UNLINK => Request::Unlink {
+                path: d.get_os_string()?.into(),
+                rm_file_time: d.get_time()?,
+            },
+            OP_REMOVE_DIR => Request::RemoveDir {
+                path: d.get_os_string()?.into(),
+                rm_dir_time: d.get_time()?,
+            },
+            OP_SET_ATTR => Request::SetAttr {
+                path: d.get_os_string()?.into(),
+                attr: RemoteAttr::decode(&mut d)?,
+            },
+            OP_SYMLINK => Request::Symlink {
+                path: d.get_os_string()?.into(),
+                target: d.get_os_string()?.into(),
+            },
+            OP_READ_LINK => Request::ReadLink {
+                path: d.get_os_string()?.into(),
+            },
+            OP_LINK => Request::Link {
+                path: d.get_os_string()?.into(),
+                new_path: d.get_os_string()?.into(),
+            },
+            OP_GET_XATTR => Request::GetXattr {
+                path: d.get_os_string()?.into(),
+                name: d.get_str()?,
+            },
+            OP_SET_XATTR => Request::SetXattr {
+                path: d.get_os_string()?.into(),
+                name: d.get_str()?,
+                value: d.get_bytes()?,
+                flags: d.get_u32()? as i32,
+            },
+            OP_LIST_XATTR => Request::ListXattr {
+                path: d.get_os_string()?.into(),
+            },
+            OP_REMOVE_XATTR => Request::RemoveXattr {
+                path: d.get_os_string()?.into(),
+                name: d.get_str()?,
+            },
+            OP_STATFS => Request::StatFs {
+                path: d.get_os_string()?.into(),
+            },
+            op => return Err(ProtocolError::UnknownOp(op)),
+        };
+        Ok((id, request))
+    }
+}
+
+impl Response {
+    pub fn encode(&self, id: u64) -> Vec<u8> {
+        let mut e = Encoder::new();
+        e.put_u64(id);
+        match self {
+            Response::Handshake { version } => {
+                e.put_u8(RE_HANDSHAKE);
+                e.put_u32(*version);
+            }
+            Response::Ok => e.put_u8(RE_OK),
+            Response::Attr(attr) => {
+                e.put_u8(RE_ATTR);
+                attr.encode(&mut e);
+            }
+            Response::Entries(entries) => {
+                e.put_u8(RE_ENTRIES);
+                e.put_u32(entries.len() as u32);
+                for entry in entries {
+                    e.put_os_str(&entry.name);
+                    entry.attr.encode(&mut e);
+                }
+            }
+            Response::Data(data) => {
+                e.put_u8(RE_DATA);
+                e.put_bytes(data);
+            }
+            Response::StatFs(stat) => {
+                e.put_u8(RE_STATFS);
+                e.put_u64(stat.blocks);
+                e.put_u64(stat.bfree);
+                e.put_u64(stat.bavail);
+                e.put_u64(stat.files);
+                e.put_u64(stat.ffree);
+                e.put_u32(stat.bsize);
+                e.put_u32(stat.namelen);
+                e.put_u32(stat.frsize);
+            }
+            Response::Error(err) => {
+                e.put_u8(RE_ERROR);
+                put_error(&mut e, err);
+            }
+        }
+        e.finish()
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<(u64, Self), ProtocolError> {
+        let mut d = Decoder::new(buf);
+        let id = d.get_u64()?;
+        let response = match d.get_u8()? {
+            RE_HANDSHAKE => Response::Handshake {
+                version: d.get_u32()?,
+            },
+            RE_OK => Response::Ok,
+            RE_ATTR => Response::Attr(RemoteAttr::decode(&mut d)?),
+            RE_ENTRIES => {
+                let len = d.get_u32()?;
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    entries.push(DirEntry {
+                        name: d.get_os_string()?,
+                        attr: RemoteAttr::decode(&mut d)?,
+                    });
+                }
+                Response::Entries(entries)
+            }
+            RE_DATA => Response::Data(d.get_bytes()?),
+            RE_STATFS => Response::StatFs(StatFs {
+                blocks: d.get_u64()?,
+                bfree: d.get_u64()?,
+                bavail: d.get_u64()?,
+                files: d.get_u64()?,
+                ffree: d.get_u64()?,
+                bsize: d.get_u32()?,
+                namelen: d.get_u32()?,
+                frsize: d.get_u32()?,
+            }),
+            RE_ERROR => Response::Error(get_error(&mut d)?),
+            op => return Err(ProtocolError::UnknownOp(op)),
+        };
+        Ok((id, response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsStr,
+        os::unix::ffi::OsStrExt,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use rfuse_core::{
+        inode::InodeKind,
+        tmp_file::{StatFs, TmpFileError},
+    };
+
+    use crate::{
+        codec::{Decoder, Encoder, ProtocolError},
+        message::{DirEntry, RemoteAttr, Request, Response},
+    };
+
+    fn attr() -> RemoteAttr {
+        RemoteAttr {
+            ino: 42,
+            kind: InodeKind::File,
+            size: 13,
+            atime: UNIX_EPOCH + Duration::new(1_700_000_000, 123),
+            mtime: UNIX_EPOCH - Duration::new(10, 5),
+            ctime: UNIX_EPOCH,
+            permissions: 0o644,
+            uid: 1000,
+            gid: 1000,
+        }
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let requests = vec![
+            Request::Handshake {
+                version: 1,
+                token: "secret".into(),
+            },
+            Request::Lookup {
+                parent: "/a/".into(),
+                name: "b.txt".into(),
+            },
+            Request::Write {
+                path: "/a/b.txt".into(),
+                offset: 7,
+                data: b"Hello, World!".to_vec(),
+                write_time: UNIX_EPOCH + Duration::new(5, 6),
+            },
+            Request::SetAttr {
+                path: "/a/b.txt".into(),
+                attr: attr(),
+            },
+            Request::Symlink {
+                path: "/a/link".into(),
+                target: "../b.txt".into(),
+            },
+            Request::Rename {
+                path: "/a/b.txt".into(),
+                new_path: "/c.txt".into(),
+                rename_time: UNIX_EPOCH + Duration::new(7, 8),
+                flags: 2,
+            },
+            Request::SetXattr {
+                path: "/a/b.txt".into(),
+                name: "user.rfuse".into(),
+                value: b"value".to_vec(),
+                flags: 1,
+            },
+        ];
+        for (id, request) in requests.into_iter().enumerate() {
+            let buf = request.encode(id as u64);
+            assert_eq!(Request::decode(&buf).unwrap(), (id as u64, request));
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let responses = vec![
+            Response::Ok,
+            Response::Attr(attr()),
+            Response::Entries(vec![DirEntry {
+                name: "b.txt".into(),
+                attr: attr(),
+            }]),
+            Response::Data(vec![1, 2, 3]),
+            Response::StatFs(StatFs {
+                blocks: 1024,
+                bfree: 512,
+                bavail: 256,
+                files: 100,
+                ffree: 50,
+                ..StatFs::default()
+            }),
+            Response::Error(TmpFileError::RenameError),
+            // ENOSPC
+            Response::Error(TmpFileError::Errno(28)),
+        ];
+        for (id, response) in responses.into_iter().enumerate() {
+            let buf = response.encode(id as u64);
+            assert_eq!(Response::decode(&buf).unwrap(), (id as u64, response));
+        }
+    }
+
+    // 路径和文件名按原始字节传输, 非 UTF-8 的名字也可以往返
+    #[test]
+    fn non_utf8_names() {
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        let request = Request::Lookup {
+            parent: "/a/".into(),
+            name: name.to_os_string(),
+        };
+        let buf = request.encode(1);
+        assert_eq!(Request::decode(&buf).unwrap(), (1, request));
+
+        let response = Response::Entries(vec![DirEntry {
+            name: name.to_os_string(),
+            attr: attr(),
+        }]);
+        let buf = response.encode(2);
+        assert_eq!(Response::decode(&buf).unwrap(), (2, response));
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let buf = Request::ReadAll { path: "/a".into() }.encode(1);
+        assert!(Request::decode(&buf[..buf.len() - 1]).is_err());
+    }
+
+    // 超出范围的时间返回错误, 而不是在解码时 panic
+    #[test]
+    fn invalid_time() {
+        for (secs, nanos) in [(i64::MAX, u32::MAX), (i64::MIN, 1_000_000_000)] {
+            let mut e = Encoder::new();
+            e.put_i64(secs);
+            e.put_u32(nanos);
+            let buf = e.finish();
+            assert!(matches!(
+                Decoder::new(&buf).get_time(),
+                Err(ProtocolError::InvalidTime)
+            ));
+        }
+
+        // 最小的秒数也可以解码
+        let mut e = Encoder::new();
+        e.put_i64(i64::MIN);
+        e.put_u32(0);
+        let buf = e.finish();
+        assert!(Decoder::new(&buf).get_time().is_ok());
+    }
+}
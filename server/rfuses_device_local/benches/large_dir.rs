@@ -0,0 +1,82 @@
+mod base_fn;
+
+use std::{ffi::OsString, path::PathBuf, sync::Arc};
+
+use base_fn::self_criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkId, Criterion,
+};
+use fuser::FUSE_ROOT_ID;
+use rfuse_core::sys_fs::{CacheOptions, Caller, RFuseFS};
+use rfuse_device_disk::mem_disk::MemDisk;
+use rfuses_device_local::{init_fs::mem_defined_init_fs, mem_fs::MemFS};
+
+// 文件夹中的文件数量
+const DIR_SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+const ROOT: Caller = Caller { uid: 0, gid: 0 };
+
+fn file_name(index: usize) -> OsString {
+    OsString::from(format!("file_{}.txt", index))
+}
+
+// 不挂载, 在内存磁盘上创建一个有 size 个文件的根目录
+fn large_dir(size: usize) -> RFuseFS {
+    let disk = Arc::new(MemDisk::new(0o755, 0, 0, u64::MAX));
+    let mut rfs = RFuseFS::new(
+        "rfuse".to_string(),
+        CacheOptions::default(),
+        true,
+        PathBuf::new(),
+        mem_defined_init_fs(disk.clone()),
+        Box::new(MemFS(disk)),
+    );
+    rfs.re_init_fs();
+    rfs.ensure_loaded(FUSE_ROOT_ID).unwrap();
+    for index in 0..size {
+        rfs.create_file(ROOT, FUSE_ROOT_ID, &file_name(index), 0o644, 0o022, 0)
+            .unwrap();
+    }
+    rfs
+}
+
+fn benchmark_large_dir(c: &mut Criterion<WallTime>) {
+    let mut group = c.benchmark_group("large_dir");
+
+    for size in DIR_SIZES {
+        let mut rfs = large_dir(size);
+
+        // 按名字查找, 每次查找不同的文件
+        let mut index = 0;
+        group.bench_function(BenchmarkId::new("lookup", size), |b| {
+            b.iter(|| {
+                index = (index + 7919) % size;
+                rfs.lookup_entry(ROOT, FUSE_ROOT_ID, &file_name(index))
+                    .unwrap();
+            });
+        });
+
+        // 从文件夹中间继续读取一页目录项
+        let middle = rfs.read_dir_entries(FUSE_ROOT_ID, 0).unwrap()[size / 2].0;
+        group.bench_function(BenchmarkId::new("readdir_page", size), |b| {
+            b.iter(|| {
+                let entries = rfs.read_dir_entries(FUSE_ROOT_ID, middle).unwrap();
+                assert!(!entries.is_empty());
+            });
+        });
+
+        // 在已经很大的文件夹中继续创建文件, 创建之前需要检查名字是否已经存在
+        let mut next = size;
+        group.bench_function(BenchmarkId::new("create", size), |b| {
+            b.iter(|| {
+                rfs.create_file(ROOT, FUSE_ROOT_ID, &file_name(next), 0o644, 0o022, 0)
+                    .unwrap();
+                next += 1;
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(large_dir_operation, benchmark_large_dir);
+criterion_main!(large_dir_operation);
@@ -1,8 +1,10 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use clap::{command, Parser};
 use rfuse_device_disk::DiskType;
 
+use rfuse_core::sys_fs::CacheOptions;
+
 use crate::logging::LogLevel;
 
 #[derive(Debug, Parser)]
@@ -23,6 +25,7 @@ pub struct Args {
 #[derive(Debug, clap::Subcommand)]
 pub enum Command {
     Link(LinkCommand),
+    Serve(ServeCommand),
 }
 
 #[derive(Parser, Debug)]
@@ -40,8 +43,40 @@ pub struct LinkCommand {
     #[clap(default_value = "rfuses", help = "Set the name of the source in mtab.")]
     pub fs_name: String,
 
+    #[clap(long, help = "Disable extended attributes")]
+    pub no_xattr: bool,
+
+    #[clap(
+        long,
+        default_value_t = 4,
+        value_name = "N",
+        help = "Worker threads for file reads and writes, 0 disables the pool"
+    )]
+    pub threads: usize,
+
     #[clap(flatten)]
     pub disk_type: DiskTypeArgs,
+
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, author, about, long_about = None, about = "Serve a folder to rfuse clients over TCP.")]
+pub struct ServeCommand {
+    #[clap(help = "Origin file address [default: .]")]
+    pub origin: PathBuf,
+
+    #[clap(
+        short,
+        long,
+        default_value = "127.0.0.1:7878",
+        help = "Address to listen on"
+    )]
+    pub bind: String,
+
+    #[clap(long, help = "Shared secret that clients must send in the handshake")]
+    pub token: Option<String>,
 }
 
 #[derive(Debug, clap::Args)]
@@ -65,13 +100,21 @@ pub struct DiskTypeArgs {
         help = "Use memory as disk"
     )]
     pub mem: bool,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        value_name = "MiB",
+        requires = "mem",
+        help_heading = "Disk types",
+        help = "Capacity of the memory disk"
+    )]
+    pub mem_size: u64,
 }
 
 impl From<&DiskTypeArgs> for DiskType {
     fn from(args: &DiskTypeArgs) -> Self {
-        if args.local {
-            Self::Local
-        } else if args.mem {
+        // `local` 默认为 true, 所以需要先判断 `mem`
+        if args.mem {
             Self::Mem
         } else {
             Self::Local
@@ -79,6 +122,77 @@ impl From<&DiskTypeArgs> for DiskType {
     }
 }
 
+#[derive(Debug, clap::Args)]
+pub struct CacheArgs {
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may cache file attributes"
+    )]
+    pub attr_ttl: Duration,
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may cache directory entries"
+    )]
+    pub entry_ttl: Duration,
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "SECONDS",
+        value_parser = parse_ttl,
+        help_heading = "Kernel cache",
+        help = "How long the kernel may remember that a name does not exist"
+    )]
+    pub negative_ttl: Duration,
+    #[arg(
+        long,
+        help_heading = "Kernel cache",
+        help = "Read and write through the kernel page cache"
+    )]
+    pub no_direct_io: bool,
+    #[arg(
+        long,
+        requires = "no_direct_io",
+        help_heading = "Kernel cache",
+        help = "Keep cached file pages when a file is opened again"
+    )]
+    pub keep_cache: bool,
+    #[arg(
+        long,
+        help_heading = "Kernel cache",
+        help = "Buffer writes in the kernel and flush them in batches"
+    )]
+    pub writeback_cache: bool,
+}
+
+// TTL 以秒为单位, 可以是小数
+fn parse_ttl(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("invalid TTL `{}`, expected seconds >= 0", s)),
+    }
+}
+
+impl From<&CacheArgs> for CacheOptions {
+    fn from(args: &CacheArgs) -> Self {
+        Self {
+            attr_ttl: args.attr_ttl,
+            entry_ttl: args.entry_ttl,
+            negative_ttl: args.negative_ttl,
+            direct_io: !args.no_direct_io,
+            keep_cache: args.keep_cache,
+            writeback_cache: args.writeback_cache,
+        }
+    }
+}
+
 #[derive(Debug, clap::Args)]
 pub struct LogLevelArgs {
     /// Enable verbose logging.
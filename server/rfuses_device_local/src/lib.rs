@@ -4,8 +4,10 @@ pub mod cli;
 pub mod init_fs;
 pub mod local_fs;
 pub mod logging;
+pub mod mem_fs;
 pub mod notify_loop;
 pub mod run;
+pub mod serve;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum ExitStatus {
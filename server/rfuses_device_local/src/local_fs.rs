@@ -1,13 +1,34 @@
 use rfuse_device_disk::local_disk;
-use std::time::SystemTime;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use rfuse_core::{
     inode::{Inode, InodeAttributes},
-    tmp_file::{TmpFile, TmpFileError, TmpFileTrait},
+    tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait},
 };
 
 pub struct LocalFS;
 
+// 打开的原始文件, drop 时关闭
+pub struct LocalFileHandle(File);
+
+impl FileHandle for LocalFileHandle {
+    fn read_exact(&self, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        local_disk::read_exact_at(&self.0, buf, offset)
+    }
+
+    fn write(&self, data: &[u8], write_time: &SystemTime, offset: u64) -> Result<(), TmpFileError> {
+        local_disk::write_at(&self.0, data, write_time, offset)
+    }
+
+    fn fsync(&self, datasync: bool) -> Result<(), TmpFileError> {
+        local_disk::fsync(&self.0, datasync)
+    }
+}
+
 impl TmpFileTrait for LocalFS {
     fn write(
         &self,
@@ -20,6 +41,22 @@ impl TmpFileTrait for LocalFS {
         local_disk::write(tf, data, write_time, offset)
     }
 
+    fn open(&self, tf: &TmpFile, flags: i32) -> Result<Option<Box<dyn FileHandle>>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        let file = local_disk::open(tf, flags)?;
+        Ok(Some(Box::new(LocalFileHandle(file))))
+    }
+
+    fn get_attr(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::get_attr(tf)
+    }
+
+    fn read_dir(&self, tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::read_dir(tf)
+    }
+
     fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
         let _guard = tf.lock.read().unwrap();
         local_disk::read_all(tf)
@@ -38,16 +75,17 @@ impl TmpFileTrait for LocalFS {
     fn rename(
         &self,
         tf: &TmpFile,
-        new_path: String,
+        new_path: &Path,
         rename_time: &SystemTime,
+        flags: u32,
     ) -> Result<(), TmpFileError> {
         let _guard = tf.lock.write().unwrap();
-        local_disk::rename(tf, new_path, rename_time)
+        local_disk::rename(tf, new_path, rename_time, flags)
     }
 
-    fn create_file(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+    fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
         let _guard = tf.lock.write().unwrap();
-        local_disk::create_file(tf)
+        local_disk::create_file(tf, mode)
     }
 
     fn remove_file(&self, tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
@@ -60,6 +98,52 @@ impl TmpFileTrait for LocalFS {
         local_disk::make_dir(tf, mode)
     }
 
+    fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        local_disk::symlink(tf, target)
+    }
+
+    fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::read_link(tf)
+    }
+
+    fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        local_disk::link(tf, new_path)
+    }
+
+    fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::get_xattr(tf, name)
+    }
+
+    fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        local_disk::set_xattr(tf, name, value, flags)
+    }
+
+    fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::list_xattr(tf)
+    }
+
+    fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        local_disk::remove_xattr(tf, name)
+    }
+
+    fn statfs(&self, tf: &TmpFile) -> Result<StatFs, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        local_disk::statfs(tf)
+    }
+
     fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
         let _guard = tf.lock.write().unwrap();
         local_disk::remove_dir(tf, rm_dir_time)
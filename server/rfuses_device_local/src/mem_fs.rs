@@ -0,0 +1,129 @@
+use rfuse_device_disk::mem_disk::MemDisk;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use rfuse_core::{
+    inode::{Inode, InodeAttributes},
+    tmp_file::{TmpFile, TmpFileError, TmpFileTrait},
+};
+
+// 内存磁盘需要在重新挂载之间保留数据, 所以这里持有的是共享的 MemDisk
+pub struct MemFS(pub Arc<MemDisk>);
+
+impl TmpFileTrait for MemFS {
+    fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.write(tf, data, write_time, offset)
+    }
+
+    fn read_all(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.read_all(tf)
+    }
+
+    fn read_exact(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        match self.0.read(tf, buf, offset)? {
+            len if len == buf.len() => Ok(()),
+            _ => Err(TmpFileError::ReadError),
+        }
+    }
+
+    // 读到文件末尾时返回较短的数据, 而不是错误
+    fn read_sized(
+        &self,
+        tf: &TmpFile,
+        buf: &mut [u8],
+        offset: u64,
+        _file_size: u64,
+    ) -> Result<usize, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.read(tf, buf, offset)
+    }
+
+    fn set_attr(&self, tf: &TmpFile, attr: &InodeAttributes) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.set_attr(tf, attr)
+    }
+
+    fn rename(
+        &self,
+        tf: &TmpFile,
+        new_path: &Path,
+        rename_time: &SystemTime,
+        flags: u32,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.rename(tf, new_path, rename_time, flags)
+    }
+
+    fn create_file(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.create_file(tf, mode)
+    }
+
+    fn remove_file(&self, tf: &TmpFile, rm_file_time: &SystemTime) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.remove_file(tf, rm_file_time)
+    }
+
+    fn make_dir(&self, tf: &TmpFile, mode: u32) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.make_dir(tf, mode)
+    }
+
+    fn symlink(&self, tf: &TmpFile, target: &Path) -> Result<Inode, TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.symlink(tf, target)
+    }
+
+    fn link(&self, tf: &TmpFile, new_path: &Path) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.link(tf, new_path)
+    }
+
+    fn read_link(&self, tf: &TmpFile) -> Result<PathBuf, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.read_link(tf)
+    }
+
+    fn get_xattr(&self, tf: &TmpFile, name: &str) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.get_xattr(tf, name)
+    }
+
+    fn set_xattr(
+        &self,
+        tf: &TmpFile,
+        name: &str,
+        value: &[u8],
+        flags: i32,
+    ) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.set_xattr(tf, name, value, flags)
+    }
+
+    fn list_xattr(&self, tf: &TmpFile) -> Result<Vec<u8>, TmpFileError> {
+        let _guard = tf.lock.read().unwrap();
+        self.0.list_xattr(tf)
+    }
+
+    fn remove_xattr(&self, tf: &TmpFile, name: &str) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.remove_xattr(tf, name)
+    }
+
+    fn remove_dir(&self, tf: &TmpFile, rm_dir_time: &SystemTime) -> Result<(), TmpFileError> {
+        let _guard = tf.lock.write().unwrap();
+        self.0.remove_dir(tf, rm_dir_time)
+    }
+}
@@ -1,139 +1,217 @@
 use log::{debug, info};
-use rfuse_core::sys_fs::RFuseFSOP;
+use rfuse_core::notify::OriginChange;
 
 #[cfg(target_os = "linux")]
-use std::path::Path;
+use std::{ffi::OsString, path::Path};
+use std::{path::PathBuf, sync::mpsc::Sender};
 
+#[cfg(target_os = "linux")]
 use tokio::sync::mpsc;
 
 use crate::ExitStatus;
 
+// 改名的 From 事件等待配对的 To 事件的时间, 超时说明文件被移出了源目录
+#[cfg(target_os = "linux")]
+const RENAME_PAIR_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
 #[cfg(target_os = "linux")]
 pub async fn notify_loop(
-    rfs_send: mpsc::Sender<RFuseFSOP>,
-    source_dir: String,
+    change_send: Sender<OriginChange>,
+    source_dir: PathBuf,
 ) -> Result<(), ExitStatus> {
     use log::error;
-    use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+    use notify::{
+        event::{ModifyKind, RenameMode},
+        Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    };
     info!("[notify loop] Listening thread started");
 
+    // 创建一个通道，用于接收文件改动事件
+    let (notify_send, mut notify_recv) = mpsc::channel(1024);
+
+    // 创建一个文件监视器, 整个挂载期间只监听一次
+    let mut watcher = match RecommendedWatcher::new(
+        move |res| {
+            if let Err(e) = notify_send.blocking_send(res) {
+                error!("[notify loop] send error: {:?}", e);
+            }
+        },
+        Config::default(),
+    ) {
+        Ok(w) => w,
+        Err(e) => {
+            error!("create watcher failed: {:?}", e);
+            return Err(ExitStatus::Error);
+        }
+    };
+
+    // 监听文件夹（递归）
+    if let Err(e) = watcher.watch(&source_dir, RecursiveMode::Recursive) {
+        error!("watcher watch failed: {:?}", e);
+        return Err(ExitStatus::Error);
+    }
+
     tokio::spawn(async move {
+        // watcher 被 drop 后就不再产生事件, 需要和循环活得一样久
+        let _watcher = watcher;
+        let source_dir = source_dir.as_path();
+        // 还没有配对的改名事件 (tracker, 旧路径)
+        let mut rename_from: Option<(Option<usize>, OsString)> = None;
+
         loop {
-            // 创建一个通道，用于接收文件改动事件
-            let (notify_send, mut notify_recv) = mpsc::channel(10);
-
-            // 创建一个文件监视器
-            let mut watcher = match RecommendedWatcher::new(
-                move |res| {
-                    if let Err(e) = notify_send.blocking_send(res) {
-                        error!("[notify loop] send error: {:?}", e);
+            let event = if rename_from.is_some() {
+                match tokio::time::timeout(RENAME_PAIR_TIMEOUT, notify_recv.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        // 没有等到新名字, 按删除处理
+                        let (_, from) = rename_from.take().unwrap();
+                        if !send_change(&change_send, OriginChange::Remove(from)) {
+                            break;
+                        }
+                        continue;
                     }
-                },
-                Config::default(),
-            ) {
-                Ok(w) => w,
-                Err(e) => {
-                    error!("create watcher failed: {:?}", e);
+                }
+            } else {
+                notify_recv.recv().await
+            };
+            let event = match event {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => {
+                    error!("[notify loop] event error: {:?}", e);
+                    continue;
+                }
+                None => {
+                    error!("[notify loop] recv error");
                     break;
                 }
             };
+            debug!(
+                "[notify loop] event: {:?}, paths: {:?}",
+                event.kind, event.paths
+            );
 
-            // 监听文件夹（递归）
-            match watcher.watch(Path::new(&source_dir), RecursiveMode::Recursive) {
-                Ok(_) => {}
-                Err(e) => {
-                    error!("watcher watch failed: {:?}", e);
-                    break;
+            let mut changes = Vec::new();
+            // 事件队列溢出, 无法确定丢失了哪些事件
+            if event.need_rescan() {
+                changes.push(OriginChange::Reload);
+            }
+            let paths: Option<Vec<OsString>> = event
+                .paths
+                .iter()
+                .map(|path| origin_path(source_dir, path))
+                .collect();
+            let paths = match paths {
+                Some(paths) => paths,
+                None => {
+                    debug!("[notify loop] path outside origin: {:?}", event.paths);
+                    changes.push(OriginChange::Reload);
+                    Vec::new()
                 }
             };
-            match notify_recv.recv().await {
-                Some(event) => match event {
-                    Ok(event) => match event.kind {
-                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
-                            debug!(
-                                "[notify loop] event: {:?}, paths: {:?}",
-                                event.kind, event.paths
-                            );
-                            // 检查最近哪个进程访问了该文件
-                            for path in event.paths {
-                                if let Some(pids) = find_pids_accessing_file(&path) {
-                                    // 如果有进程访问了该文件, 那么重新初始化文件系统, 除了自己的进程
-                                    if pids.len() > 1
-                                        || (pids.len() == 1 && pids[0] != std::process::id() as i32)
-                                    {
-                                        info!("[notify loop] reinit fs");
-                                        debug!("[notify loop] pids: {:?}", pids);
-                                        match rfs_send.send(RFuseFSOP::ReInItFs).await {
-                                            Ok(_) => debug!("[notify loop] send success"),
-                                            Err(e) => {
-                                                error!("[notify loop] send error: {:?}", e);
-                                                break;
-                                            }
-                                        };
-                                        break;
-                                    }
-                                }
-                            }
+
+            match event.kind {
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                    if let Some((_, from)) = rename_from.take() {
+                        changes.push(OriginChange::Remove(from));
+                    }
+                    rename_from = paths
+                        .into_iter()
+                        .next()
+                        .map(|from| (event.attrs.tracker(), from));
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                    match rename_from.as_ref() {
+                        // 配对成功, 随后的 Both 事件中同时带有新旧路径
+                        Some((tracker, _))
+                            if tracker.is_some() && *tracker == event.attrs.tracker() => {}
+                        _ => changes.extend(paths.into_iter().map(OriginChange::Create)),
+                    }
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                    rename_from = None;
+                    let mut paths = paths.into_iter();
+                    changes.push(OriginChange::Rename {
+                        from: paths.next().unwrap(),
+                        to: paths.next().unwrap(),
+                    });
+                }
+                // 无法区分新旧路径时, 根据文件是否存在判断
+                EventKind::Modify(ModifyKind::Name(_)) => {
+                    for (path, origin) in event.paths.iter().zip(paths) {
+                        if path.symlink_metadata().is_ok() {
+                            changes.push(OriginChange::Create(origin));
+                        } else {
+                            changes.push(OriginChange::Remove(origin));
                         }
-                        _ => {}
-                    },
-                    Err(e) => {
-                        error!("[notify loop] event error: {:?}", e);
                     }
-                },
-                None => {
-                    error!("[notify loop] recv error");
+                }
+                EventKind::Create(_) => changes.extend(paths.into_iter().map(OriginChange::Create)),
+                EventKind::Modify(_) | EventKind::Any => {
+                    changes.extend(paths.into_iter().map(OriginChange::Modify))
+                }
+                EventKind::Remove(_) => changes.extend(paths.into_iter().map(OriginChange::Remove)),
+                // 访问事件不会改变文件
+                EventKind::Access(_) | EventKind::Other => {}
+            }
+
+            // 其他事件到达时, 之前的改名不会再配对
+            if !matches!(event.kind, EventKind::Modify(ModifyKind::Name(_))) {
+                if let Some((_, from)) = rename_from.take() {
+                    changes.insert(0, OriginChange::Remove(from));
                 }
             }
+
+            if !changes
+                .into_iter()
+                .all(|change| send_change(&change_send, change))
+            {
+                break;
+            }
         }
     });
     Ok(())
 }
 
+// 将事件中的绝对路径转换为源目录中的相对路径, 例如 `/dir/file`
 #[cfg(target_os = "linux")]
-fn find_pids_accessing_file(path: &Path) -> Option<Vec<i32>> {
-    use log::debug;
-    use procfs::process::all_processes;
-    use std::fs;
-
-    let processes = all_processes().ok()?;
-    let mut accessing_pids = Vec::new();
-
-    // TODO: 这里有时候会出现问题, 有时候会出现找不到文件的情况, 简单来说就是处理的太快了, 导致文件已经被删除了还没来得及处理 (随着进程的增多可能会恶化这种情况)
-    for process in processes.flatten() {
-        let fd_dir = format!("/proc/{}/fd", process.pid);
-        if let Ok(fds) = fs::read_dir(fd_dir) {
-            for fd in fds.flatten() {
-                if let Ok(link_path) = fs::read_link(fd.path()) {
-                    if link_path == *path {
-                        debug!("[find_pids_accessing_file] fd path: {:?}", fd.path());
-                        accessing_pids.push(process.pid);
-                    }
-                }
-            }
-        }
+fn origin_path(source_dir: &Path, path: &Path) -> Option<OsString> {
+    let relative = path.strip_prefix(source_dir).ok()?;
+    let mut origin = OsString::new();
+    for component in relative.components() {
+        origin.push("/");
+        origin.push(component.as_os_str());
+    }
+    if origin.is_empty() {
+        origin.push("/");
     }
+    Some(origin)
+}
 
-    if accessing_pids.is_empty() {
-        None
-    } else {
-        Some(accessing_pids)
+#[cfg(target_os = "linux")]
+fn send_change(change_send: &Sender<OriginChange>, change: OriginChange) -> bool {
+    debug!("[notify loop] origin change: {:?}", change);
+    match change_send.send(change) {
+        Ok(_) => true,
+        Err(e) => {
+            log::error!("[notify loop] send error: {:?}", e);
+            false
+        }
     }
 }
 
 #[cfg(not(target_os = "linux"))]
 pub async fn notify_loop(
-    rfs_send: mpsc::Sender<RFuseFSOP>,
-    _source_dir: String,
+    change_send: Sender<OriginChange>,
+    _source_dir: PathBuf,
 ) -> Result<(), ExitStatus> {
     use std::time;
 
     info!("[notify loop] time interval thread started");
     tokio::spawn(async move {
         loop {
-            // 一分钟重制一次
+            // 一分钟重新加载一次
             tokio::time::sleep(time::Duration::from_secs(60)).await;
-            match rfs_send.send(RFuseFSOP::ReInItFs).await {
+            match change_send.send(OriginChange::Reload) {
                 Ok(_) => debug!("[notify loop] send success"),
                 Err(e) => {
                     info!("[notify loop] send error: {:?}", e);
@@ -1,18 +1,31 @@
-use crate::init_fs::user_defined_init_fs;
+use std::{
+    sync::{mpsc as std_mpsc, Arc},
+    thread,
+};
+
+use crate::init_fs::{mem_defined_init_fs, user_defined_init_fs};
 use crate::notify_loop::notify_loop;
 use crate::{
-    cli::args::{Args, Command, LinkCommand},
+    cli::args::{Args, Command, LinkCommand, ServeCommand},
     local_fs::LocalFS,
     logging::{init_log, LogLevel},
+    mem_fs::MemFS,
+    serve::serve,
     ExitStatus,
 };
 use anyhow::Result;
 use fuser::MountOption;
 use log::{debug, error, info};
-use nix::unistd::geteuid;
-use rfuse_core::sys_fs::{RFuseFS, RFuseFSOP};
-use rfuse_device_disk::DiskType;
-use tokio::{signal, sync::mpsc};
+use nix::unistd::{getegid, geteuid};
+use rfuse_core::{
+    notify::notify_kernel,
+    remote_fs::InitFsFuncType,
+    shared_fs::{consume_origin_changes, SharedFS},
+    sys_fs::{CacheOptions, RFuseFS, RFuseFSOP},
+    tmp_file::TmpFileTrait,
+};
+use rfuse_device_disk::{mem_disk::MemDisk, DiskType};
+use tokio::{net::TcpListener, runtime::Handle, signal, sync::mpsc};
 
 pub async fn run(
     Args {
@@ -26,16 +39,44 @@ pub async fn run(
 
     match command {
         Command::Link(link_command) => run_link(link_command).await,
+        Command::Serve(serve_command) => run_serve(serve_command).await,
     }
 }
 
+async fn run_serve(
+    ServeCommand {
+        origin,
+        bind,
+        token,
+    }: ServeCommand,
+) -> Result<ExitStatus> {
+    let listener = TcpListener::bind(&bind).await?;
+    info!("[serve] listening on {}", listener.local_addr()?);
+
+    tokio::select! {
+        res = serve(listener, origin, token) => res?,
+        res = signal::ctrl_c() => {
+            match res {
+                Ok(_) => debug!("ctrl+c received"),
+                Err(e) => error!("ctrl+c error: {:?}", e),
+            };
+            info!("[serve] shutting down");
+        }
+    }
+
+    Ok(ExitStatus::Success)
+}
+
 async fn run_link(
     LinkCommand {
         origin,
         mount,
         read_only,
         fs_name,
+        no_xattr,
+        threads,
         disk_type,
+        cache,
     }: LinkCommand,
 ) -> Result<ExitStatus> {
     // 挂载选项
@@ -71,40 +112,64 @@ async fn run_link(
         }
     });
 
-    if let Err(e) = notify_loop(rfs_send.clone(), origin.display().to_string()).await {
-        error!("[run] notify_loop failed");
-        return Ok(e);
+    let mem_capacity = disk_type.mem_size.saturating_mul(1024 * 1024);
+    let disk_type = DiskType::from(&disk_type);
+
+    // 创建文件系统
+    let (init_fs, tmp_file_trait): (Box<InitFsFuncType>, Box<dyn TmpFileTrait>) = match disk_type {
+        DiskType::Local => (Box::new(user_defined_init_fs), Box::new(LocalFS)),
+        DiskType::Mem => {
+            let mem_disk = Arc::new(MemDisk::new(
+                0o755,
+                geteuid().as_raw(),
+                getegid().as_raw(),
+                mem_capacity,
+            ));
+            (
+                mem_defined_init_fs(mem_disk.clone()),
+                Box::new(MemFS(mem_disk)),
+            )
+        }
+    };
+    let mut rfs = RFuseFS::new(
+        fs_name.clone(),
+        CacheOptions::from(&cache),
+        !no_xattr,
+        origin.clone(),
+        init_fs,
+        tmp_file_trait,
+    );
+    rfs.spawn_workers(threads);
+    // 文件读写在 tokio 的阻塞线程池中执行, 不占用 fuser 的请求线程
+    rfs.adapt_sync_backend(Handle::current());
+
+    // 源目录的改动直接应用到挂载中的文件系统, 不再重新挂载
+    let (invalidation_send, invalidation_recv) = std_mpsc::channel();
+    rfs.watch_origin(invalidation_send);
+    let rfs = SharedFS::new(rfs);
+    // 内存磁盘不依赖源目录, 不需要监听源目录的改动
+    if let DiskType::Local = disk_type {
+        let (change_send, change_recv) = std_mpsc::channel();
+        let consumer = rfs.clone();
+        thread::spawn(move || consume_origin_changes(consumer, change_recv));
+        if let Err(e) = notify_loop(change_send, origin.clone()).await {
+            error!("[run] notify_loop failed");
+            return Ok(e);
+        }
     }
 
-    loop {
-        // 创建本地文件系统
-        let lfs = match DiskType::from(&disk_type) {
-            DiskType::Local => LocalFS,
-            DiskType::Mem => unimplemented!(),
-        };
-        let rfs = RFuseFS::new(
-            fs_name.clone(),
-            true,
-            origin.display().to_string(),
-            Box::new(user_defined_init_fs),
-            Box::new(lfs),
-        );
-        let guard = fuser::spawn_mount2(rfs, mount.display().to_string(), &options).unwrap();
-
-        if let Some(rfs_op) = rfs_recv.recv().await {
-            match rfs_op {
-                RFuseFSOP::ReInItFs => {
-                    drop(guard);
-                    continue;
-                }
-                RFuseFSOP::Exit => {
-                    guard.join();
-                    break;
-                }
-                RFuseFSOP::Nothing => {
-                    // do nothing
-                    // Note: 或许不应该有这个参数，这样会导致 guard 不断的被加载
-                }
+    let guard = fuser::spawn_mount2(rfs, mount.display().to_string(), &options).unwrap();
+    let notifier = guard.notifier();
+    thread::spawn(move || notify_kernel(notifier, invalidation_recv));
+
+    while let Some(rfs_op) = rfs_recv.recv().await {
+        match rfs_op {
+            RFuseFSOP::Exit => {
+                guard.join();
+                break;
+            }
+            RFuseFSOP::Nothing => {
+                // do nothing
             }
         }
     }
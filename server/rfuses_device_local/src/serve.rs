@@ -0,0 +1,634 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, ErrorKind},
+    ops::Deref,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::{OsStrExt, OsStringExt},
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use log::{debug, error, info, warn};
+#[cfg(not(target_os = "linux"))]
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::{
+    errno::Errno,
+    fcntl::{openat, AtFlags, OFlag},
+    libc,
+    sys::stat::{fstat, fstatat, Mode},
+};
+use rfuse_core::{
+    inode::InodeKind,
+    tmp_file::{TmpFile, TmpFileError},
+};
+use rfuse_device_disk::local_disk;
+use rfuse_protocol::{
+    codec::{check_frame_len, MAX_DATA_SIZE},
+    message::{DirEntry, RemoteAttr, Request, Response},
+    PROTOCOL_VERSION,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+// 这个文件主要是把 local_disk 的操作通过网络提供给 rfusec 使用
+
+/// 客户端连接后必须先握手, 配置了 token 时握手中的 token 必须一致
+pub async fn serve(
+    listener: TcpListener,
+    source_dir: PathBuf,
+    token: Option<String>,
+) -> Result<()> {
+    let export = Arc::new(Export::open(&source_dir)?);
+    let token = Arc::new(token);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        info!("[serve] accept connection from {}", addr);
+        let export = export.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            match handle_connection(stream, export, token).await {
+                Ok(_) => info!("[serve] connection {} closed", addr),
+                Err(e) => error!("[serve] connection {} error: {:?}", addr, e),
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    export: Arc<Export>,
+    token: Arc<Option<String>>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut authorized = false;
+    loop {
+        let mut len = [0u8; 4];
+        match stream.read_exact(&mut len).await {
+            Ok(_) => {}
+            // 客户端关闭了连接
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let mut payload = vec![0u8; check_frame_len(u32::from_be_bytes(len))?];
+        stream.read_exact(&mut payload).await?;
+        let (id, request) = Request::decode(&payload)?;
+
+        // 握手通过之前不处理其他请求, 握手失败时回复错误之后关闭连接
+        if !authorized {
+            let response = handshake(request, token.as_deref());
+            authorized = matches!(response, Response::Handshake { .. });
+            write_response(&mut stream, id, &response).await?;
+            if !authorized {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // local_disk 中的操作都是阻塞的, 放到单独的线程中执行
+        let export = export.clone();
+        let response =
+            tokio::task::spawn_blocking(move || handle_request(&export, request)).await?;
+        write_response(&mut stream, id, &response).await?;
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, id: u64, response: &Response) -> Result<()> {
+    let payload = response.encode(id);
+    stream
+        .write_all(&(payload.len() as u32).to_be_bytes())
+        .await?;
+    stream.write_all(&payload).await?;
+    Ok(())
+}
+
+// 检查客户端的第一个请求, 协议版本和 token 都一致时才允许继续
+fn handshake(request: Request, token: Option<&str>) -> Response {
+    match request {
+        Request::Handshake { version, .. } if version != PROTOCOL_VERSION => {
+            warn!(
+                "[serve][handshake] client protocol version {} does not match {}",
+                version, PROTOCOL_VERSION
+            );
+            Response::Error(TmpFileError::Errno(libc::EPROTONOSUPPORT))
+        }
+        Request::Handshake {
+            token: client_token,
+            ..
+        } => match token {
+            Some(token) if !token_eq(token.as_bytes(), client_token.as_bytes()) => {
+                warn!("[serve][handshake] invalid token");
+                Response::Error(TmpFileError::Errno(libc::EACCES))
+            }
+            _ => Response::Handshake {
+                version: PROTOCOL_VERSION,
+            },
+        },
+        _ => {
+            warn!("[serve][handshake] the first request is not a handshake");
+            Response::Error(TmpFileError::Errno(libc::EACCES))
+        }
+    }
+}
+
+/// 共享目录, 客户端的路径都从共享目录的句柄开始逐级打开
+pub struct Export {
+    // 已经解析过符号链接的真实路径
+    dir: PathBuf,
+    root: OwnedFd,
+}
+
+impl Export {
+    pub fn open(source_dir: &Path) -> io::Result<Self> {
+        // 沙箱检查比较的是解析过符号链接的真实路径, 所以共享目录本身也要先解析
+        let dir = source_dir.canonicalize()?;
+        let root = OwnedFd::from(fs::File::open(&dir)?);
+        Ok(Self { dir, root })
+    }
+}
+
+// 路径的最后一级怎样解析
+#[derive(Clone, Copy)]
+enum Target {
+    // 不跟随最后一级的符号链接, 例如 get_attr 和 read_link
+    Node,
+    // 增加或删除目录项, 例如 unlink 和 rename, 不能是共享目录本身
+    Entry,
+    // 跟随最后一级的符号链接, 例如 open 和 write, 符号链接必须指向共享目录以内
+    Follow,
+    // 创建文件, 已经存在时和 Follow 一样
+    Create,
+}
+
+/// 解析后的文件, 操作完成之前保持路径上打开的句柄
+///
+/// TmpFile 的路径经过这些句柄 (`/proc/self/fd/<fd>/`), 解析之后路径中的文件夹被替换成符号链接也不会离开共享目录
+struct Resolved {
+    tf: TmpFile,
+    _fds: Vec<OwnedFd>,
+}
+
+impl Deref for Resolved {
+    type Target = TmpFile;
+
+    fn deref(&self) -> &TmpFile {
+        &self.tf
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn fd_path(fd: &OwnedFd) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+}
+
+// 没有 /proc 时使用句柄当前的路径, 打开时已经检查过每一级都不是符号链接
+#[cfg(not(target_os = "linux"))]
+fn fd_path(fd: &OwnedFd) -> PathBuf {
+    let mut path = PathBuf::new();
+    match fcntl(fd.as_raw_fd(), FcntlArg::F_GETPATH(&mut path)) {
+        Ok(_) => path,
+        Err(e) => {
+            error!("[serve] Failed to get the path of fd: {}", e);
+            PathBuf::from("/nonexistent")
+        }
+    }
+}
+
+// openat 返回新打开的句柄
+fn open_at(dir: &OwnedFd, name: &OsStr, flags: OFlag, mode: Mode) -> io::Result<OwnedFd> {
+    let fd = openat(
+        Some(dir.as_raw_fd()),
+        name,
+        flags | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+        mode,
+    )?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+#[cfg(target_os = "linux")]
+const PIN_FLAGS: OFlag = OFlag::O_PATH;
+#[cfg(not(target_os = "linux"))]
+const PIN_FLAGS: OFlag = OFlag::O_RDONLY.union(OFlag::O_NONBLOCK);
+
+// 将客户端传来的相对路径转换为 local_disk 使用的 TmpFile, 不允许访问共享目录以外的文件
+//
+// 所在文件夹先被解析成真实路径并检查是否在共享目录以内, 再从共享目录的句柄开始用 O_NOFOLLOW 逐级打开,
+// 检查之后被替换成符号链接的文件夹会打开失败。跟随最后一级时最后一级也会被打开, 操作的是打开的文件本身
+fn tmp_file(
+    export: &Export,
+    path: &Path,
+    target: Target,
+    err: TmpFileError,
+) -> Result<Resolved, TmpFileError> {
+    let bytes = path.as_os_str().as_bytes();
+    if !bytes.starts_with(b"/") || bytes.split(|b| *b == b'/').any(|c| c == b"..") {
+        warn!("[serve] reject path: {}", path.display());
+        return Err(err);
+    }
+    let index = bytes.iter().rposition(|b| *b == b'/').unwrap() + 1;
+    let (dir, file_name) = bytes.split_at(index);
+    let file_name = OsStr::from_bytes(file_name);
+    if file_name.is_empty() && matches!(target, Target::Entry | Target::Create) {
+        // 包括共享目录本身, 例如 RemoveDir "/"
+        warn!("[serve] reject entry operation on: {}", path.display());
+        return Err(TmpFileError::Errno(libc::EBUSY));
+    }
+    let mut dir_path = export.dir.as_os_str().to_os_string();
+    dir_path.push(OsStr::from_bytes(dir));
+    let dir_path = match fs::canonicalize(&dir_path) {
+        Ok(p) if p.starts_with(&export.dir) => p,
+        Ok(p) => {
+            warn!(
+                "[serve] reject path: {}, resolved to {}",
+                path.display(),
+                p.display()
+            );
+            return Err(err);
+        }
+        Err(e) => return Err(TmpFileError::from_io(&e, err)),
+    };
+    let dir_fd = open_dir(export, &dir_path, path, err)?;
+    match target {
+        Target::Follow | Target::Create if !file_name.is_empty() => {
+            open_final(export, dir_fd, file_name, path, target, err)
+        }
+        _ => Ok(Resolved {
+            tf: in_dir(&dir_fd, file_name),
+            _fds: vec![dir_fd],
+        }),
+    }
+}
+
+// 从共享目录的句柄开始逐级打开解析过的文件夹, 任何一级是符号链接都会失败
+fn open_dir(
+    export: &Export,
+    dir_path: &Path,
+    path: &Path,
+    err: TmpFileError,
+) -> Result<OwnedFd, TmpFileError> {
+    let relative = dir_path.strip_prefix(&export.dir).map_err(|_| err)?;
+    let mut dir_fd = export
+        .root
+        .try_clone()
+        .map_err(|e| TmpFileError::from_io(&e, err))?;
+    for component in relative.components() {
+        dir_fd = match open_at(
+            &dir_fd,
+            component.as_os_str(),
+            OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+            Mode::empty(),
+        ) {
+            Ok(fd) => fd,
+            // 检查之后文件夹被替换成了符号链接
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ELOOP | libc::ENOTDIR)) => {
+                warn!(
+                    "[serve] reject path changed while resolving: {}",
+                    path.display()
+                );
+                return Err(err);
+            }
+            Err(e) => return Err(TmpFileError::from_io(&e, err)),
+        };
+    }
+    Ok(dir_fd)
+}
+
+// TmpFile 的 path 是以 `/` 结尾的所在文件夹
+fn in_dir(dir_fd: &OwnedFd, file_name: &OsStr) -> TmpFile {
+    let mut dir = fd_path(dir_fd).into_os_string();
+    dir.push("/");
+    TmpFile::new(file_name.to_os_string(), PathBuf::from(dir))
+}
+
+// 打开最后一级, 符号链接解析成真实路径之后重新检查, 悬空的符号链接也要拒绝, 否则创建文件时会在共享目录以外创建
+fn open_final(
+    export: &Export,
+    dir_fd: OwnedFd,
+    file_name: &OsStr,
+    path: &Path,
+    target: Target,
+    err: TmpFileError,
+) -> Result<Resolved, TmpFileError> {
+    let stat = match fstatat(
+        Some(dir_fd.as_raw_fd()),
+        file_name,
+        AtFlags::AT_SYMLINK_NOFOLLOW,
+    ) {
+        Ok(stat) => stat,
+        Err(Errno::ENOENT) if matches!(target, Target::Create) => {
+            // 用 O_EXCL 创建, 不会跟随同时创建的符号链接, 权限由 create_file 重新设置
+            return match open_at(
+                &dir_fd,
+                file_name,
+                OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+                Mode::from_bits_truncate(0o600),
+            ) {
+                Ok(fd) => Ok(pinned(dir_fd, fd)),
+                Err(e) => Err(TmpFileError::from_io(&e, err)),
+            };
+        }
+        Err(e) => return Err(TmpFileError::Errno(e as i32)),
+    };
+
+    if stat.st_mode & libc::S_IFMT == libc::S_IFLNK {
+        let mut link = fd_path(&dir_fd).into_os_string();
+        link.push("/");
+        link.push(file_name);
+        let real = match fs::canonicalize(&link) {
+            Ok(p) if p.starts_with(&export.dir) => p,
+            _ => {
+                warn!("[serve] reject symlink: {}", path.display());
+                return Err(err);
+            }
+        };
+        // 指向共享目录本身
+        if real == export.dir {
+            let root = export
+                .root
+                .try_clone()
+                .map_err(|e| TmpFileError::from_io(&e, err))?;
+            return Ok(Resolved {
+                tf: in_dir(&root, OsStr::new("")),
+                _fds: vec![dir_fd, root],
+            });
+        }
+        // 真实路径的最后一级不是符号链接, 被替换成符号链接时打开会失败
+        let real_dir = real.parent().ok_or(err)?;
+        let real_name = real.file_name().ok_or(err)?;
+        let real_fd = open_dir(export, real_dir, path, err)?;
+        return match open_at(&real_fd, real_name, PIN_FLAGS, Mode::empty()) {
+            Ok(fd) if !is_symlink(&fd) => Ok(pinned(real_fd, fd)),
+            _ => {
+                warn!("[serve] reject symlink: {}", path.display());
+                Err(err)
+            }
+        };
+    }
+
+    match open_at(&dir_fd, file_name, PIN_FLAGS, Mode::empty()) {
+        Ok(fd) if !is_symlink(&fd) => Ok(pinned(dir_fd, fd)),
+        Ok(_) => {
+            warn!(
+                "[serve] reject path changed while resolving: {}",
+                path.display()
+            );
+            Err(err)
+        }
+        Err(e) => Err(TmpFileError::from_io(&e, err)),
+    }
+}
+
+// O_PATH 和 O_NOFOLLOW 一起使用时会打开符号链接本身
+fn is_symlink(fd: &OwnedFd) -> bool {
+    matches!(fstat(fd.as_raw_fd()), Ok(stat) if stat.st_mode & libc::S_IFMT == libc::S_IFLNK)
+}
+
+// 通过打开的句柄访问最后一级
+fn pinned(dir_fd: OwnedFd, fd: OwnedFd) -> Resolved {
+    let file_path = fd_path(&fd);
+    let mut dir = file_path
+        .parent()
+        .unwrap_or(Path::new("/"))
+        .as_os_str()
+        .to_os_string();
+    dir.push("/");
+    let tf = TmpFile::new(
+        file_path.file_name().unwrap_or_default().to_os_string(),
+        PathBuf::from(dir),
+    );
+    Resolved {
+        tf,
+        _fds: vec![dir_fd, fd],
+    }
+}
+
+// 按字节比较全部内容, 比较的时间不随第一个不同字节的位置变化
+fn token_eq(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn attr_response(inode: rfuse_core::inode::Inode) -> Response {
+    Response::Attr(RemoteAttr::from_inode(&inode))
+}
+
+/// 处理握手之后的请求
+pub fn handle_request(export: &Export, request: Request) -> Response {
+    let result = match request {
+        // 握手在 handle_connection 中完成, 重复的握手直接返回版本
+        Request::Handshake { .. } => Ok(Response::Handshake {
+            version: PROTOCOL_VERSION,
+        }),
+        Request::GetAttr { path } => {
+            debug!("[serve][get_attr] {}", path.display());
+            tmp_file(export, &path, Target::Node, TmpFileError::ReadError)
+                .and_then(|tf| local_disk::get_attr(&tf))
+                .map(attr_response)
+        }
+        Request::Lookup { parent, name } => {
+            debug!("[serve][lookup] {} {:?}", parent.display(), name);
+            if name.as_bytes().contains(&b'/') {
+                Err(TmpFileError::ReadError)
+            } else {
+                let mut path = parent.as_os_str().as_bytes().to_vec();
+                while path.last() == Some(&b'/') {
+                    path.pop();
+                }
+                path.push(b'/');
+                path.extend_from_slice(name.as_bytes());
+                let path = PathBuf::from(OsString::from_vec(path));
+                tmp_file(export, &path, Target::Node, TmpFileError::ReadError)
+                    .and_then(|tf| local_disk::get_attr(&tf))
+                    .map(attr_response)
+            }
+        }
+        Request::ReadDir { path } => {
+            debug!("[serve][read_dir] {}", path.display());
+            tmp_file(export, &path, Target::Follow, TmpFileError::ReadError)
+                .and_then(|tf| local_disk::read_dir(&tf))
+                .map(|children| {
+                    Response::Entries(
+                        children
+                            .iter()
+                            .map(|child| DirEntry {
+                                name: child.attr.name.clone(),
+                                attr: RemoteAttr::from_inode(child),
+                            })
+                            .collect(),
+                    )
+                })
+        }
+        Request::Read { path, offset, size } => {
+            debug!(
+                "[serve][read] {} offset={} size={}",
+                path.display(),
+                offset,
+                size
+            );
+            tmp_file(export, &path, Target::Follow, TmpFileError::ReadError).and_then(|tf| {
+                let mut buf = vec![0u8; size.min(MAX_DATA_SIZE) as usize];
+                local_disk::read_exact(&tf, &mut buf, offset).map(|_| Response::Data(buf))
+            })
+        }
+        Request::ReadAll { path } => {
+            debug!("[serve][read_all] {}", path.display());
+            // 整个文件必须能放进一帧, 更大的文件由客户端分段读取
+            tmp_file(export, &path, Target::Follow, TmpFileError::ReadError)
+                .and_then(|tf| match fs::metadata(tf.full_path()) {
+                    Ok(meta) if meta.len() > MAX_DATA_SIZE as u64 => {
+                        Err(TmpFileError::Errno(libc::EFBIG))
+                    }
+                    Ok(_) => local_disk::read_all(&tf),
+                    Err(e) => Err(TmpFileError::from_io(&e, TmpFileError::ReadError)),
+                })
+                .and_then(|data| match data.len() > MAX_DATA_SIZE as usize {
+                    // 读取期间文件变大了
+                    true => Err(TmpFileError::Errno(libc::EFBIG)),
+                    false => Ok(data),
+                })
+                .map(Response::Data)
+        }
+        Request::Write {
+            path,
+            offset,
+            data,
+            write_time,
+        } => {
+            debug!(
+                "[serve][write] {} offset={} len={}",
+                path.display(),
+                offset,
+                data.len()
+            );
+            tmp_file(export, &path, Target::Follow, TmpFileError::WriteError)
+                .and_then(|tf| local_disk::write(&tf, &data, &write_time, offset))
+                .map(|_| Response::Ok)
+        }
+        Request::Create { path, mode } => {
+            debug!("[serve][create] {} mode={:o}", path.display(), mode);
+            tmp_file(export, &path, Target::Create, TmpFileError::CreateError)
+                .and_then(|tf| local_disk::create_file(&tf, mode))
+                .map(attr_response)
+        }
+        Request::MakeDir { path, mode } => {
+            debug!("[serve][make_dir] {} mode={:o}", path.display(), mode);
+            tmp_file(export, &path, Target::Entry, TmpFileError::MakeDirError)
+                .and_then(|tf| local_disk::make_dir(&tf, mode))
+                .map(attr_response)
+        }
+        Request::Rename {
+            path,
+            new_path,
+            rename_time,
+            flags,
+        } => {
+            debug!(
+                "[serve][rename] {} -> {}, flags: {}",
+                path.display(),
+                new_path.display(),
+                flags
+            );
+            tmp_file(export, &path, Target::Entry, TmpFileError::RenameError).and_then(|tf| {
+                let new_tf = tmp_file(export, &new_path, Target::Entry, TmpFileError::RenameError)?;
+                local_disk::rename(&tf, &new_tf.full_path(), &rename_time, flags).map(|_| Response::Ok)
+            })
+        }
+        Request::Unlink { path, rm_file_time } => {
+            debug!("[serve][unlink] {}", path.display());
+            tmp_file(export, &path, Target::Entry, TmpFileError::RemoveError)
+                .and_then(|tf| local_disk::remove_file(&tf, &rm_file_time))
+                .map(|_| Response::Ok)
+        }
+        Request::RemoveDir { path, rm_dir_time } => {
+            debug!("[serve][remove_dir] {}", path.display());
+            tmp_file(export, &path, Target::Entry, TmpFileError::RemoveDirError)
+                .and_then(|tf| local_disk::remove_dir(&tf, &rm_dir_time))
+                .map(|_| Response::Ok)
+        }
+        Request::SetAttr { path, attr } => {
+            debug!("[serve][set_attr] {}", path.display());
+            // 软链接修改的是链接本身
+            let target = match attr.kind {
+                InodeKind::Symlink => Target::Node,
+                _ => Target::Follow,
+            };
+            tmp_file(export, &path, target, TmpFileError::SetAttrError).and_then(|tf| {
+                let attr =
+                    attr.to_attributes(tf.file_name.clone(), tf.path.clone().into_os_string());
+                local_disk::set_attr(&tf, &attr).map(|_| Response::Ok)
+            })
+        }
+        Request::Symlink { path, target } => {
+            debug!(
+                "[serve][symlink] {} -> {}",
+                path.display(),
+                target.display()
+            );
+            tmp_file(export, &path, Target::Entry, TmpFileError::CreateError)
+                .and_then(|tf| local_disk::symlink(&tf, &target))
+                .map(attr_response)
+        }
+        Request::ReadLink { path } => {
+            debug!("[serve][read_link] {}", path.display());
+            tmp_file(export, &path, Target::Node, TmpFileError::ReadError)
+                .and_then(|tf| local_disk::read_link(&tf))
+                .map(|target| Response::Data(target.into_os_string().into_vec()))
+        }
+        Request::Link { path, new_path } => {
+            debug!(
+                "[serve][link] {} -> {}",
+                new_path.display(),
+                path.display()
+            );
+            tmp_file(export, &path, Target::Node, TmpFileError::CreateError).and_then(|tf| {
+                let new_tf = tmp_file(export, &new_path, Target::Entry, TmpFileError::CreateError)?;
+                local_disk::link(&tf, &new_tf.full_path()).map(|_| Response::Ok)
+            })
+        }
+        Request::GetXattr { path, name } => {
+            debug!("[serve][get_xattr] {} {}", path.display(), name);
+            tmp_file(export, &path, Target::Node, TmpFileError::XattrError)
+                .and_then(|tf| local_disk::get_xattr(&tf, &name))
+                .map(Response::Data)
+        }
+        Request::SetXattr {
+            path,
+            name,
+            value,
+            flags,
+        } => {
+            debug!("[serve][set_xattr] {} {}", path.display(), name);
+            tmp_file(export, &path, Target::Node, TmpFileError::XattrError)
+                .and_then(|tf| local_disk::set_xattr(&tf, &name, &value, flags))
+                .map(|_| Response::Ok)
+        }
+        Request::ListXattr { path } => {
+            debug!("[serve][list_xattr] {}", path.display());
+            tmp_file(export, &path, Target::Node, TmpFileError::XattrError)
+                .and_then(|tf| local_disk::list_xattr(&tf))
+                .map(Response::Data)
+        }
+        Request::RemoveXattr { path, name } => {
+            debug!("[serve][remove_xattr] {} {}", path.display(), name);
+            tmp_file(export, &path, Target::Node, TmpFileError::XattrError)
+                .and_then(|tf| local_disk::remove_xattr(&tf, &name))
+                .map(|_| Response::Ok)
+        }
+        Request::StatFs { path } => {
+            debug!("[serve][statfs] {}", path.display());
+            tmp_file(export, &path, Target::Follow, TmpFileError::ReadError)
+                .and_then(|tf| local_disk::statfs(&tf))
+                .map(Response::StatFs)
+        }
+    };
+    result.unwrap_or_else(Response::Error)
+}
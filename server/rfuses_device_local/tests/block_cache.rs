@@ -0,0 +1,383 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::SystemTime,
+};
+
+use fuser::FUSE_ROOT_ID;
+use rfuse_core::{
+    block_cache::{BlockCache, BlockCacheOptions, BlockCacheStats},
+    inode::Inode,
+    notify::OriginChange,
+    sys_fs::{CacheOptions, Caller, RFuseFS},
+    tmp_file::{FileHandle, TmpFile, TmpFileError, TmpFileTrait},
+};
+use rfuses_device_local::{init_fs::user_defined_init_fs, local_fs::LocalFS};
+
+const BLOCK_SIZE: usize = 4096;
+
+// 记录 read_exact 调用次数的本地后端, 模拟每次读取都是一次网络往返
+struct Counting(Arc<AtomicUsize>);
+
+impl TmpFileTrait for Counting {
+    fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        LocalFS.write(tf, data, write_time, offset)
+    }
+
+    // 和网络后端一样不持有句柄, 读写都按路径进行
+    fn open(
+        &self,
+        _tf: &TmpFile,
+        _flags: i32,
+    ) -> Result<Option<Box<dyn FileHandle>>, TmpFileError> {
+        Ok(None)
+    }
+
+    fn read_exact(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        LocalFS.read_exact(tf, buf, offset)
+    }
+
+    fn get_attr(&self, tf: &TmpFile) -> Result<Inode, TmpFileError> {
+        LocalFS.get_attr(tf)
+    }
+
+    fn read_dir(&self, tf: &TmpFile) -> Result<Vec<Inode>, TmpFileError> {
+        LocalFS.read_dir(tf)
+    }
+}
+
+// 第一次读取后端时读完内容之后暂停, 等测试线程通知之后才返回
+struct Paused(Mutex<Option<(Sender<()>, Receiver<()>)>>);
+
+impl TmpFileTrait for Paused {
+    fn write(
+        &self,
+        tf: &TmpFile,
+        data: &[u8],
+        write_time: &SystemTime,
+        offset: u64,
+    ) -> Result<(), TmpFileError> {
+        LocalFS.write(tf, data, write_time, offset)
+    }
+
+    fn read_exact(&self, tf: &TmpFile, buf: &mut [u8], offset: u64) -> Result<(), TmpFileError> {
+        LocalFS.read_exact(tf, buf, offset)?;
+        let pause = self.0.lock().unwrap().take();
+        if let Some((reached, resume)) = pause {
+            reached.send(()).unwrap();
+            resume.recv().unwrap();
+        }
+        Ok(())
+    }
+}
+
+// 每个块的内容都不同
+fn content(blocks: usize) -> Vec<u8> {
+    (0..blocks * BLOCK_SIZE)
+        .map(|index| (index / BLOCK_SIZE * 7 + index % 251) as u8)
+        .collect()
+}
+
+fn tmp_file(dir: &Path, name: &str) -> TmpFile {
+    // TmpFile 的 path 是以 `/` 结尾的所在文件夹, 块按 tmp_file_map 中的 ino 缓存
+    let ino = fs::metadata(dir.join(name)).unwrap().ino();
+    TmpFile::new(
+        OsString::from(name),
+        PathBuf::from(format!("{}/", dir.display())),
+    )
+    .with_ino(ino)
+}
+
+fn block_cache(options: BlockCacheOptions) -> (BlockCache, Arc<AtomicUsize>) {
+    let reads = Arc::new(AtomicUsize::new(0));
+    let cache = BlockCache::new(Box::new(Counting(reads.clone())), options);
+    (cache, reads)
+}
+
+// 文件大小和 inode 表中的一样, 来自源目录
+fn read(cache: &BlockCache, tf: &TmpFile, block: usize, len: usize) -> Vec<u8> {
+    let size = fs::metadata(tf.full_path()).unwrap().len();
+    let mut buf = vec![0; len];
+    cache
+        .read_sized(tf, &mut buf, (block * BLOCK_SIZE) as u64, size)
+        .unwrap();
+    buf
+}
+
+#[test]
+fn test_block_cache_read_ahead() {
+    let origin = tempfile::tempdir().unwrap();
+    let data = content(10);
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    let tf = tmp_file(origin.path(), "file.bin");
+    let (cache, reads) = block_cache(BlockCacheOptions {
+        block_size: BLOCK_SIZE as u64,
+        read_ahead: 4,
+        ..Default::default()
+    });
+
+    // 第一次读取不是顺序读取, 之后在预读的块用完时再预读 4 个块
+    for block in 0..10 {
+        assert_eq!(
+            read(&cache, &tf, block, BLOCK_SIZE),
+            &data[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE]
+        );
+    }
+    assert_eq!(reads.load(Ordering::Relaxed), 3);
+    assert_eq!(
+        cache.stats(),
+        BlockCacheStats {
+            hits: 8,
+            misses: 2,
+            read_ahead: 8,
+            evictions: 0,
+            backend_reads: 3,
+        }
+    );
+
+    // 跨块的随机读取直接从缓存中返回
+    let mut buf = vec![0; 3 * BLOCK_SIZE];
+    cache
+        .read_sized(&tf, &mut buf, 2500, data.len() as u64)
+        .unwrap();
+    assert_eq!(buf, &data[2500..2500 + 3 * BLOCK_SIZE]);
+    assert_eq!(reads.load(Ordering::Relaxed), 3);
+
+    // 超出文件大小的读取交给后端
+    let mut buf = vec![0; BLOCK_SIZE];
+    assert!(cache
+        .read_sized(
+            &tf,
+            &mut buf,
+            (9 * BLOCK_SIZE + 1) as u64,
+            data.len() as u64
+        )
+        .is_err());
+}
+
+#[test]
+fn test_block_cache_invalidation() {
+    let origin = tempfile::tempdir().unwrap();
+    let data = content(4);
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    let tf = tmp_file(origin.path(), "file.bin");
+    let (cache, reads) = block_cache(BlockCacheOptions {
+        block_size: BLOCK_SIZE as u64,
+        read_ahead: 0,
+        ..Default::default()
+    });
+    assert_eq!(
+        read(&cache, &tf, 1, BLOCK_SIZE),
+        &data[BLOCK_SIZE..2 * BLOCK_SIZE]
+    );
+
+    // 通过缓存写入之后读到新的内容
+    cache
+        .write(&tf, b"rfuse", &SystemTime::now(), BLOCK_SIZE as u64)
+        .unwrap();
+    assert_eq!(read(&cache, &tf, 1, 5), b"rfuse");
+    assert_eq!(reads.load(Ordering::Relaxed), 2);
+
+    // 其他客户端的修改在下一次 open 之后可见
+    fs::write(origin.path().join("file.bin"), content(2)).unwrap();
+    assert_eq!(read(&cache, &tf, 1, 5), b"rfuse");
+    cache.open(&tf, libc::O_RDONLY).unwrap();
+    assert_eq!(
+        read(&cache, &tf, 1, BLOCK_SIZE),
+        &content(2)[BLOCK_SIZE..2 * BLOCK_SIZE]
+    );
+    assert_eq!(reads.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_block_cache_growth_and_links() {
+    let origin = tempfile::tempdir().unwrap();
+    let data = content(3);
+    fs::write(origin.path().join("file.bin"), &data[..BLOCK_SIZE + 100]).unwrap();
+    fs::hard_link(
+        origin.path().join("file.bin"),
+        origin.path().join("link.bin"),
+    )
+    .unwrap();
+    let tf = tmp_file(origin.path(), "file.bin");
+    let link = tmp_file(origin.path(), "link.bin");
+    let (cache, reads) = block_cache(BlockCacheOptions {
+        block_size: BLOCK_SIZE as u64,
+        read_ahead: 0,
+        ..Default::default()
+    });
+    assert_eq!(read(&cache, &tf, 0, BLOCK_SIZE), &data[..BLOCK_SIZE]);
+    assert_eq!(
+        read(&cache, &tf, 1, 100),
+        &data[BLOCK_SIZE..BLOCK_SIZE + 100]
+    );
+    assert_eq!(reads.load(Ordering::Relaxed), 2);
+
+    // 硬链接的另一个名字使用同一个 ino 的块
+    assert_eq!(read(&cache, &link, 0, BLOCK_SIZE), &data[..BLOCK_SIZE]);
+    assert_eq!(reads.load(Ordering::Relaxed), 2);
+
+    // 服务端的文件变长之后, 按新的大小读取时只重新读取不完整的最后一个块
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    assert_eq!(
+        read(&cache, &link, 1, BLOCK_SIZE),
+        &data[BLOCK_SIZE..2 * BLOCK_SIZE]
+    );
+    assert_eq!(read(&cache, &tf, 0, BLOCK_SIZE), &data[..BLOCK_SIZE]);
+    assert_eq!(reads.load(Ordering::Relaxed), 3);
+
+    // 通过一个名字写入之后, 另一个名字读到新的内容
+    cache.write(&link, b"rfuse", &SystemTime::now(), 0).unwrap();
+    assert_eq!(read(&cache, &tf, 0, 5), b"rfuse");
+    assert_eq!(reads.load(Ordering::Relaxed), 4);
+
+    // 不在 tmp_file_map 中的文件不经过缓存
+    let temporary = TmpFile::new(
+        OsString::from("file.bin"),
+        PathBuf::from(format!("{}/", origin.path().display())),
+    );
+    read(&cache, &temporary, 0, 5);
+    read(&cache, &temporary, 0, 5);
+    assert_eq!(reads.load(Ordering::Relaxed), 6);
+    assert_eq!(cache.stats().backend_reads, 6);
+}
+
+#[test]
+fn test_block_cache_eviction() {
+    let origin = tempfile::tempdir().unwrap();
+    let disk = tempfile::tempdir().unwrap();
+    let data = content(8);
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    let tf = tmp_file(origin.path(), "file.bin");
+    let (cache, reads) = block_cache(BlockCacheOptions {
+        block_size: BLOCK_SIZE as u64,
+        memory_budget: 2 * BLOCK_SIZE as u64,
+        disk_dir: Some(disk.path().join("blocks")),
+        disk_budget: 2 * BLOCK_SIZE as u64,
+        read_ahead: 0,
+    });
+    let block_files = || fs::read_dir(disk.path().join("blocks")).unwrap().count();
+
+    // 内存中只保留最近的 2 个块, 淘汰的块写到磁盘
+    for block in [0, 2, 4] {
+        read(&cache, &tf, block, BLOCK_SIZE);
+    }
+    assert_eq!(cache.stats().evictions, 1);
+    assert_eq!(block_files(), 1);
+
+    // 磁盘中的块不需要再读取后端
+    assert_eq!(read(&cache, &tf, 0, BLOCK_SIZE), &data[..BLOCK_SIZE]);
+    assert_eq!(reads.load(Ordering::Relaxed), 3);
+
+    // 磁盘也超出预算时丢弃最久没有使用的块
+    for block in [1, 3, 5, 6] {
+        read(&cache, &tf, block, BLOCK_SIZE);
+    }
+    assert_eq!(block_files(), 2);
+    assert_eq!(
+        read(&cache, &tf, 3, BLOCK_SIZE),
+        &data[3 * BLOCK_SIZE..4 * BLOCK_SIZE]
+    );
+    assert_eq!(reads.load(Ordering::Relaxed), 7);
+    assert_eq!(read(&cache, &tf, 0, BLOCK_SIZE), &data[..BLOCK_SIZE]);
+    assert_eq!(reads.load(Ordering::Relaxed), 8);
+
+    // 释放缓存时删除磁盘上的块
+    drop(cache);
+    assert_eq!(block_files(), 0);
+}
+
+// 读取后端期间文件被写入, 读到的旧内容不能放进缓存
+#[test]
+fn test_block_cache_write_during_read() {
+    let origin = tempfile::tempdir().unwrap();
+    let data = content(2);
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    let tf = tmp_file(origin.path(), "file.bin");
+    let (reached, wait_reached) = mpsc::channel();
+    let (resume, wait_resume) = mpsc::channel();
+    let cache = BlockCache::new(
+        Box::new(Paused(Mutex::new(Some((reached, wait_resume))))),
+        BlockCacheOptions {
+            block_size: BLOCK_SIZE as u64,
+            read_ahead: 0,
+            ..Default::default()
+        },
+    );
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(|| read(&cache, &tf, 0, BLOCK_SIZE));
+        // 后端已经读到旧的内容, 还没有放进缓存
+        wait_reached.recv().unwrap();
+        cache.write(&tf, b"rfuse", &SystemTime::now(), 0).unwrap();
+        resume.send(()).unwrap();
+        // 和写入同时进行的读取可以返回旧的内容
+        assert_eq!(reader.join().unwrap(), &data[..BLOCK_SIZE]);
+    });
+
+    assert_eq!(read(&cache, &tf, 0, 5), b"rfuse");
+    assert_eq!(read(&cache, &tf, 0, BLOCK_SIZE)[5..], data[5..BLOCK_SIZE]);
+}
+
+// 源目录中的文件被修改之后, RFuseFS 处理改动事件时丢弃缓存的块
+#[test]
+fn test_block_cache_origin_modify() {
+    let origin = tempfile::tempdir().unwrap();
+    let data = content(2);
+    fs::write(origin.path().join("file.bin"), &data).unwrap();
+    let reads = Arc::new(AtomicUsize::new(0));
+    let cache = BlockCache::new(
+        Box::new(Counting(reads.clone())),
+        BlockCacheOptions {
+            block_size: BLOCK_SIZE as u64,
+            read_ahead: 0,
+            ..Default::default()
+        },
+    );
+    let mut rfs = RFuseFS::new(
+        "rfuse".to_string(),
+        CacheOptions::default(),
+        true,
+        origin.path().to_path_buf(),
+        Box::new(user_defined_init_fs),
+        Box::new(cache),
+    );
+    rfs.re_init_fs();
+    let meta = fs::metadata(origin.path()).unwrap();
+    let caller = Caller {
+        uid: meta.uid(),
+        gid: meta.gid(),
+    };
+    let ino = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.bin"))
+        .unwrap()
+        .ino;
+
+    // 读取没有打开的文件按路径进行, 第二次读取来自缓存
+    assert_eq!(rfs.read_data(ino, 0, 0, 5).unwrap(), &data[..5]);
+    assert_eq!(rfs.read_data(ino, 0, 0, 5).unwrap(), &data[..5]);
+    assert_eq!(reads.load(Ordering::Relaxed), 1);
+
+    // 大小不变的修改也会丢弃缓存的块
+    let mut modified = data.clone();
+    modified[..5].copy_from_slice(b"rfuse");
+    fs::write(origin.path().join("file.bin"), &modified).unwrap();
+    rfs.apply_origin_change(OriginChange::Modify("/file.bin".into()));
+    assert_eq!(rfs.read_data(ino, 0, 0, 5).unwrap(), b"rfuse");
+    assert_eq!(reads.load(Ordering::Relaxed), 2);
+}
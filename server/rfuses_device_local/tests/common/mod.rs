@@ -73,6 +73,21 @@ impl TestContext {
         command.arg("link");
         command
     }
+
+    #[allow(dead_code)]
+    /// Create a `rfusers_device_local serve` command with options shared across scenarios.
+    pub fn serve(&self) -> Command {
+        let mut command = Command::new(get_bin());
+        command.arg("serve");
+        command
+    }
+}
+
+#[allow(dead_code)]
+/// Returns a free local address for `rfusers_device_local serve` to listen on.
+pub fn free_addr() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind a free port");
+    listener.local_addr().unwrap().to_string()
 }
 
 /// Returns the rfusers_device_local binary that cargo built before launching the tests.
@@ -1,6 +1,6 @@
 use std::{
-    fs::{self, File},
-    os::unix::fs::MetadataExt,
+    fs::{self, DirBuilder, File, OpenOptions},
+    os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
     path::Path,
 };
 
@@ -297,3 +297,80 @@ async fn test_create_dir_in_dir() {
         closure
     );
 }
+
+// 读取当前进程的 umask, 不修改进程状态
+fn current_umask() -> u32 {
+    let status = fs::read_to_string("/proc/self/status").unwrap();
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("Umask:"))
+        .unwrap();
+    u32::from_str_radix(line["Umask:".len()..].trim(), 8).unwrap()
+}
+
+#[tokio::test]
+async fn test_create_mode_umask() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let umask = current_umask();
+
+        // 创建文件时使用 mode & !umask 作为权限
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_create_mode.txt");
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o754)
+            .open(&test_file_mount)
+            .unwrap();
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push("test_create_mode.txt");
+        let file_mode = fs::metadata(&test_file_mount).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(file_mode, 0o754 & !umask);
+        assert_eq!(
+            fs::metadata(&test_file_origin)
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777,
+            file_mode
+        );
+
+        // O_EXCL 时文件已经存在返回 EEXIST
+        let err = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&test_file_mount)
+            .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+
+        // 创建文件夹时同样去掉 umask
+        let mut test_dir_mount = mount_path.clone();
+        test_dir_mount.push("test_create_mode_dir");
+        DirBuilder::new()
+            .mode(0o751)
+            .create(&test_dir_mount)
+            .unwrap();
+        let mut test_dir_origin = origin_path.clone();
+        test_dir_origin.push("test_create_mode_dir");
+        let dir_mode = fs::metadata(&test_dir_mount).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(dir_mode, 0o751 & !umask);
+        assert_eq!(
+            fs::metadata(&test_dir_origin).unwrap().permissions().mode() & 0o7777,
+            dir_mode
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
@@ -283,3 +283,42 @@ async fn test_clean_file_content() {
         closure
     );
 }
+
+#[tokio::test]
+async fn test_del_stale_dir() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 创建空的测试文件夹
+    let test_dir = "test_del_stale_dir";
+    let test_dir_origin = origin_path.join(test_dir);
+    fs::create_dir(&test_dir_origin).unwrap();
+
+    let closure = || {
+        let test_dir_mount = mount_path.join(test_dir);
+        // 挂载之后文件夹是空的
+        assert_eq!(fs::read_dir(&test_dir_mount).unwrap().count(), 0);
+
+        // 绕过挂载目录, 直接在原始目录中添加文件
+        let test_file_origin = test_dir_origin.join("test_del_stale.txt");
+        fs::write(&test_file_origin, "Hello, World!").unwrap();
+
+        // 删除文件夹失败, 原始目录中的文件不会被删除
+        let result = fs::remove_dir(&test_dir_mount);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::ENOTEMPTY));
+        assert!(Path::new(&test_file_origin).exists());
+        assert!(Path::new(&test_dir_origin).exists());
+    };
+
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
@@ -0,0 +1,1330 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use fuser::FUSE_ROOT_ID;
+use rfuse_core::{
+    async_tmp_file::{AsyncTmpFileTrait, BackendFuture, SyncAdapter},
+    common::{RFUSE_ENOATTR, RFUSE_RENAME_EXCHANGE, RFUSE_RENAME_NOREPLACE},
+    notify::OriginChange,
+    remote_fs::RemoteFileManager,
+    sys_fs::{CacheOptions, Caller, RFuseFS, SetAttr},
+    tmp_file::{FileHandle, StatFs, TmpFile, TmpFileError, TmpFileTrait},
+    worker::WorkerPool,
+};
+use rfuse_device_disk::mem_disk::MemDisk;
+use rfuses_device_local::{
+    init_fs::{mem_defined_init_fs, user_defined_init_fs},
+    local_fs::LocalFS,
+    mem_fs::MemFS,
+};
+
+// 内核中不存在的 inode, 例如重新初始化之前留下的 ino
+const STALE_INO: u64 = u64::MAX - 1;
+
+// 不挂载, 直接使用源目录初始化 inode 表
+fn local_fs(origin: &std::path::Path) -> RFuseFS {
+    let mut rfs = RFuseFS::new(
+        "rfuse".to_string(),
+        CacheOptions::default(),
+        true,
+        origin.to_path_buf(),
+        Box::new(user_defined_init_fs),
+        Box::new(LocalFS),
+    );
+    rfs.re_init_fs();
+    rfs.ensure_loaded(FUSE_ROOT_ID).unwrap();
+    rfs
+}
+
+// 不挂载, 使用容量为 capacity 字节的内存磁盘
+fn mem_fs(capacity: u64) -> (RFuseFS, Arc<MemDisk>) {
+    let disk = Arc::new(MemDisk::new(0o755, 0, 0, capacity));
+    let mut rfs = RFuseFS::new(
+        "rfuse".to_string(),
+        CacheOptions::default(),
+        true,
+        std::path::PathBuf::new(),
+        mem_defined_init_fs(disk.clone()),
+        Box::new(MemFS(disk.clone())),
+    );
+    rfs.re_init_fs();
+    rfs.ensure_loaded(FUSE_ROOT_ID).unwrap();
+    (rfs, disk)
+}
+
+// 源目录的所有者
+fn owner(origin: &std::path::Path) -> Caller {
+    let meta = fs::metadata(origin).unwrap();
+    Caller {
+        uid: meta.uid(),
+        gid: meta.gid(),
+    }
+}
+
+#[test]
+fn test_handlers_unknown_ino() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("file.txt"), "rfuse").unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let name = OsStr::new("new.txt");
+
+    assert_eq!(
+        rfs.lookup_entry(caller, STALE_INO, OsStr::new("file.txt"))
+            .unwrap_err(),
+        libc::ENOENT
+    );
+    let changes = SetAttr {
+        mode: Some(0o644),
+        ..Default::default()
+    };
+    assert_eq!(
+        rfs.set_attr(caller, STALE_INO, changes).unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.read_dir_entries(STALE_INO, 0).unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.check_permission(caller, STALE_INO, libc::R_OK)
+            .unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.make_dir(caller, STALE_INO, name, 0o755, 0o022)
+            .unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.create_file(caller, STALE_INO, name, 0o644, 0o022, libc::O_RDWR)
+            .unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.read_data(STALE_INO, 0, 0, 16).unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(
+        rfs.write_data(STALE_INO, 0, 0, b"rfuse").unwrap_err(),
+        libc::ENOENT
+    );
+    assert!(!origin.path().join(name).exists());
+
+    // 普通文件不能作为父文件夹
+    let file = rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"));
+    let file = file.unwrap().ino;
+    assert_eq!(
+        rfs.lookup_entry(caller, file, name).unwrap_err(),
+        libc::ENOTDIR
+    );
+    assert_eq!(
+        rfs.make_dir(caller, file, name, 0o755, 0o022).unwrap_err(),
+        libc::ENOTDIR
+    );
+    assert_eq!(rfs.read_dir_entries(file, 0).unwrap_err(), libc::ENOTDIR);
+
+    // 出错之后文件系统仍然可以正常使用
+    let entries = rfs.read_dir_entries(FUSE_ROOT_ID, 0).unwrap();
+    assert_eq!(entries.len(), 3);
+    rfs.verify_tree().unwrap();
+}
+
+#[test]
+fn test_handlers_negative_offset() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("file.txt"), "rfuse").unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    assert_eq!(
+        rfs.read_data(attr.ino, fh, -1, 16).unwrap_err(),
+        libc::EINVAL
+    );
+    assert_eq!(
+        rfs.write_data(attr.ino, fh, -1, b"rfuse").unwrap_err(),
+        libc::EINVAL
+    );
+    assert_eq!(
+        rfs.read_dir_entries(FUSE_ROOT_ID, -1).unwrap_err(),
+        libc::EINVAL
+    );
+
+    // 偏移量超过文件末尾时读到空数据
+    assert_eq!(rfs.read_data(attr.ino, fh, 0, 16).unwrap(), b"rfuse");
+    assert!(rfs.read_data(attr.ino, fh, 1024, 16).unwrap().is_empty());
+    assert_eq!(fs::read(origin.path().join("file.txt")).unwrap(), b"rfuse");
+}
+
+#[test]
+fn test_handlers_origin_errno() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::create_dir(origin.path().join("dir")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let dir = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+        .unwrap()
+        .ino;
+
+    // 源目录中的改动还没有同步到 inode 表, 错误码来自源目录
+    fs::create_dir(origin.path().join("late")).unwrap();
+    assert_eq!(
+        rfs.make_dir(caller, FUSE_ROOT_ID, OsStr::new("late"), 0o755, 0o022)
+            .unwrap_err(),
+        libc::EEXIST
+    );
+    fs::remove_dir(origin.path().join("dir")).unwrap();
+    assert_eq!(
+        rfs.create_file(
+            caller,
+            dir,
+            OsStr::new("new.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR
+        )
+        .unwrap_err(),
+        libc::ENOENT
+    );
+
+    // 不存在的文件返回 ENOENT 而不是 EIO
+    let mut dir_path = origin.path().as_os_str().to_os_string();
+    dir_path.push("/");
+    assert_eq!(
+        LocalFS
+            .get_attr(&TmpFile::new(
+                OsString::from("dir"),
+                PathBuf::from(dir_path)
+            ))
+            .unwrap_err(),
+        TmpFileError::Errno(libc::ENOENT)
+    );
+}
+
+// 测试结束时卸载挂载在源目录中的文件系统
+struct Unmount(PathBuf);
+
+impl Drop for Unmount {
+    fn drop(&mut self) {
+        let _ = Command::new("umount").arg(&self.0).status();
+    }
+}
+
+// 挂载 tmpfs, 没有权限时返回 None
+fn mount_tmpfs(path: &Path) -> Option<Unmount> {
+    fs::create_dir(path).unwrap();
+    let status = Command::new("mount")
+        .args(["-t", "tmpfs", "rfuse"])
+        .arg(path)
+        .stderr(std::process::Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => Some(Unmount(path.to_path_buf())),
+        _ => None,
+    }
+}
+
+// 源目录中挂载的其他文件系统的 inode 号可能和源目录中的文件重复, 所以不进入
+#[test]
+fn test_handlers_skip_mount_point() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("file.txt"), "rfuse").unwrap();
+    let _mount = match mount_tmpfs(&origin.path().join("mnt")) {
+        Some(mount) => mount,
+        None => {
+            eprintln!("skip test_handlers_skip_mount_point: can not mount tmpfs");
+            return;
+        }
+    };
+    fs::write(origin.path().join("mnt/inner.txt"), "rfuse").unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+
+    let names: Vec<OsString> = rfs
+        .read_dir_entries(FUSE_ROOT_ID, 0)
+        .unwrap()
+        .into_iter()
+        .map(|(_, _, _, name)| name)
+        .collect();
+    assert!(names.contains(&OsString::from("file.txt")));
+    assert!(!names.contains(&OsString::from("mnt")));
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("mnt"))
+            .unwrap_err(),
+        libc::ENOENT
+    );
+
+    // 源目录的改动事件也不会把挂载点加入 inode 表
+    rfs.apply_origin_change(OriginChange::Create("/mnt".into()));
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("mnt"))
+            .unwrap_err(),
+        libc::ENOENT
+    );
+    assert!(rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"))
+        .is_ok());
+}
+
+#[test]
+fn test_handlers_rename_flags() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("a.txt"), "a").unwrap();
+    fs::write(origin.path().join("b.txt"), "b").unwrap();
+    fs::write(origin.path().join("c.txt"), "c").unwrap();
+    fs::create_dir_all(origin.path().join("dir/sub")).unwrap();
+    fs::create_dir(origin.path().join("empty")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let root = FUSE_ROOT_ID;
+    let lookup = |rfs: &mut RFuseFS, parent, name: &str| {
+        rfs.lookup_entry(caller, parent, OsStr::new(name))
+            .map(|attr| attr.ino)
+    };
+    let a = lookup(&mut rfs, root, "a.txt").unwrap();
+    let b = lookup(&mut rfs, root, "b.txt").unwrap();
+    let dir = lookup(&mut rfs, root, "dir").unwrap();
+    let sub = lookup(&mut rfs, dir, "sub").unwrap();
+
+    // 不覆盖模式下目标已经存在
+    assert_eq!(
+        rfs.rename_entry(
+            caller,
+            root,
+            OsStr::new("a.txt"),
+            root,
+            OsStr::new("b.txt"),
+            RFUSE_RENAME_NOREPLACE
+        )
+        .unwrap_err(),
+        libc::EEXIST
+    );
+    assert_eq!(fs::read(origin.path().join("b.txt")).unwrap(), b"b");
+
+    // 交换两个文件
+    rfs.rename_entry(
+        caller,
+        root,
+        OsStr::new("a.txt"),
+        root,
+        OsStr::new("b.txt"),
+        RFUSE_RENAME_EXCHANGE,
+    )
+    .unwrap();
+    assert_eq!(fs::read(origin.path().join("a.txt")).unwrap(), b"b");
+    assert_eq!(fs::read(origin.path().join("b.txt")).unwrap(), b"a");
+    assert_eq!(lookup(&mut rfs, root, "a.txt").unwrap(), b);
+    assert_eq!(lookup(&mut rfs, root, "b.txt").unwrap(), a);
+    assert_eq!(
+        rfs.rename_entry(
+            caller,
+            root,
+            OsStr::new("a.txt"),
+            root,
+            OsStr::new("missing"),
+            RFUSE_RENAME_EXCHANGE
+        )
+        .unwrap_err(),
+        libc::ENOENT
+    );
+
+    // 默认覆盖已经存在的文件
+    rfs.rename_entry(
+        caller,
+        root,
+        OsStr::new("c.txt"),
+        root,
+        OsStr::new("a.txt"),
+        0,
+    )
+    .unwrap();
+    assert_eq!(fs::read(origin.path().join("a.txt")).unwrap(), b"c");
+    assert!(!origin.path().join("c.txt").exists());
+    assert_eq!(lookup(&mut rfs, root, "c.txt").unwrap_err(), libc::ENOENT);
+
+    // 覆盖时类型不一致, 或者目标文件夹不为空
+    let cases = [
+        ("dir", "a.txt", libc::ENOTDIR),
+        ("a.txt", "empty", libc::EISDIR),
+        ("empty", "dir", libc::ENOTEMPTY),
+    ];
+    for (from, to, errno) in cases {
+        assert_eq!(
+            rfs.rename_entry(caller, root, OsStr::new(from), root, OsStr::new(to), 0)
+                .unwrap_err(),
+            errno
+        );
+    }
+    // 文件夹不能移动到自己的子孙下
+    assert_eq!(
+        rfs.rename_entry(caller, root, OsStr::new("dir"), sub, OsStr::new("dir"), 0)
+            .unwrap_err(),
+        libc::EINVAL
+    );
+    assert_eq!(
+        rfs.rename_entry(
+            caller,
+            root,
+            OsStr::new("a.txt"),
+            root,
+            OsStr::new("b.txt"),
+            RFUSE_RENAME_EXCHANGE | RFUSE_RENAME_NOREPLACE
+        )
+        .unwrap_err(),
+        libc::EINVAL
+    );
+
+    // 文件夹可以覆盖空文件夹
+    rfs.rename_entry(caller, dir, OsStr::new("sub"), root, OsStr::new("empty"), 0)
+        .unwrap();
+    assert!(origin.path().join("empty").is_dir());
+    assert!(!origin.path().join("dir/sub").exists());
+    assert_eq!(lookup(&mut rfs, root, "empty").unwrap(), sub);
+    rfs.verify_tree().unwrap();
+}
+
+#[test]
+fn test_handlers_rename_populated_dir() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::create_dir_all(origin.path().join("a/sub")).unwrap();
+    fs::write(origin.path().join("a/sub/nested.txt"), "a").unwrap();
+    fs::create_dir_all(origin.path().join("b/sub")).unwrap();
+    fs::write(origin.path().join("b/sub/nested.txt"), "b").unwrap();
+    fs::create_dir(origin.path().join("target")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let lookup = |rfs: &mut RFuseFS, parent, name: &str| {
+        rfs.lookup_entry(caller, parent, OsStr::new(name))
+            .unwrap()
+            .ino
+    };
+    // 加载所有子孙节点
+    let mut nested = Vec::new();
+    for dir in ["a", "b"] {
+        let dir = lookup(&mut rfs, FUSE_ROOT_ID, dir);
+        let sub = lookup(&mut rfs, dir, "sub");
+        nested.push(lookup(&mut rfs, sub, "nested.txt"));
+    }
+    let target = lookup(&mut rfs, FUSE_ROOT_ID, "target");
+
+    // 移动到其他文件夹下, 子孙文件跟着换到新的路径
+    rfs.rename_entry(
+        caller,
+        FUSE_ROOT_ID,
+        OsStr::new("a"),
+        target,
+        OsStr::new("moved"),
+        0,
+    )
+    .unwrap();
+    rfs.verify_tree().unwrap();
+    assert_eq!(rfs.read_data(nested[0], 0, 0, 16).unwrap(), b"a");
+    let moved = lookup(&mut rfs, target, "moved");
+    let sub = lookup(&mut rfs, moved, "sub");
+    rfs.make_dir(caller, sub, OsStr::new("new"), 0o755, 0o022)
+        .unwrap();
+    assert!(origin.path().join("target/moved/sub/new").is_dir());
+
+    // 交换两个文件夹, 两边的子孙都要更新
+    rfs.rename_entry(
+        caller,
+        target,
+        OsStr::new("moved"),
+        FUSE_ROOT_ID,
+        OsStr::new("b"),
+        RFUSE_RENAME_EXCHANGE,
+    )
+    .unwrap();
+    rfs.verify_tree().unwrap();
+    assert_eq!(rfs.read_data(nested[0], 0, 0, 16).unwrap(), b"a");
+    assert_eq!(rfs.read_data(nested[1], 0, 0, 16).unwrap(), b"b");
+    assert_eq!(
+        fs::read(origin.path().join("target/moved/sub/nested.txt")).unwrap(),
+        b"b"
+    );
+    assert!(origin.path().join("b/sub/new").is_dir());
+}
+
+// 读取文件夹失败时返回后端的错误码, 而不是当作不存在或者空文件夹
+#[test]
+fn test_handlers_load_dir_error() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::create_dir(origin.path().join("dir")).unwrap();
+    fs::write(origin.path().join("dir/file.txt"), "rfuse").unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let dir = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+        .unwrap()
+        .ino;
+
+    // 文件夹还没有读取时在源目录中被替换成了文件
+    fs::remove_dir_all(origin.path().join("dir")).unwrap();
+    fs::write(origin.path().join("dir"), "rfuse").unwrap();
+    assert_eq!(
+        rfs.lookup_entry(caller, dir, OsStr::new("file.txt"))
+            .unwrap_err(),
+        libc::ENOTDIR
+    );
+    assert_eq!(rfs.read_dir_entries(dir, 0).unwrap_err(), libc::ENOTDIR);
+    assert!(!rfs.get_inode(dir).unwrap().loaded);
+
+    // 读取失败的文件夹在下次访问时重新读取
+    fs::remove_file(origin.path().join("dir")).unwrap();
+    fs::create_dir(origin.path().join("dir")).unwrap();
+    fs::write(origin.path().join("dir/file.txt"), "rfuse").unwrap();
+    assert!(rfs
+        .lookup_entry(caller, dir, OsStr::new("file.txt"))
+        .is_ok());
+}
+
+#[test]
+fn test_handlers_rmdir_stale_dir() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::create_dir(origin.path().join("dir")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let dir = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+        .unwrap()
+        .ino;
+    rfs.ensure_loaded(dir).unwrap();
+
+    // 加载之后源目录中又多了文件, inode 表中的文件夹仍然是空的
+    fs::write(origin.path().join("dir/late.txt"), "rfuse").unwrap();
+    assert_eq!(
+        rfs.remove_dir_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+            .unwrap_err(),
+        libc::ENOTEMPTY
+    );
+    assert_eq!(
+        fs::read(origin.path().join("dir/late.txt")).unwrap(),
+        b"rfuse"
+    );
+    // 删除失败时 inode 表保持不变
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+            .unwrap()
+            .ino,
+        dir
+    );
+    rfs.verify_tree().unwrap();
+
+    fs::remove_file(origin.path().join("dir/late.txt")).unwrap();
+    rfs.remove_dir_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+        .unwrap();
+    assert!(!origin.path().join("dir").exists());
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+            .unwrap_err(),
+        libc::ENOENT
+    );
+}
+
+#[test]
+fn test_handlers_readdir_offsets() {
+    let origin = tempfile::tempdir().unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    for name in ["d0", "d1", "d2", "d3", "d4"] {
+        rfs.make_dir(caller, FUSE_ROOT_ID, OsStr::new(name), 0o755, 0o022)
+            .unwrap();
+    }
+    let names = |entries: &[(i64, u64, fuser::FileType, std::ffi::OsString)]| {
+        entries
+            .iter()
+            .map(|(_, _, _, name)| name.to_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // 第一次只读取前 4 个目录项
+    let first = rfs.read_dir_entries(FUSE_ROOT_ID, 0).unwrap();
+    assert_eq!(names(&first), [".", "..", "d0", "d1", "d2", "d3", "d4"]);
+    assert_eq!(first[1].1, FUSE_ROOT_ID);
+    let offsets = first.iter().map(|entry| entry.0).collect::<Vec<_>>();
+    assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+    let last = first[3].0;
+
+    // 读取过程中删除已经返回和还没返回的目录项, 并新建一个目录项
+    rfs.remove_dir_entry(caller, FUSE_ROOT_ID, OsStr::new("d0"))
+        .unwrap();
+    rfs.remove_dir_entry(caller, FUSE_ROOT_ID, OsStr::new("d3"))
+        .unwrap();
+    rfs.make_dir(caller, FUSE_ROOT_ID, OsStr::new("late"), 0o755, 0o022)
+        .unwrap();
+
+    // 从上次的偏移继续读取, 不重复也不遗漏仍然存在的目录项
+    let rest = rfs.read_dir_entries(FUSE_ROOT_ID, last).unwrap();
+    assert_eq!(names(&rest), ["d2", "d4", "late"]);
+    assert_eq!(rest[0].0, first[4].0);
+    assert_eq!(rest[1].0, first[6].0);
+
+    // 剩下的目录项偏移保持不变, 按名字查找仍然有效
+    let all = rfs.read_dir_entries(FUSE_ROOT_ID, 0).unwrap();
+    assert_eq!(names(&all), [".", "..", "d1", "d2", "d4", "late"]);
+    assert_eq!(all[2].0, first[3].0);
+    for (_, ino, _, name) in &all[2..] {
+        assert_eq!(
+            rfs.lookup_entry(caller, FUSE_ROOT_ID, name).unwrap().ino,
+            *ino
+        );
+    }
+    assert!(rfs
+        .read_dir_entries(FUSE_ROOT_ID, all.last().unwrap().0)
+        .unwrap()
+        .is_empty());
+
+    // 子文件夹的 `..` 指向父文件夹
+    let (_, d1, ..) = &all[2];
+    let sub = rfs.read_dir_entries(*d1, 0).unwrap();
+    assert_eq!(names(&sub), [".", ".."]);
+    assert_eq!(sub[0].1, *d1);
+    assert_eq!(sub[1].1, FUSE_ROOT_ID);
+    rfs.verify_tree().unwrap();
+}
+
+#[test]
+fn test_handlers_worker_io() {
+    let origin = tempfile::tempdir().unwrap();
+    for index in 0..8 {
+        fs::write(
+            origin.path().join(format!("file_{}.txt", index)),
+            format!("rfuse {}", index),
+        )
+        .unwrap();
+    }
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let workers = WorkerPool::new(4);
+    assert_eq!(workers.size(), 4);
+
+    // 读取任务在工作线程中执行, 请求线程同时继续修改 inode 表
+    let (send, recv) = mpsc::channel();
+    for index in 0..8 {
+        let name = format!("file_{}.txt", index);
+        let ino = rfs
+            .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new(&name))
+            .unwrap()
+            .ino;
+        let task = rfs.read_task(ino, 0, 0, 4096).unwrap();
+        let send = send.clone();
+        workers.execute(move || send.send((index, task.run())).unwrap());
+    }
+    rfs.make_dir(caller, FUSE_ROOT_ID, OsStr::new("dir"), 0o755, 0o022)
+        .unwrap();
+    drop(send);
+    let mut results = recv.iter().collect::<Vec<_>>();
+    results.sort_by_key(|(index, _)| *index);
+    assert_eq!(results.len(), 8);
+    for (index, data) in results {
+        assert_eq!(data.unwrap(), format!("rfuse {}", index).as_bytes());
+    }
+
+    // 写入任务完成后, 之后的请求能看到新的文件大小
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("new.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    let (send, recv) = mpsc::channel();
+    for index in 0..4 {
+        let data = [b'0' + index as u8; 4];
+        let task = rfs.write_task(attr.ino, fh, index * 4, &data).unwrap();
+        let send = send.clone();
+        workers.execute(move || send.send(task.run()).unwrap());
+    }
+    drop(send);
+    assert!(recv.iter().all(|written| written == Ok(4)));
+    let attr = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("new.txt"))
+        .unwrap();
+    assert_eq!(attr.size, 16);
+    assert_eq!(
+        rfs.read_data(attr.ino, fh, 0, 16).unwrap(),
+        b"0000111122223333"
+    );
+    assert_eq!(
+        fs::read(origin.path().join("new.txt")).unwrap(),
+        b"0000111122223333"
+    );
+
+    // 释放线程池时会等待剩下的任务执行完
+    let (send, recv) = mpsc::channel();
+    for index in 0..16 {
+        let send = send.clone();
+        workers.execute(move || send.send(index).unwrap());
+    }
+    drop(send);
+    drop(workers);
+    assert_eq!(recv.try_iter().count(), 16);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handlers_async_backend() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("file.txt"), "rfuse").unwrap();
+    std::os::unix::fs::symlink("file.txt", origin.path().join("link")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let backend: Arc<dyn AsyncTmpFileTrait> = Arc::new(SyncAdapter(Arc::new(LocalFS)));
+    let ino = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"))
+        .unwrap()
+        .ino;
+
+    // 没有后端句柄时按路径读写, 交给异步后端
+    let read = rfs.read_task(ino, 0, 0, 4096).unwrap().into_async(&backend);
+    assert_eq!(read.await.unwrap(), b"rfuse");
+
+    let write = rfs
+        .write_task(ino, 0, 5, b" async")
+        .unwrap()
+        .into_async(&backend);
+    assert_eq!(write.await.unwrap(), 6);
+    // 写入完成后的请求能看到新的文件大小
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"))
+            .unwrap()
+            .size,
+        11
+    );
+    assert_eq!(
+        fs::read(origin.path().join("file.txt")).unwrap(),
+        b"rfuse async"
+    );
+
+    // 有后端句柄的读写也交给异步后端
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("new.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    let write = rfs
+        .write_task(attr.ino, fh, 0, b"rfuse")
+        .unwrap()
+        .into_async(&backend);
+    assert_eq!(write.await.unwrap(), 5);
+    let read = rfs
+        .read_task(attr.ino, fh, 0, 5)
+        .unwrap()
+        .into_async(&backend);
+    assert_eq!(read.await.unwrap(), b"rfuse");
+
+    // 源文件不存在时返回原始的错误码
+    // TmpFile 的 path 是以 `/` 结尾的所在文件夹
+    let dir = std::path::PathBuf::from(format!("{}/", origin.path().display()));
+    let missing = Arc::new(TmpFile::new(OsString::from("missing.txt"), dir.clone()));
+    assert_eq!(
+        backend.read(missing, 0, 1, 1).await.unwrap_err().errno(),
+        libc::ENOENT
+    );
+    let link = Arc::new(TmpFile::new(OsString::from("link"), dir));
+    assert_eq!(
+        backend.read_link(link).await.unwrap(),
+        std::path::Path::new("file.txt")
+    );
+}
+
+#[test]
+fn test_handlers_mem_capacity() {
+    let caller = Caller { uid: 0, gid: 0 };
+    let (mut rfs, disk) = mem_fs(4096);
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.bin"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    let size = |size| SetAttr {
+        size: Some(size),
+        ..Default::default()
+    };
+
+    // 单个文件超出容量时返回 EFBIG, 文件大小保持不变
+    assert_eq!(
+        rfs.write_data(attr.ino, fh, 1 << 50, b"rfuse").unwrap_err(),
+        libc::EFBIG
+    );
+    assert_eq!(
+        rfs.write_data(attr.ino, fh, i64::MAX, b"rfuse")
+            .unwrap_err(),
+        libc::EFBIG
+    );
+    assert_eq!(
+        rfs.set_attr(caller, attr.ino, size(1 << 50)).unwrap_err(),
+        libc::EFBIG
+    );
+    assert_eq!(rfs.get_inode(attr.ino).unwrap().attr.size, 0);
+    assert_eq!(disk.used(), 0);
+
+    // 所有文件加起来超出容量时返回 ENOSPC
+    assert_eq!(rfs.write_data(attr.ino, fh, 0, &[1; 3000]), Ok(3000));
+    let (other, other_fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("other.bin"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    assert_eq!(
+        rfs.write_data(other.ino, other_fh, 0, &[2; 2000])
+            .unwrap_err(),
+        libc::ENOSPC
+    );
+    assert_eq!(
+        rfs.set_attr(caller, other.ino, size(2000)).unwrap_err(),
+        libc::ENOSPC
+    );
+
+    // 截断和删除之后释放容量
+    rfs.set_attr(caller, attr.ino, size(1000)).unwrap();
+    assert_eq!(disk.used(), 1000);
+    assert_eq!(rfs.write_data(other.ino, other_fh, 0, &[2; 2000]), Ok(2000));
+    assert_eq!(disk.used(), 3000);
+
+    // 内存磁盘不支持硬链接
+    let tf = TmpFile::new(OsString::from("file.bin"), std::path::PathBuf::from("/"));
+    assert_eq!(
+        MemFS(disk)
+            .link(&tf, std::path::Path::new("/link.bin"))
+            .unwrap_err()
+            .errno(),
+        libc::EPERM
+    );
+}
+
+#[test]
+fn test_handlers_mem_short_read() {
+    let caller = Caller { uid: 0, gid: 0 };
+    let (mut rfs, disk) = mem_fs(4096);
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.bin"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    assert_eq!(rfs.write_data(attr.ino, fh, 0, b"rfuse"), Ok(5));
+
+    // 每个内存磁盘单独分配 ino, 不受其他内存磁盘的影响
+    let (mut other_rfs, _other_disk) = mem_fs(4096);
+    let (other, _) = other_rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.bin"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    assert_eq!(other.ino, attr.ino);
+
+    // inode 表中的大小还没有更新时, 读到文件末尾返回较短的数据而不是 EIO
+    let tf = TmpFile::new(OsString::from("file.bin"), std::path::PathBuf::from("/"));
+    let mut truncated = rfs.get_inode(attr.ino).unwrap().attr.clone();
+    truncated.size = 2;
+    MemFS(disk.clone()).set_attr(&tf, &truncated).unwrap();
+    assert_eq!(rfs.read_data(attr.ino, fh, 0, 10).unwrap(), b"rf");
+    assert_eq!(rfs.read_data(attr.ino, fh, 4, 10).unwrap(), b"");
+    let mut buf = [0; 4];
+    assert_eq!(MemFS(disk).read_sized(&tf, &mut buf, 1, 5), Ok(1));
+    assert_eq!(buf[0], b'f');
+}
+
+// 文件在 inode 表之外被删除之后, 扩展属性操作返回 ENOENT 而不是 EIO
+#[test]
+fn test_handlers_mem_xattr_unlinked() {
+    let caller = Caller { uid: 0, gid: 0 };
+    let (mut rfs, disk) = mem_fs(4096);
+    let (attr, _) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    let name = OsStr::new("user.rfuse");
+    rfs.set_xattr(caller, attr.ino, name, b"rfuse", 0).unwrap();
+
+    let tf = TmpFile::new(OsString::from("file.txt"), std::path::PathBuf::from("/"));
+    disk.remove_file(&tf, &SystemTime::now()).unwrap();
+    assert_eq!(
+        disk.get_xattr(&tf, "user.rfuse"),
+        Err(TmpFileError::Errno(libc::ENOENT))
+    );
+    assert_eq!(rfs.get_xattr(attr.ino, name), Err(libc::ENOENT));
+    assert_eq!(
+        rfs.set_xattr(caller, attr.ino, name, b"rfuse", 0),
+        Err(libc::ENOENT)
+    );
+    assert_eq!(rfs.list_xattr(attr.ino), Err(libc::ENOENT));
+    assert_eq!(rfs.remove_xattr(caller, attr.ino, name), Err(libc::ENOENT));
+}
+
+#[test]
+fn test_handlers_root_xattr() {
+    let caller = Caller { uid: 0, gid: 0 };
+    let (rfs, _disk) = mem_fs(4096);
+    let name = OsStr::new("user.rfuse.root");
+
+    // 挂载根目录上的扩展属性和其他文件一样可以读写
+    rfs.set_xattr(caller, FUSE_ROOT_ID, name, b"root", 0)
+        .unwrap();
+    assert_eq!(rfs.get_xattr(FUSE_ROOT_ID, name).unwrap(), b"root");
+    assert_eq!(rfs.list_xattr(FUSE_ROOT_ID).unwrap(), b"user.rfuse.root\0");
+    rfs.remove_xattr(caller, FUSE_ROOT_ID, name).unwrap();
+    assert_eq!(
+        rfs.get_xattr(FUSE_ROOT_ID, name).unwrap_err(),
+        RFUSE_ENOATTR
+    );
+
+    // 不存在的 inode 返回 ENOENT
+    assert_eq!(rfs.get_xattr(STALE_INO, name).unwrap_err(), libc::ENOENT);
+    assert_eq!(
+        rfs.set_xattr(caller, STALE_INO, name, b"root", 0)
+            .unwrap_err(),
+        libc::ENOENT
+    );
+}
+
+#[test]
+fn test_handlers_unlink_open_file() {
+    let origin = tempfile::tempdir().unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let name = OsStr::new("open.txt");
+    let (attr, fh) = rfs
+        .create_file(caller, FUSE_ROOT_ID, name, 0o644, 0o022, libc::O_RDWR)
+        .unwrap();
+    assert_eq!(rfs.write_data(attr.ino, fh, 0, b"rfuse"), Ok(5));
+
+    // 删除之后名字消失, 但打开的句柄仍然可以读写
+    rfs.unlink_entry(caller, FUSE_ROOT_ID, name).unwrap();
+    assert!(!origin.path().join(name).exists());
+    assert_eq!(
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, name).unwrap_err(),
+        libc::ENOENT
+    );
+    assert_eq!(rfs.read_data(attr.ino, fh, 0, 16).unwrap(), b"rfuse");
+    assert_eq!(rfs.write_data(attr.ino, fh, 5, b"!"), Ok(1));
+    assert_eq!(rfs.read_data(attr.ino, fh, 0, 16).unwrap(), b"rfuse!");
+    assert_eq!(rfs.get_inode(attr.ino).unwrap().nlink, 0);
+    rfs.verify_tree().unwrap();
+
+    // 最后一个句柄释放之后 inode 才被移除
+    rfs.release_handle(fh);
+    assert!(rfs.get_inode(attr.ino).is_none());
+    rfs.verify_tree().unwrap();
+
+    // 没有打开的文件删除时直接移除
+    let (attr, fh) = rfs
+        .create_file(caller, FUSE_ROOT_ID, name, 0o644, 0o022, libc::O_RDWR)
+        .unwrap();
+    rfs.release_handle(fh);
+    rfs.unlink_entry(caller, FUSE_ROOT_ID, name).unwrap();
+    assert!(rfs.get_inode(attr.ino).is_none());
+    rfs.verify_tree().unwrap();
+}
+
+#[test]
+fn test_handlers_re_init_twice() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("file.txt"), "rfuse").unwrap();
+    let caller = owner(origin.path());
+    // local_fs 已经初始化过一次
+    let mut rfs = local_fs(origin.path());
+
+    // 再次初始化时使用同一个初始化函数, 根目录和文件仍然可以访问
+    for _ in 0..2 {
+        rfs.re_init_fs();
+        assert!(rfs
+            .get_inode(FUSE_ROOT_ID)
+            .is_some_and(|root| root.is_dir()));
+        let attr = rfs
+            .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"))
+            .unwrap();
+        assert_eq!(rfs.read_data(attr.ino, 0, 0, 16).unwrap(), b"rfuse");
+        rfs.verify_tree().unwrap();
+    }
+}
+
+#[test]
+fn test_handlers_unloaded_parent() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::create_dir(origin.path().join("dir")).unwrap();
+    fs::write(origin.path().join("dir/file.txt"), "rfuse").unwrap();
+    fs::create_dir(origin.path().join("dir/sub")).unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    // lookup 只读取根目录, dir 还没有读取
+    let dir = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("dir"))
+        .unwrap()
+        .ino;
+
+    // 源目录中已经存在的名字不会被覆盖
+    assert_eq!(
+        rfs.make_dir(caller, dir, OsStr::new("file.txt"), 0o755, 0)
+            .unwrap_err(),
+        libc::EEXIST
+    );
+    assert_eq!(
+        rfs.create_file(
+            caller,
+            dir,
+            OsStr::new("file.txt"),
+            0o644,
+            0,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL,
+        )
+        .unwrap_err(),
+        libc::EEXIST
+    );
+    assert_eq!(
+        fs::read(origin.path().join("dir/file.txt")).unwrap(),
+        b"rfuse"
+    );
+
+    // 删除父文件夹还没有读取时的文件和文件夹
+    rfs.unlink_entry(caller, dir, OsStr::new("file.txt"))
+        .unwrap();
+    rfs.remove_dir_entry(caller, dir, OsStr::new("sub"))
+        .unwrap();
+    assert!(!origin.path().join("dir/file.txt").exists());
+    assert!(!origin.path().join("dir/sub").exists());
+    rfs.verify_tree().unwrap();
+}
+
+#[test]
+fn test_handlers_append_tasks() {
+    let origin = tempfile::tempdir().unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("append.txt"),
+            0o644,
+            0o022,
+            libc::O_WRONLY | libc::O_APPEND,
+        )
+        .unwrap();
+
+    // 追加写在准备时依次预留文件末尾的位置, 不需要等前一次写入完成
+    let tasks =
+        [b"aaaa", b"bbbb", b"cccc"].map(|data| rfs.write_task(attr.ino, fh, 0, data).unwrap());
+    for task in tasks.into_iter().rev() {
+        assert_eq!(task.run(), Ok(4));
+    }
+    assert_eq!(
+        fs::read(origin.path().join("append.txt")).unwrap(),
+        b"aaaabbbbcccc"
+    );
+    let attr = rfs
+        .lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("append.txt"))
+        .unwrap();
+    assert_eq!(attr.size, 12);
+
+    // 截断之后从新的文件末尾继续追加
+    let task = rfs.write_task(attr.ino, fh, 0, b"dddd").unwrap();
+    let changes = SetAttr {
+        size: Some(2),
+        ..Default::default()
+    };
+    rfs.set_attr(caller, attr.ino, changes).unwrap();
+    drop(task);
+    rfs.write_data(attr.ino, fh, 0, b"eeee").unwrap();
+    assert_eq!(
+        fs::read(origin.path().join("append.txt")).unwrap(),
+        b"aaeeee"
+    );
+}
+
+// 写入在截断之前完成, 但是完成的结果在截断之后才更新到 inode 表
+#[test]
+fn test_handlers_truncate_pending_write() {
+    let origin = tempfile::tempdir().unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+
+    let task = rfs.write_task(attr.ino, fh, 0, b"rfuse rfuse").unwrap();
+    assert_eq!(task.run(), Ok(11));
+    let changes = SetAttr {
+        size: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(rfs.set_attr(caller, attr.ino, changes).unwrap().size, 0);
+
+    // 截断之前的写入不能把文件大小改回去
+    let lookup = |rfs: &mut RFuseFS| {
+        rfs.lookup_entry(caller, FUSE_ROOT_ID, OsStr::new("file.txt"))
+            .unwrap()
+            .size
+    };
+    assert_eq!(lookup(&mut rfs), 0);
+    assert_eq!(
+        fs::metadata(origin.path().join("file.txt")).unwrap().len(),
+        0
+    );
+
+    // 截断之后创建的写入正常更新文件大小
+    let task = rfs.write_task(attr.ino, fh, 0, b"rf").unwrap();
+    assert_eq!(task.run(), Ok(2));
+    assert_eq!(lookup(&mut rfs), 2);
+    assert_eq!(rfs.read_data(attr.ino, fh, 0, 16).unwrap(), b"rf");
+}
+
+#[test]
+fn test_handlers_rename_keeps_lock() {
+    let origin = tempfile::tempdir().unwrap();
+    fs::write(origin.path().join("a.txt"), "rfuse").unwrap();
+    let path = PathBuf::from(format!("{}/", origin.path().display()));
+    let mut manager = RemoteFileManager::new(Box::new(user_defined_init_fs), Box::new(LocalFS));
+    manager.add_file(2, OsString::from("a.txt"), path.clone());
+    let before = manager.tmp_file(2).unwrap();
+
+    // 改名前后的 TmpFile 共用同一个读写锁, 正在进行的读写结束之前改名需要等待
+    manager
+        .rename(
+            2,
+            OsString::from("b.txt"),
+            path.clone(),
+            &SystemTime::now(),
+            0,
+        )
+        .unwrap();
+    let after = manager.tmp_file(2).unwrap();
+    assert_eq!(after.file_name, "b.txt");
+    assert!(Arc::ptr_eq(&before.lock, &after.lock));
+    assert_eq!(fs::read(origin.path().join("b.txt")).unwrap(), b"rfuse");
+
+    // 源目录的改动重新登记同一个文件时也沿用原来的锁
+    manager.add_file(2, OsString::from("c.txt"), path);
+    assert!(Arc::ptr_eq(
+        &before.lock,
+        &manager.tmp_file(2).unwrap().lock
+    ));
+}
+
+// 记录通过句柄读写次数的异步后端
+struct CountingAsync(SyncAdapter, Arc<AtomicUsize>);
+
+impl AsyncTmpFileTrait for CountingAsync {
+    fn read(
+        &self,
+        tf: Arc<TmpFile>,
+        offset: u64,
+        size: usize,
+        file_size: u64,
+    ) -> BackendFuture<Vec<u8>> {
+        self.0.read(tf, offset, size, file_size)
+    }
+
+    fn write(
+        &self,
+        tf: Arc<TmpFile>,
+        data: Vec<u8>,
+        write_time: SystemTime,
+        offset: u64,
+    ) -> BackendFuture<()> {
+        self.0.write(tf, data, write_time, offset)
+    }
+
+    fn read_link(&self, tf: Arc<TmpFile>) -> BackendFuture<PathBuf> {
+        self.0.read_link(tf)
+    }
+
+    fn statfs(&self, tf: Arc<TmpFile>) -> BackendFuture<StatFs> {
+        self.0.statfs(tf)
+    }
+
+    fn get_xattr(&self, tf: Arc<TmpFile>, name: String) -> BackendFuture<Vec<u8>> {
+        self.0.get_xattr(tf, name)
+    }
+
+    fn list_xattr(&self, tf: Arc<TmpFile>) -> BackendFuture<Vec<u8>> {
+        self.0.list_xattr(tf)
+    }
+
+    fn read_handle(
+        &self,
+        handle: Arc<dyn FileHandle>,
+        offset: u64,
+        size: usize,
+    ) -> BackendFuture<Vec<u8>> {
+        self.1.fetch_add(1, Ordering::Relaxed);
+        self.0.read_handle(handle, offset, size)
+    }
+
+    fn write_handle(
+        &self,
+        handle: Arc<dyn FileHandle>,
+        data: Vec<u8>,
+        write_time: SystemTime,
+        offset: u64,
+    ) -> BackendFuture<()> {
+        self.1.fetch_add(1, Ordering::Relaxed);
+        self.0.write_handle(handle, data, write_time, offset)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handlers_async_replies() {
+    let origin = tempfile::tempdir().unwrap();
+    let caller = owner(origin.path());
+    let mut rfs = local_fs(origin.path());
+    let (attr, fh) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("new.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+
+    // 没有异步后端和线程池时在当前线程中回复
+    let (send, recv) = mpsc::channel();
+    let reply = send.clone();
+    rfs.spawn_write(attr.ino, fh, 0, b"rfuse", move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    assert_eq!(recv.try_recv().unwrap(), (None, Ok(5)));
+
+    // LocalFS 打开文件时返回句柄, 通过句柄的读写也在异步后端的任务中回复
+    let calls = Arc::new(AtomicUsize::new(0));
+    let backend = CountingAsync(SyncAdapter(Arc::new(LocalFS)), calls.clone());
+    rfs.set_async_backend(Arc::new(backend), tokio::runtime::Handle::current());
+    let reply = send.clone();
+    rfs.spawn_write(attr.ino, fh, 5, b" async", move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    let (task, written) = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(task.is_some());
+    assert_eq!(written, Ok(6));
+
+    let (send, recv) = mpsc::channel();
+    rfs.spawn_read(attr.ino, fh, 0, 4096, move |result| {
+        send.send((tokio::task::try_id(), result)).unwrap()
+    });
+    let (task, data) = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(task.is_some());
+    assert_eq!(data.unwrap(), b"rfuse async");
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}
+
+// 有异步后端时扩展属性的读取也在后端的任务中回复
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handlers_async_xattr() {
+    let caller = Caller { uid: 0, gid: 0 };
+    let (mut rfs, disk) = mem_fs(4096);
+    let (attr, _) = rfs
+        .create_file(
+            caller,
+            FUSE_ROOT_ID,
+            OsStr::new("file.txt"),
+            0o644,
+            0o022,
+            libc::O_RDWR,
+        )
+        .unwrap();
+    let name = OsStr::new("user.rfuse");
+    rfs.set_xattr(caller, attr.ino, name, b"rfuse", 0).unwrap();
+
+    // 没有异步后端时在当前线程中回复
+    let (send, recv) = mpsc::channel();
+    let reply = send.clone();
+    rfs.spawn_get_xattr(attr.ino, name, move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    assert_eq!(recv.try_recv().unwrap(), (None, Ok(b"rfuse".to_vec())));
+
+    rfs.set_async_backend(
+        Arc::new(SyncAdapter(Arc::new(MemFS(disk)))),
+        tokio::runtime::Handle::current(),
+    );
+    let reply = send.clone();
+    rfs.spawn_get_xattr(attr.ino, name, move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    let (task, value) = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(task.is_some());
+    assert_eq!(value, Ok(b"rfuse".to_vec()));
+
+    let reply = send.clone();
+    rfs.spawn_list_xattr(attr.ino, move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    let (task, names) = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(task.is_some());
+    assert_eq!(names, Ok(b"user.rfuse\0".to_vec()));
+
+    // 后端的错误码原样返回, inode 表中不存在的 ino 不会交给后端
+    let reply = send.clone();
+    rfs.spawn_get_xattr(attr.ino, OsStr::new("user.none"), move |result| {
+        reply.send((tokio::task::try_id(), result)).unwrap()
+    });
+    let (_, value) = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(value, Err(RFUSE_ENOATTR));
+    rfs.spawn_list_xattr(STALE_INO, move |result| {
+        send.send((tokio::task::try_id(), result)).unwrap()
+    });
+    assert_eq!(recv.try_recv().unwrap(), (None, Err(libc::ENOENT)));
+}
@@ -15,8 +15,9 @@ rfuse: A fuse server.
 Usage: rfuses_device_local [OPTIONS] <COMMAND>
 
 Commands:
-  link  A fuse server, similar to link.
-  help  Print this message or the help of the given subcommand(s)
+  link   A fuse server, similar to link.
+  serve  Serve a folder to rfuse clients over TCP.
+  help   Print this message or the help of the given subcommand(s)
 
 Options:
   -h, --help     Print help
@@ -50,13 +51,53 @@ Arguments:
   [FS_NAME]  Set the name of the source in mtab. [default: rfuses]
 
 Options:
-  -r, --read-only  Read only
-  -h, --help       Print help
-  -V, --version    Print version
+  -r, --read-only    Read only
+      --no-xattr     Disable extended attributes
+      --threads <N>  Worker threads for file reads and writes, 0 disables the pool [default: 4]
+  -h, --help         Print help
+  -V, --version      Print version
 
 Disk types:
-  -l, --local  Use local disk
-  -m, --mem    Use memory as disk
+  -l, --local           Use local disk
+  -m, --mem             Use memory as disk
+      --mem-size <MiB>  Capacity of the memory disk [default: 1024]
+
+Kernel cache:
+      --attr-ttl <SECONDS>      How long the kernel may cache file attributes [default: 0]
+      --entry-ttl <SECONDS>     How long the kernel may cache directory entries [default: 0]
+      --negative-ttl <SECONDS>  How long the kernel may remember that a name does not exist [default: 0]
+      --no-direct-io            Read and write through the kernel page cache
+      --keep-cache              Keep cached file pages when a file is opened again
+      --writeback-cache         Buffer writes in the kernel and flush them in batches
+
+Log levels:
+  -v, --verbose  Enable verbose logging
+  -q, --quiet    Print diagnostics, but nothing else
+  -s, --silent   Disable all logging (but still exit with status code "1" upon detecting diagnostics)
+
+----- stderr -----"###);
+}
+
+#[tokio::test]
+async fn help_serve() {
+    let context = TestContext::new();
+
+    rfuses_snapshot!(context.help().arg("serve"), @r###"
+success: true
+exit_code: 0
+----- stdout -----
+Serve a folder to rfuse clients over TCP.
+
+Usage: rfuses_device_local serve [OPTIONS] <ORIGIN>
+
+Arguments:
+  <ORIGIN>  Origin file address [default: .]
+
+Options:
+  -b, --bind <BIND>    Address to listen on [default: 127.0.0.1:7878]
+      --token <TOKEN>  Shared secret that clients must send in the handshake
+  -h, --help           Print help
+  -V, --version        Print version
 
 Log levels:
   -v, --verbose  Enable verbose logging
@@ -0,0 +1,143 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    os::unix::fs::MetadataExt,
+};
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+#[tokio::test]
+async fn test_hard_link_mount() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_link.txt");
+        File::create(&test_file_mount)
+            .unwrap()
+            .write_all(b"rfuse")
+            .unwrap();
+
+        // 在子文件夹中创建硬链接
+        let mut test_dir_mount = mount_path.clone();
+        test_dir_mount.push("test_link_dir");
+        fs::create_dir(&test_dir_mount).unwrap();
+        let mut test_link_mount = test_dir_mount.clone();
+        test_link_mount.push("test_link_hard.txt");
+        fs::hard_link(&test_file_mount, &test_link_mount).unwrap();
+
+        let file_meta = fs::metadata(&test_file_mount).unwrap();
+        let link_meta = fs::metadata(&test_link_mount).unwrap();
+        assert_eq!(file_meta.ino(), link_meta.ino());
+        assert_eq!(file_meta.nlink(), 2);
+        assert_eq!(link_meta.nlink(), 2);
+        assert_eq!(fs::read(&test_link_mount).unwrap(), b"rfuse");
+
+        // 原始目录中也是硬链接
+        let mut test_link_origin = origin_path.clone();
+        test_link_origin.push("test_link_dir");
+        test_link_origin.push("test_link_hard.txt");
+        assert_eq!(fs::metadata(&test_link_origin).unwrap().nlink(), 2);
+
+        // 删除原文件后, 硬链接仍然可以访问
+        fs::remove_file(&test_file_mount).unwrap();
+        assert!(!test_file_mount.exists());
+        assert_eq!(fs::read(&test_link_mount).unwrap(), b"rfuse");
+        assert_eq!(fs::metadata(&test_link_mount).unwrap().nlink(), 1);
+
+        fs::remove_file(&test_link_mount).unwrap();
+        assert!(!test_link_mount.exists());
+        assert!(!test_link_origin.exists());
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_hard_link_origin() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 挂载前在原始目录中创建硬链接
+    let mut test_file_origin = origin_path.clone();
+    test_file_origin.push("test_link.txt");
+    File::create(&test_file_origin)
+        .unwrap()
+        .write_all(b"Hello, World!")
+        .unwrap();
+    let mut test_link_origin = origin_path.clone();
+    test_link_origin.push("test_link_hard.txt");
+    fs::hard_link(&test_file_origin, &test_link_origin).unwrap();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_link.txt");
+        let mut test_link_mount = mount_path.clone();
+        test_link_mount.push("test_link_hard.txt");
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().nlink(), 2);
+        assert_eq!(
+            fs::metadata(&test_file_mount).unwrap().ino(),
+            fs::metadata(&test_link_mount).unwrap().ino()
+        );
+        assert_eq!(fs::read(&test_link_mount).unwrap(), b"Hello, World!");
+        assert_eq!(fs::read_dir(&mount_path).unwrap().count(), 2);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_dir_nlink() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+
+    let closure = || {
+        let mut test_dir_mount = mount_path.clone();
+        test_dir_mount.push("test_nlink_dir");
+        fs::create_dir(&test_dir_mount).unwrap();
+        assert_eq!(fs::metadata(&test_dir_mount).unwrap().nlink(), 2);
+
+        // 每个子文件夹都会增加一个链接, 文件不会
+        for name in ["a", "b"] {
+            let mut sub_dir = test_dir_mount.clone();
+            sub_dir.push(name);
+            fs::create_dir(&sub_dir).unwrap();
+        }
+        let mut test_file_mount = test_dir_mount.clone();
+        test_file_mount.push("test_nlink.txt");
+        File::create(&test_file_mount).unwrap();
+        assert_eq!(fs::metadata(&test_dir_mount).unwrap().nlink(), 4);
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().nlink(), 1);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
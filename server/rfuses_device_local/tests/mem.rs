@@ -0,0 +1,188 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+#[tokio::test]
+async fn test_mem_write_read() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let test_file = "test_mem.txt";
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push(test_file);
+        // 写入文件内容
+        let content = "Hello, World!";
+        let mut file = File::create(&test_file_mount).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        // 读取文件内容
+        let mut file = File::open(&test_file_mount).unwrap();
+        let mut read_content = String::new();
+        file.read_to_string(&mut read_content).unwrap();
+        assert_eq!(content, read_content);
+        assert_eq!(
+            fs::metadata(&test_file_mount).unwrap().len(),
+            content.len() as u64
+        );
+
+        // 内存磁盘不会写入原始目录
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push(test_file);
+        assert!(!Path::new(&test_file_origin).exists());
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--mem")
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_mem_sparse_write_and_truncate() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_mem_sparse.txt");
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&test_file_mount)
+            .unwrap();
+        // 在偏移处写入, 中间的空洞应该读出 0
+        file.write_at(b"rfuse", 10).unwrap();
+        let mut buf = vec![0xffu8; 15];
+        file.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(&buf[..10], &[0u8; 10]);
+        assert_eq!(&buf[10..], b"rfuse");
+
+        // 截断文件
+        file.set_len(4).unwrap();
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().len(), 4);
+        let mut content = Vec::new();
+        File::open(&test_file_mount)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, vec![0u8; 4]);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--mem")
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_mem_dir() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+
+    let closure = || {
+        let mut test_dir_mount = mount_path.clone();
+        test_dir_mount.push("test_mem_dir");
+        fs::create_dir(&test_dir_mount).unwrap();
+        assert!(test_dir_mount.is_dir());
+
+        // 在子文件夹中创建文件
+        let mut test_file_mount = test_dir_mount.clone();
+        test_file_mount.push("test_mem.txt");
+        File::create(&test_file_mount)
+            .unwrap()
+            .write_all(b"rfuse")
+            .unwrap();
+        assert_eq!(fs::read(&test_file_mount).unwrap(), b"rfuse");
+
+        // 重命名文件
+        let mut test_file_rename_mount = test_dir_mount.clone();
+        test_file_rename_mount.push("test_mem_rename.txt");
+        fs::rename(&test_file_mount, &test_file_rename_mount).unwrap();
+        assert!(!test_file_mount.exists());
+        assert_eq!(fs::read(&test_file_rename_mount).unwrap(), b"rfuse");
+
+        // 删除文件和文件夹
+        fs::remove_file(&test_file_rename_mount).unwrap();
+        fs::remove_dir(&test_dir_mount).unwrap();
+        assert!(!test_dir_mount.exists());
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--mem")
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_mem_capacity_and_link() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_mem_capacity.txt");
+        let file = File::create(&test_file_mount).unwrap();
+
+        // 超出内存磁盘容量的截断和写入返回错误, 而不是让进程申请内存失败
+        assert_eq!(
+            file.set_len(1 << 50).unwrap_err().raw_os_error(),
+            Some(libc::EFBIG)
+        );
+        assert_eq!(
+            file.write_at(b"rfuse", 1 << 40).unwrap_err().raw_os_error(),
+            Some(libc::EFBIG)
+        );
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().len(), 0);
+
+        // 内存磁盘不支持硬链接
+        let mut test_link_mount = mount_path.clone();
+        test_link_mount.push("test_mem_link.txt");
+        assert_eq!(
+            fs::hard_link(&test_file_mount, &test_link_mount)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EPERM)
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--mem")
+                .arg("--mem-size")
+                .arg("1")
+        },
+        closure
+    );
+}
@@ -0,0 +1,107 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    thread,
+    time::Duration,
+};
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+#[tokio::test]
+async fn test_notify_origin_change() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let mut test_file_origin = origin_path.clone();
+    test_file_origin.push("test_notify.txt");
+    fs::write(&test_file_origin, b"Hello, World!").unwrap();
+
+    let closure = || {
+        // 挂载目录中打开的文件在源目录改动之后仍然可用
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_notify.txt");
+        let mut opened = File::open(&test_file_mount).unwrap();
+
+        // 在源目录中创建文件
+        let mut test_new_origin = origin_path.clone();
+        test_new_origin.push("test_notify_new.txt");
+        let mut new_file = File::create(&test_new_origin).unwrap();
+        new_file.write_all(b"rfuse").unwrap();
+        new_file.flush().unwrap();
+        thread::sleep(Duration::from_secs(1));
+
+        let mut test_new_mount = mount_path.clone();
+        test_new_mount.push("test_notify_new.txt");
+        assert_eq!(fs::read(&test_new_mount).unwrap(), b"rfuse");
+        drop(new_file);
+
+        let mut content = String::new();
+        opened.seek(SeekFrom::Start(0)).unwrap();
+        opened.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello, World!");
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_notify_origin_rename_remove() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let mut dir_origin = origin_path.clone();
+    dir_origin.push("notify_dir");
+    fs::create_dir(&dir_origin).unwrap();
+    fs::write(dir_origin.join("a.txt"), b"rfuse").unwrap();
+    fs::write(origin_path.join("b.txt"), b"remove me").unwrap();
+
+    let closure = || {
+        assert_eq!(
+            fs::read(mount_path.join("notify_dir").join("a.txt")).unwrap(),
+            b"rfuse"
+        );
+
+        // 在源目录中移动文件夹, 子文件跟随移动
+        fs::create_dir(origin_path.join("parent")).unwrap();
+        fs::rename(&dir_origin, origin_path.join("parent").join("renamed")).unwrap();
+        // 在源目录中删除文件
+        fs::remove_file(origin_path.join("b.txt")).unwrap();
+        // 在源目录中新建的文件夹, 里面的文件也能看到
+        fs::create_dir_all(origin_path.join("x").join("y")).unwrap();
+        fs::write(origin_path.join("x").join("y").join("c.txt"), b"nested").unwrap();
+        thread::sleep(Duration::from_secs(1));
+
+        assert!(!mount_path.join("notify_dir").exists());
+        assert!(!mount_path.join("b.txt").exists());
+        assert_eq!(
+            fs::read(mount_path.join("parent").join("renamed").join("a.txt")).unwrap(),
+            b"rfuse"
+        );
+        assert_eq!(
+            fs::read(mount_path.join("x").join("y").join("c.txt")).unwrap(),
+            b"nested"
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
@@ -295,3 +295,49 @@ async fn test_read_dir() {
         closure
     );
 }
+
+#[tokio::test]
+async fn test_read_lazy_dir() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 多层文件夹在第一次访问时才读取
+    let nested = origin_path.join("lazy_a").join("lazy_b").join("lazy_c");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(nested.join("deep.txt"), "rfuse").unwrap();
+    fs::create_dir(origin_path.join("lazy_full")).unwrap();
+    fs::write(origin_path.join("lazy_full").join("keep.txt"), "keep").unwrap();
+
+    let closure = || {
+        // 直接按路径访问, 不先列出文件夹
+        let deep_mount = mount_path
+            .join("lazy_a")
+            .join("lazy_b")
+            .join("lazy_c")
+            .join("deep.txt");
+        assert_eq!(fs::read_to_string(&deep_mount).unwrap(), "rfuse");
+        assert_eq!(
+            fs::read_dir(mount_path.join("lazy_a").join("lazy_b"))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        // 没有访问过的文件夹也不能被当作空文件夹删除
+        let err = fs::remove_dir(mount_path.join("lazy_full")).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTEMPTY));
+        assert!(origin_path.join("lazy_full").join("keep.txt").exists());
+    };
+
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
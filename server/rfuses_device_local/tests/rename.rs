@@ -303,3 +303,80 @@ async fn test_rename_in_mount_dir() {
         closure
     );
 }
+
+#[tokio::test]
+async fn test_rename_populated_dir() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 在原始目录中创建带有多层子文件的文件夹
+    let test_dir = "test_rename_populated";
+    let nested_file = "sub/deep/nested.txt";
+    let content = "Hello, World!";
+    let mut nested_file_origin = origin_path.clone();
+    nested_file_origin.push(test_dir);
+    nested_file_origin.push(nested_file);
+    fs::create_dir_all(nested_file_origin.parent().unwrap()).unwrap();
+    fs::write(&nested_file_origin, content).unwrap();
+    fs::write(origin_path.join(test_dir).join("top.txt"), content).unwrap();
+
+    let closure = || {
+        let test_dir_mount = mount_path.join(test_dir);
+        // 先访问一次, 让子孙节点都加载到 inode 表中
+        assert_eq!(
+            fs::read_to_string(test_dir_mount.join(nested_file)).unwrap(),
+            content
+        );
+
+        // 移动到另一个文件夹下并改名
+        let moved_parent = mount_path.join("test_rename_moved");
+        fs::create_dir(&moved_parent).unwrap();
+        let moved_dir = moved_parent.join("renamed");
+        fs::rename(&test_dir_mount, &moved_dir).unwrap();
+        assert!(!test_dir_mount.exists());
+
+        // 移动之后还能读写子孙文件
+        assert_eq!(
+            fs::read_to_string(moved_dir.join(nested_file)).unwrap(),
+            content
+        );
+        assert_eq!(
+            fs::read_to_string(moved_dir.join("top.txt")).unwrap(),
+            content
+        );
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(moved_dir.join(nested_file))
+            .unwrap();
+        file.write_all(b" Again").unwrap();
+        drop(file);
+        let moved_origin = origin_path.join("test_rename_moved/renamed");
+        assert_eq!(
+            fs::read_to_string(moved_origin.join(nested_file)).unwrap(),
+            "Hello, World! Again"
+        );
+
+        // 在子文件夹中新建文件, 落到源目录中新的位置
+        fs::write(moved_dir.join("sub/deep/new.txt"), content).unwrap();
+        assert!(moved_origin.join("sub/deep/new.txt").exists());
+
+        // 再移动回原来的位置
+        fs::rename(&moved_dir, &test_dir_mount).unwrap();
+        assert_eq!(
+            fs::read_to_string(test_dir_mount.join("sub/deep/new.txt")).unwrap(),
+            content
+        );
+    };
+
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
@@ -0,0 +1,648 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    net::TcpStream,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{symlink, MetadataExt},
+    },
+    time::SystemTime,
+};
+
+use common::{free_addr, rfuses_spawn_run, run_command_with_status, TestContext};
+use rfuse_core::{inode::InodeKind, tmp_file::TmpFileError};
+use rfuse_protocol::{
+    codec::{read_frame, write_frame, MAX_DATA_SIZE},
+    message::{Request, Response},
+    PROTOCOL_VERSION,
+};
+
+mod common;
+
+// 发送一个请求并等待响应
+fn call(stream: &mut TcpStream, id: u64, request: Request) -> Response {
+    write_frame(stream, &request.encode(id)).unwrap();
+    let payload = read_frame(stream).unwrap().unwrap();
+    let (response_id, response) = Response::decode(&payload).unwrap();
+    assert_eq!(id, response_id);
+    response
+}
+
+// 握手之后才能发送其他请求
+fn handshake(stream: &mut TcpStream, token: &str) {
+    assert_eq!(
+        call(
+            stream,
+            0,
+            Request::Handshake {
+                version: PROTOCOL_VERSION,
+                token: token.into(),
+            }
+        ),
+        Response::Handshake {
+            version: PROTOCOL_VERSION
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_serve_read() {
+    let context = TestContext::new();
+
+    let origin_path = context.origin_dir.to_owned();
+    let addr = free_addr();
+
+    // 在原始目录中准备文件
+    let mut test_file_origin = origin_path.clone();
+    test_file_origin.push("test_serve.txt");
+    let content = "Hello, World!";
+    File::create(&test_file_origin)
+        .unwrap()
+        .write_all(content.as_bytes())
+        .unwrap();
+    let mut test_dir_origin = origin_path.clone();
+    test_dir_origin.push("test_serve_dir");
+    fs::create_dir(&test_dir_origin).unwrap();
+
+    let server_addr = addr.clone();
+    let closure = || {
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        assert_eq!(
+            call(
+                &mut stream,
+                1,
+                Request::Handshake {
+                    version: PROTOCOL_VERSION,
+                    token: String::new(),
+                }
+            ),
+            Response::Handshake {
+                version: PROTOCOL_VERSION
+            }
+        );
+
+        // 读取文件夹
+        let mut entries = match call(&mut stream, 2, Request::ReadDir { path: "/".into() }) {
+            Response::Entries(entries) => entries,
+            r => panic!("unexpected response: {:?}", r),
+        };
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "test_serve.txt");
+        assert_eq!(entries[0].attr.kind, InodeKind::File);
+        assert_eq!(entries[1].name, "test_serve_dir");
+        assert_eq!(entries[1].attr.kind, InodeKind::Directory);
+
+        // 查找文件并检查 meta 信息
+        let attr = match call(
+            &mut stream,
+            3,
+            Request::Lookup {
+                parent: "/".into(),
+                name: "test_serve.txt".into(),
+            },
+        ) {
+            Response::Attr(attr) => attr,
+            r => panic!("unexpected response: {:?}", r),
+        };
+        let origin_meta = fs::metadata(&test_file_origin).unwrap();
+        assert_eq!(attr.ino, origin_meta.ino());
+        assert_eq!(attr.size, origin_meta.size());
+        assert_eq!(attr.uid, origin_meta.uid());
+        assert_eq!(attr.gid, origin_meta.gid());
+
+        // 读取文件
+        assert_eq!(
+            call(
+                &mut stream,
+                4,
+                Request::Read {
+                    path: "/test_serve.txt".into(),
+                    offset: 7,
+                    size: 5,
+                }
+            ),
+            Response::Data(b"World".to_vec())
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                5,
+                Request::ReadAll {
+                    path: "/test_serve.txt".into(),
+                }
+            ),
+            Response::Data(content.as_bytes().to_vec())
+        );
+
+        // 不允许访问共享目录以外的文件
+        assert_eq!(
+            call(
+                &mut stream,
+                6,
+                Request::ReadAll {
+                    path: "/../origin_dir/test_serve.txt".into(),
+                }
+            ),
+            Response::Error(TmpFileError::ReadError)
+        );
+
+        // 查询共享目录所在文件系统的容量信息
+        match call(&mut stream, 7, Request::StatFs { path: "/".into() }) {
+            Response::StatFs(stat) => {
+                assert!(stat.blocks > 0);
+                assert!(stat.bsize > 0);
+                assert!(stat.namelen > 0);
+            }
+            r => panic!("unexpected response: {:?}", r),
+        };
+
+        // 放不进一帧的文件不能一次读取, 只能分段读取
+        let mut big_file_origin = origin_path.clone();
+        big_file_origin.push("test_serve_big.bin");
+        File::create(&big_file_origin)
+            .unwrap()
+            .set_len(MAX_DATA_SIZE as u64 + 1)
+            .unwrap();
+        assert_eq!(
+            call(
+                &mut stream,
+                8,
+                Request::ReadAll {
+                    path: "/test_serve_big.bin".into(),
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::EFBIG))
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                9,
+                Request::Read {
+                    path: "/test_serve_big.bin".into(),
+                    offset: MAX_DATA_SIZE as u64,
+                    size: 1,
+                }
+            ),
+            Response::Data(vec![0])
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .serve()
+                .arg(context.origin_dir.path())
+                .arg("--bind")
+                .arg(&addr)
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_serve_write() {
+    let context = TestContext::new();
+
+    let origin_path = context.origin_dir.to_owned();
+    let addr = free_addr();
+
+    let server_addr = addr.clone();
+    let closure = || {
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        handshake(&mut stream, "");
+        let now = SystemTime::now();
+
+        // 创建文件夹和文件
+        match call(
+            &mut stream,
+            1,
+            Request::MakeDir {
+                path: "/test_serve_dir".into(),
+                mode: 0o755,
+            },
+        ) {
+            Response::Attr(attr) => assert_eq!(attr.kind, InodeKind::Directory),
+            r => panic!("unexpected response: {:?}", r),
+        };
+        match call(
+            &mut stream,
+            2,
+            Request::Create {
+                path: "/test_serve_dir/test_serve.txt".into(),
+                mode: 0o640,
+            },
+        ) {
+            Response::Attr(attr) => assert_eq!(attr.kind, InodeKind::File),
+            r => panic!("unexpected response: {:?}", r),
+        };
+
+        // 源目录返回的错误码原样传给客户端
+        assert_eq!(
+            call(
+                &mut stream,
+                10,
+                Request::MakeDir {
+                    path: "/test_serve_dir".into(),
+                    mode: 0o755,
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::EEXIST))
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                11,
+                Request::Create {
+                    path: "/not_exist_dir/test_serve.txt".into(),
+                    mode: 0o640,
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::ENOENT))
+        );
+
+        // 写入文件
+        assert_eq!(
+            call(
+                &mut stream,
+                3,
+                Request::Write {
+                    path: "/test_serve_dir/test_serve.txt".into(),
+                    offset: 0,
+                    data: b"Hello, World!".to_vec(),
+                    write_time: now,
+                }
+            ),
+            Response::Ok
+        );
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push("test_serve_dir");
+        test_file_origin.push("test_serve.txt");
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"Hello, World!");
+
+        // 重命名文件
+        assert_eq!(
+            call(
+                &mut stream,
+                4,
+                Request::Rename {
+                    path: "/test_serve_dir/test_serve.txt".into(),
+                    new_path: "/test_serve_rename.txt".into(),
+                    rename_time: now,
+                    flags: 0,
+                }
+            ),
+            Response::Ok
+        );
+        assert!(!test_file_origin.exists());
+        let mut test_file_rename_origin = origin_path.clone();
+        test_file_rename_origin.push("test_serve_rename.txt");
+        assert!(test_file_rename_origin.exists());
+
+        // 删除文件和文件夹
+        assert_eq!(
+            call(
+                &mut stream,
+                5,
+                Request::Unlink {
+                    path: "/test_serve_rename.txt".into(),
+                    rm_file_time: now,
+                }
+            ),
+            Response::Ok
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                6,
+                Request::RemoveDir {
+                    path: "/test_serve_dir".into(),
+                    rm_dir_time: now,
+                }
+            ),
+            Response::Ok
+        );
+        assert!(!test_file_rename_origin.exists());
+        test_file_origin.pop();
+        assert!(!test_file_origin.exists());
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .serve()
+                .arg(context.origin_dir.path())
+                .arg("--bind")
+                .arg(&addr)
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_serve_handshake() {
+    let context = TestContext::new();
+
+    let addr = free_addr();
+
+    let server_addr = addr.clone();
+    let closure = || {
+        // 握手失败时服务端回复错误之后关闭连接
+        let rejected = |request: Request, errno: i32| {
+            let mut stream = TcpStream::connect(&server_addr).unwrap();
+            assert_eq!(
+                call(&mut stream, 1, request),
+                Response::Error(TmpFileError::Errno(errno))
+            );
+            assert!(read_frame(&mut stream).unwrap().is_none());
+        };
+
+        // 没有握手就发送其他请求
+        rejected(Request::ReadDir { path: "/".into() }, libc::EACCES);
+        // 协议版本不一致
+        rejected(
+            Request::Handshake {
+                version: PROTOCOL_VERSION + 1,
+                token: "rfuse".into(),
+            },
+            libc::EPROTONOSUPPORT,
+        );
+        // token 不一致
+        rejected(
+            Request::Handshake {
+                version: PROTOCOL_VERSION,
+                token: "other".into(),
+            },
+            libc::EACCES,
+        );
+
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        handshake(&mut stream, "rfuse");
+        assert!(matches!(
+            call(&mut stream, 1, Request::ReadDir { path: "/".into() }),
+            Response::Entries(_)
+        ));
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .serve()
+                .arg(context.origin_dir.path())
+                .arg("--bind")
+                .arg(&addr)
+                .arg("--token")
+                .arg("rfuse")
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_serve_symlink_escape() {
+    let context = TestContext::new();
+
+    let origin_path = context.origin_dir.to_owned();
+    let addr = free_addr();
+
+    // 共享目录以外的文件
+    let outside = tempfile::tempdir().unwrap();
+    let outside_path = outside.path().to_owned();
+    fs::write(outside_path.join("passwd"), "secret").unwrap();
+    fs::write(origin_path.join("test_serve.txt"), "Hello, World!").unwrap();
+    symlink(&outside_path, origin_path.join("outside_dir")).unwrap();
+    symlink(
+        outside_path.join("passwd"),
+        origin_path.join("outside_file"),
+    )
+    .unwrap();
+    symlink(outside_path.join("new.txt"), origin_path.join("dangling")).unwrap();
+
+    let server_addr = addr.clone();
+    let closure = || {
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        handshake(&mut stream, "");
+        let now = SystemTime::now();
+
+        // 通过指向共享目录以外的文件夹访问
+        assert_eq!(
+            call(
+                &mut stream,
+                1,
+                Request::Read {
+                    path: "/outside_dir/passwd".into(),
+                    offset: 0,
+                    size: 6,
+                }
+            ),
+            Response::Error(TmpFileError::ReadError)
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                2,
+                Request::Write {
+                    path: "/outside_dir/passwd".into(),
+                    offset: 0,
+                    data: b"rfuse".to_vec(),
+                    write_time: now,
+                }
+            ),
+            Response::Error(TmpFileError::WriteError)
+        );
+        let attr = match call(
+            &mut stream,
+            3,
+            Request::GetAttr {
+                path: "/test_serve.txt".into(),
+            },
+        ) {
+            Response::Attr(attr) => attr,
+            r => panic!("unexpected response: {:?}", r),
+        };
+        assert_eq!(
+            call(
+                &mut stream,
+                4,
+                Request::SetAttr {
+                    path: "/outside_dir/passwd".into(),
+                    attr,
+                }
+            ),
+            Response::Error(TmpFileError::SetAttrError)
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                5,
+                Request::Lookup {
+                    parent: "/outside_dir".into(),
+                    name: "passwd".into(),
+                }
+            ),
+            Response::Error(TmpFileError::ReadError)
+        );
+
+        // 最后一级是指向共享目录以外的符号链接
+        assert_eq!(
+            call(
+                &mut stream,
+                6,
+                Request::ReadAll {
+                    path: "/outside_file".into(),
+                }
+            ),
+            Response::Error(TmpFileError::ReadError)
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                7,
+                Request::Create {
+                    path: "/dangling".into(),
+                    mode: 0o644,
+                }
+            ),
+            Response::Error(TmpFileError::CreateError)
+        );
+
+        // 符号链接本身仍然可以查看
+        match call(
+            &mut stream,
+            8,
+            Request::Lookup {
+                parent: "/".into(),
+                name: "outside_file".into(),
+            },
+        ) {
+            Response::Attr(attr) => assert_eq!(attr.kind, InodeKind::Symlink),
+            r => panic!("unexpected response: {:?}", r),
+        };
+        assert_eq!(
+            call(
+                &mut stream,
+                9,
+                Request::ReadLink {
+                    path: "/outside_file".into(),
+                }
+            ),
+            Response::Data(outside_path.join("passwd").as_os_str().as_bytes().to_vec())
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .serve()
+                .arg(context.origin_dir.path())
+                .arg("--bind")
+                .arg(&addr)
+        },
+        closure
+    );
+
+    assert_eq!(fs::read(outside.path().join("passwd")).unwrap(), b"secret");
+    assert!(!outside.path().join("new.txt").exists());
+}
+
+#[tokio::test]
+async fn test_serve_export_root() {
+    let context = TestContext::new();
+
+    let origin_path = context.origin_dir.to_owned();
+    let addr = free_addr();
+
+    // 共享目录以内的符号链接
+    fs::create_dir(origin_path.join("sub")).unwrap();
+    symlink(origin_path.join("sub"), origin_path.join("sub_link")).unwrap();
+    symlink("sub/new.txt", origin_path.join("file_link")).unwrap();
+
+    let server_addr = addr.clone();
+    let closure = || {
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        handshake(&mut stream, "");
+        let now = SystemTime::now();
+
+        // 不能删除或者重命名共享目录本身
+        assert_eq!(
+            call(
+                &mut stream,
+                1,
+                Request::RemoveDir {
+                    path: "/".into(),
+                    rm_dir_time: now,
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::EBUSY))
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                2,
+                Request::Rename {
+                    path: "/".into(),
+                    new_path: "/moved".into(),
+                    rename_time: now,
+                    flags: 0,
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::EBUSY))
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                3,
+                Request::Unlink {
+                    path: "/".into(),
+                    rm_file_time: now,
+                }
+            ),
+            Response::Error(TmpFileError::Errno(libc::EBUSY))
+        );
+
+        // 通过共享目录以内的符号链接创建, 写入和读取
+        assert!(matches!(
+            call(
+                &mut stream,
+                4,
+                Request::Create {
+                    path: "/sub_link/new.txt".into(),
+                    mode: 0o640,
+                }
+            ),
+            Response::Attr(_)
+        ));
+        assert_eq!(
+            call(
+                &mut stream,
+                5,
+                Request::Write {
+                    path: "/file_link".into(),
+                    offset: 0,
+                    data: b"rfuse".to_vec(),
+                    write_time: now,
+                }
+            ),
+            Response::Ok
+        );
+        assert_eq!(
+            call(
+                &mut stream,
+                6,
+                Request::ReadAll {
+                    path: "/sub/new.txt".into(),
+                }
+            ),
+            Response::Data(b"rfuse".to_vec())
+        );
+        assert!(origin_path.is_dir());
+        let meta = fs::metadata(origin_path.join("sub/new.txt")).unwrap();
+        assert_eq!(meta.mode() & 0o777, 0o640);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .serve()
+                .arg(context.origin_dir.path())
+                .arg("--bind")
+                .arg(&addr)
+        },
+        closure
+    );
+}
@@ -0,0 +1,35 @@
+use nix::sys::statvfs::statvfs;
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+#[tokio::test]
+async fn test_statfs() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let origin_stat = statvfs(origin_path.as_path()).unwrap();
+        let mount_stat = statvfs(mount_path.as_path()).unwrap();
+
+        // 挂载目录的容量信息来自原始目录所在的文件系统
+        assert_eq!(mount_stat.blocks(), origin_stat.blocks());
+        assert_eq!(mount_stat.files(), origin_stat.files());
+        assert_eq!(mount_stat.fragment_size(), origin_stat.fragment_size());
+        assert!(mount_stat.blocks_available() > 0);
+        assert!(mount_stat.name_max() <= 255);
+        assert!(mount_stat.name_max() > 0);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    os::unix::fs::symlink,
+    path::PathBuf,
+};
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+#[tokio::test]
+async fn test_symlink_origin() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 挂载前在原始目录中创建文件和软链接
+    let mut test_file_origin = origin_path.clone();
+    test_file_origin.push("test_symlink.txt");
+    File::create(&test_file_origin)
+        .unwrap()
+        .write_all(b"Hello, World!")
+        .unwrap();
+    let mut test_link_origin = origin_path.clone();
+    test_link_origin.push("test_symlink_link");
+    symlink("test_symlink.txt", &test_link_origin).unwrap();
+    // 指向不存在文件的软链接
+    let mut test_dangling_origin = origin_path.clone();
+    test_dangling_origin.push("test_symlink_dangling");
+    symlink("not_exist.txt", &test_dangling_origin).unwrap();
+
+    let closure = || {
+        let mut test_link_mount = mount_path.clone();
+        test_link_mount.push("test_symlink_link");
+        let meta = fs::symlink_metadata(&test_link_mount).unwrap();
+        assert!(meta.file_type().is_symlink());
+        assert_eq!(
+            fs::read_link(&test_link_mount).unwrap(),
+            PathBuf::from("test_symlink.txt")
+        );
+        // 通过软链接读取文件内容
+        assert_eq!(fs::read(&test_link_mount).unwrap(), b"Hello, World!");
+
+        let mut test_dangling_mount = mount_path.clone();
+        test_dangling_mount.push("test_symlink_dangling");
+        assert!(fs::symlink_metadata(&test_dangling_mount)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_link(&test_dangling_mount).unwrap(),
+            PathBuf::from("not_exist.txt")
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_symlink_mount() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_symlink.txt");
+        File::create(&test_file_mount)
+            .unwrap()
+            .write_all(b"rfuse")
+            .unwrap();
+
+        // 在挂载目录中创建软链接
+        let mut test_link_mount = mount_path.clone();
+        test_link_mount.push("test_symlink_link");
+        symlink("test_symlink.txt", &test_link_mount).unwrap();
+        assert_eq!(
+            fs::read_link(&test_link_mount).unwrap(),
+            PathBuf::from("test_symlink.txt")
+        );
+        assert_eq!(fs::read(&test_link_mount).unwrap(), b"rfuse");
+
+        // 原始目录中也是软链接
+        let mut test_link_origin = origin_path.clone();
+        test_link_origin.push("test_symlink_link");
+        assert!(fs::symlink_metadata(&test_link_origin)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_link(&test_link_origin).unwrap(),
+            PathBuf::from("test_symlink.txt")
+        );
+
+        // 删除软链接不影响原文件
+        fs::remove_file(&test_link_mount).unwrap();
+        assert!(fs::symlink_metadata(&test_link_origin).is_err());
+        assert_eq!(fs::read(&test_file_mount).unwrap(), b"rfuse");
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
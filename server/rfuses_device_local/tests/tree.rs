@@ -0,0 +1,203 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::ffi::OsStrExt,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use fuser::FUSE_ROOT_ID;
+use rfuse_core::{
+    notify::{Invalidation, OriginChange},
+    shared_fs::{consume_origin_changes, SharedFS},
+    sys_fs::{CacheOptions, RFuseFS},
+};
+use rfuses_device_local::{
+    init_fs::user_defined_init_fs, local_fs::LocalFS, notify_loop::notify_loop,
+};
+
+// 不挂载, 直接使用源目录初始化 inode 表
+fn local_fs(origin: &std::path::Path) -> RFuseFS {
+    let mut rfs = RFuseFS::new(
+        "rfuse".to_string(),
+        CacheOptions::default(),
+        true,
+        origin.to_path_buf(),
+        Box::new(user_defined_init_fs),
+        Box::new(LocalFS),
+    );
+    rfs.re_init_fs();
+    rfs
+}
+
+// 读取所有文件夹
+fn load_all(rfs: &mut RFuseFS) {
+    let mut dirs = vec![FUSE_ROOT_ID];
+    while let Some(dir) = dirs.pop() {
+        rfs.ensure_loaded(dir).unwrap();
+        let inode = rfs.get_inode(dir).unwrap();
+        let children: Vec<u64> = inode
+            .entries
+            .children()
+            .filter(|ino| rfs.get_inode(*ino).unwrap().is_dir())
+            .collect();
+        dirs.extend(children);
+    }
+}
+
+#[test]
+fn test_tree_init() {
+    let origin = tempfile::tempdir().unwrap();
+    // 名字和所在路径中的文件夹同名
+    fs::create_dir_all(origin.path().join("same/same")).unwrap();
+    fs::write(origin.path().join("same/same/same"), "rfuse").unwrap();
+    fs::create_dir_all(origin.path().join("a/b/c")).unwrap();
+    fs::write(origin.path().join("a/b/c/file.txt"), "rfuse").unwrap();
+    fs::hard_link(
+        origin.path().join("a/b/c/file.txt"),
+        origin.path().join("a/link.txt"),
+    )
+    .unwrap();
+
+    let mut rfs = local_fs(origin.path());
+    load_all(&mut rfs);
+    rfs.verify_tree().unwrap();
+
+    let dir = rfs.resolve_path("/same/same").unwrap();
+    let file = rfs
+        .get_inode(rfs.resolve_path("/same/same/same").unwrap())
+        .unwrap();
+    assert_eq!(file.parent_ino, dir);
+    assert_eq!(file.attr.path, "/same/same/");
+    assert_eq!(
+        rfs.get_inode(dir).unwrap().parent_ino,
+        rfs.resolve_path("/same").unwrap()
+    );
+
+    // 硬链接指向同一个 inode
+    assert_eq!(
+        rfs.resolve_path("/a/b/c/file.txt"),
+        rfs.resolve_path("/a/link.txt")
+    );
+}
+
+#[test]
+fn test_tree_origin_change() {
+    let origin = tempfile::tempdir().unwrap();
+    let path = origin.path();
+    fs::create_dir_all(path.join("dir/sub")).unwrap();
+    fs::write(path.join("dir/sub/file.txt"), "rfuse").unwrap();
+    fs::write(path.join("top.txt"), "rfuse").unwrap();
+
+    let mut rfs = local_fs(path);
+    load_all(&mut rfs);
+    let file = rfs.resolve_path("/dir/sub/file.txt").unwrap();
+
+    // 移动文件夹, 子孙的路径跟随改变
+    fs::create_dir(path.join("moved")).unwrap();
+    rfs.apply_origin_change(OriginChange::Create("/moved".into()));
+    rfs.ensure_loaded(rfs.resolve_path("/moved").unwrap())
+        .unwrap();
+    fs::rename(path.join("dir"), path.join("moved/dir")).unwrap();
+    rfs.apply_origin_change(OriginChange::Rename {
+        from: "/dir".into(),
+        to: "/moved/dir".into(),
+    });
+    rfs.verify_tree().unwrap();
+    assert_eq!(rfs.resolve_path("/moved/dir/sub/file.txt"), Some(file));
+    assert_eq!(rfs.get_inode(file).unwrap().attr.path, "/moved/dir/sub/");
+
+    // 删除和新建文件
+    fs::remove_file(path.join("top.txt")).unwrap();
+    rfs.apply_origin_change(OriginChange::Remove("/top.txt".into()));
+    fs::write(path.join("moved/new.txt"), "rfuse").unwrap();
+    rfs.apply_origin_change(OriginChange::Create("/moved/new.txt".into()));
+    rfs.verify_tree().unwrap();
+    assert!(rfs.resolve_path("/top.txt").is_none());
+    assert!(rfs.resolve_path("/moved/new.txt").is_some());
+
+    // 删除整个文件夹
+    fs::remove_dir_all(path.join("moved")).unwrap();
+    rfs.apply_origin_change(OriginChange::Remove("/moved".into()));
+    rfs.verify_tree().unwrap();
+    assert!(rfs.get_inode(file).is_none());
+}
+
+#[test]
+fn test_tree_non_utf8_name() {
+    let origin = tempfile::tempdir().unwrap();
+    let path = origin.path();
+    let dir = OsStr::from_bytes(b"d\xe9j\xe0");
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs::create_dir(path.join(dir)).unwrap();
+    fs::write(path.join(dir).join(name), "rfuse").unwrap();
+
+    let mut rfs = local_fs(path);
+    load_all(&mut rfs);
+    rfs.verify_tree().unwrap();
+    let file_path = std::path::Path::new("/").join(dir).join(name);
+    let file = rfs.resolve_path(&file_path).unwrap();
+    assert_eq!(rfs.get_inode(file).unwrap().attr.name, name);
+
+    // 源目录中改成另一个非 UTF-8 的名字
+    let new_name = OsStr::from_bytes(b"\xff.txt");
+    fs::rename(path.join(dir).join(name), path.join(new_name)).unwrap();
+    rfs.apply_origin_change(OriginChange::Rename {
+        from: file_path.into_os_string(),
+        to: std::path::Path::new("/").join(new_name).into_os_string(),
+    });
+    rfs.verify_tree().unwrap();
+    assert_eq!(
+        rfs.resolve_path(std::path::Path::new("/").join(new_name)),
+        Some(file)
+    );
+    assert_eq!(rfs.get_inode(file).unwrap().attr.path, "/");
+}
+
+#[tokio::test]
+async fn test_tree_origin_consumer() {
+    let origin = tempfile::tempdir().unwrap();
+    let path = origin.path().to_path_buf();
+    fs::write(path.join("file.txt"), "rfuse").unwrap();
+
+    let mut rfs = local_fs(&path);
+    load_all(&mut rfs);
+    let file = rfs.resolve_path("/file.txt").unwrap();
+    let (invalidation_send, invalidation_recv) = mpsc::channel();
+    rfs.watch_origin(invalidation_send);
+    let rfs = SharedFS::new(rfs);
+    let (change_send, change_recv) = mpsc::channel();
+    let consumer = rfs.clone();
+    thread::spawn(move || consume_origin_changes(consumer, change_recv));
+    notify_loop(change_send, path.clone()).await.unwrap();
+
+    // 源目录的改动在单独的线程中应用, 不需要先访问挂载中的文件
+    fs::write(path.join("file.txt"), "rfuse rfuse").unwrap();
+    fs::write(path.join("new.txt"), "rfuse").unwrap();
+    let expected = [
+        Invalidation::Inode { ino: file },
+        Invalidation::Entry {
+            parent: FUSE_ROOT_ID,
+            name: OsString::from("new.txt"),
+        },
+    ];
+    let invalidations = tokio::task::spawn_blocking(move || {
+        let mut invalidations = Vec::new();
+        while !expected.iter().all(|i| invalidations.contains(i)) {
+            match invalidation_recv.recv_timeout(Duration::from_secs(5)) {
+                Ok(invalidation) => invalidations.push(invalidation),
+                Err(_) => return Err(invalidations),
+            }
+        }
+        Ok(invalidations)
+    })
+    .await
+    .unwrap();
+    assert!(invalidations.is_ok(), "{:?}", invalidations);
+
+    let rfs = rfs.lock();
+    rfs.verify_tree().unwrap();
+    assert_eq!(rfs.get_inode(file).unwrap().attr.size, 11);
+    assert!(rfs.resolve_path("/new.txt").is_some());
+}
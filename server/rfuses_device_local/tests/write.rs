@@ -142,3 +142,262 @@ async fn test_write_file_at_offset() {
         closure
     );
 }
+
+#[tokio::test]
+async fn test_write_large_file() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let test_file = "test_write_large.txt";
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push(test_file);
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push(test_file);
+        // 超过内核单次写入的上限, 会被拆分成多次 write 请求
+        const FILE_SIZE: usize = 4 * 1024 * 1024; // 4M
+
+        let mut content = vec![0u8; FILE_SIZE];
+        rand::thread_rng().fill(&mut content[..]);
+        let mut file = File::create(&test_file_mount).unwrap();
+        file.write_all(&content).unwrap();
+        file.flush().unwrap();
+        drop(file);
+
+        // 前面写入的内容不能被后面的写入覆盖
+        assert_eq!(fs::read(&test_file_origin).unwrap(), content);
+        assert_eq!(fs::read(&test_file_mount).unwrap(), content);
+        assert_eq!(
+            fs::metadata(&test_file_mount).unwrap().size(),
+            FILE_SIZE as u64
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_write_random_offset() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let test_file = "test_write_random_offset.txt";
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push(test_file);
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push(test_file);
+        const FILE_SIZE: usize = 256 * 1024;
+
+        let mut rng = rand::thread_rng();
+        let mut expected = vec![0u8; FILE_SIZE];
+        rng.fill(&mut expected[..]);
+        let mut file = File::create(&test_file_mount).unwrap();
+        file.write_all(&expected).unwrap();
+        drop(file);
+
+        // 随机位置覆盖写入, 同时在内存中记录期望的内容
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(&test_file_mount)
+            .unwrap();
+        for _ in 0..64 {
+            let len = rng.gen_range(1..4096);
+            let offset = rng.gen_range(0..FILE_SIZE - len);
+            let mut chunk = vec![0u8; len];
+            rng.fill(&mut chunk[..]);
+            file.write_all_at(&chunk, offset as u64).unwrap();
+            expected[offset..offset + len].copy_from_slice(&chunk);
+        }
+
+        // 在文件末尾之后写入, 中间的空洞用 0 填充
+        let tail = b"rfuse";
+        let tail_offset = FILE_SIZE + 100;
+        file.write_all_at(tail, tail_offset as u64).unwrap();
+        expected.resize(tail_offset, 0);
+        expected.extend_from_slice(tail);
+        drop(file);
+
+        assert_eq!(fs::read(&test_file_origin).unwrap(), expected);
+        assert_eq!(fs::read(&test_file_mount).unwrap(), expected);
+        assert_eq!(
+            fs::metadata(&test_file_mount).unwrap().size(),
+            expected.len() as u64
+        );
+
+        // 截断只通过 setattr 修改大小实现
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(&test_file_mount)
+            .unwrap();
+        file.set_len(1024).unwrap();
+        drop(file);
+        assert_eq!(fs::read(&test_file_origin).unwrap(), expected[..1024]);
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_write_open_handle() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_write_handle.txt");
+        let mut file = File::create(&test_file_mount).unwrap();
+        file.write_all(b"Hello, ").unwrap();
+        // fsync 之后原始文件中可以读到写入的内容
+        file.sync_all().unwrap();
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push("test_write_handle.txt");
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"Hello, ");
+
+        // 句柄在重命名之后仍然有效
+        let mut test_rename_mount = mount_path.clone();
+        test_rename_mount.push("test_write_handle_rename.txt");
+        fs::rename(&test_file_mount, &test_rename_mount).unwrap();
+        file.write_all(b"World!").unwrap();
+        file.sync_data().unwrap();
+        drop(file);
+
+        let mut test_rename_origin = origin_path.clone();
+        test_rename_origin.push("test_write_handle_rename.txt");
+        assert_eq!(fs::read(&test_rename_origin).unwrap(), b"Hello, World!");
+        assert_eq!(fs::read(&test_rename_mount).unwrap(), b"Hello, World!");
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_write_truncate_append() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_write_truncate.txt");
+        let mut test_file_origin = origin_path.clone();
+        test_file_origin.push("test_write_truncate.txt");
+        fs::write(&test_file_mount, b"Hello, World!").unwrap();
+
+        // O_TRUNC 打开时清空文件, 对应 shell 中的 `>`
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&test_file_mount)
+            .unwrap();
+        file.write_all(b"rfuse").unwrap();
+        drop(file);
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"rfuse");
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().size(), 5);
+
+        // O_APPEND 打开时写到文件末尾, 对应 shell 中的 `>>`
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&test_file_mount)
+            .unwrap();
+        file.write_all(b", append").unwrap();
+        file.write_all(b"!").unwrap();
+        drop(file);
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"rfuse, append!");
+        assert_eq!(fs::read(&test_file_mount).unwrap(), b"rfuse, append!");
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_write_kernel_cache() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        let test_file_mount = mount_path.join("test_write_cache.txt");
+        let test_file_origin = origin_path.join("test_write_cache.txt");
+        fs::write(&test_file_mount, b"Hello").unwrap();
+
+        // 追加写的偏移由内核计算, 只写打开的文件也可以被内核读取来补齐页
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&test_file_mount)
+            .unwrap();
+        file.write_all(b", World").unwrap();
+        file.write_all(b"!").unwrap();
+        drop(file);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(&test_file_mount)
+            .unwrap();
+        file.write_all_at(b"h", 0).unwrap();
+        drop(file);
+
+        // close 时内核把缓存中的数据写回源目录
+        assert_eq!(fs::read(&test_file_origin).unwrap(), b"hello, World!");
+        assert_eq!(fs::read(&test_file_mount).unwrap(), b"hello, World!");
+        assert_eq!(fs::metadata(&test_file_mount).unwrap().size(), 13);
+
+        // 缓存的目录项在删除之后失效
+        fs::remove_file(&test_file_mount).unwrap();
+        assert!(!test_file_mount.exists());
+        assert!(!test_file_origin.exists());
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--attr-ttl")
+                .arg("1")
+                .arg("--entry-ttl")
+                .arg("1")
+                .arg("--negative-ttl")
+                .arg("1")
+                .arg("--no-direct-io")
+                .arg("--keep-cache")
+                .arg("--writeback-cache")
+        },
+        closure
+    );
+}
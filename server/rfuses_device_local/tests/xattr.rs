@@ -0,0 +1,205 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+};
+
+use common::{rfuses_spawn_run, run_command_with_status, TestContext};
+
+mod common;
+
+fn c_path(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_bytes()).unwrap()
+}
+
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let name = CString::new(name).unwrap();
+    let ret = unsafe {
+        libc::setxattr(
+            c_path(path).as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn get_xattr(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+    let name = CString::new(name).unwrap();
+    let mut buf = vec![0u8; 256];
+    let ret = unsafe {
+        libc::getxattr(
+            c_path(path).as_ptr(),
+            name.as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(ret as usize);
+    Ok(buf)
+}
+
+fn list_xattr(path: &Path) -> io::Result<Vec<String>> {
+    let mut buf = vec![0u8; 1024];
+    let ret = unsafe {
+        libc::listxattr(
+            c_path(path).as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buf.truncate(ret as usize);
+    Ok(buf
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).to_string())
+        .collect())
+}
+
+fn remove_xattr(path: &Path, name: &str) -> io::Result<()> {
+    let name = CString::new(name).unwrap();
+    let ret = unsafe { libc::removexattr(c_path(path).as_ptr(), name.as_ptr()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_xattr() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    // 挂载前在原始文件上设置扩展属性
+    let mut test_file_origin = origin_path.clone();
+    test_file_origin.push("test_xattr.txt");
+    File::create(&test_file_origin)
+        .unwrap()
+        .write_all(b"rfuse")
+        .unwrap();
+    set_xattr(&test_file_origin, "user.rfuse.origin", b"origin").unwrap();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_xattr.txt");
+        assert_eq!(
+            get_xattr(&test_file_mount, "user.rfuse.origin").unwrap(),
+            b"origin"
+        );
+
+        // 在挂载目录中设置扩展属性
+        set_xattr(&test_file_mount, "user.rfuse.mount", b"mount").unwrap();
+        assert_eq!(
+            get_xattr(&test_file_origin, "user.rfuse.mount").unwrap(),
+            b"mount"
+        );
+        let mut names = list_xattr(&test_file_mount).unwrap();
+        names.retain(|name| name.starts_with("user.rfuse"));
+        names.sort();
+        assert_eq!(names, vec!["user.rfuse.mount", "user.rfuse.origin"]);
+
+        // 删除扩展属性
+        remove_xattr(&test_file_mount, "user.rfuse.mount").unwrap();
+        assert_eq!(
+            get_xattr(&test_file_mount, "user.rfuse.mount")
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::ENODATA)
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_xattr_mount_root() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+    let origin_path = context.origin_dir.to_owned();
+
+    let closure = || {
+        // 挂载根目录上的扩展属性写入源目录本身
+        set_xattr(&mount_path, "user.rfuse.root", b"root").unwrap();
+        assert_eq!(get_xattr(&mount_path, "user.rfuse.root").unwrap(), b"root");
+        assert_eq!(get_xattr(&origin_path, "user.rfuse.root").unwrap(), b"root");
+        assert!(list_xattr(&mount_path)
+            .unwrap()
+            .contains(&"user.rfuse.root".to_string()));
+        remove_xattr(&mount_path, "user.rfuse.root").unwrap();
+        assert_eq!(
+            get_xattr(&origin_path, "user.rfuse.root")
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::ENODATA)
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+        },
+        closure
+    );
+}
+
+#[tokio::test]
+async fn test_no_xattr() {
+    let context = TestContext::new();
+
+    let mount_path = context.mount_dir.to_owned();
+
+    let closure = || {
+        let mut test_file_mount = mount_path.clone();
+        test_file_mount.push("test_xattr.txt");
+        File::create(&test_file_mount).unwrap();
+
+        // 禁用扩展属性后返回不支持
+        assert_eq!(
+            set_xattr(&test_file_mount, "user.rfuse", b"rfuse")
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EOPNOTSUPP)
+        );
+        assert_eq!(
+            get_xattr(&test_file_mount, "user.rfuse")
+                .unwrap_err()
+                .raw_os_error(),
+            Some(libc::EOPNOTSUPP)
+        );
+    };
+    rfuses_spawn_run!(
+        {
+            context
+                .link()
+                .arg(context.origin_dir.path())
+                .arg(context.mount_dir.path())
+                .arg("--no-xattr")
+        },
+        closure
+    );
+}